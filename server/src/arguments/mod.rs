@@ -0,0 +1,209 @@
+use configuration::Overrides;
+use parser::{ Channel, Format };
+
+//
+pub const USAGE: &str = "\
+usage: bmq5-server [options] [run] <configuration>
+       bmq5-server [options] check <configuration>
+       bmq5-server [options] list-clients <configuration>
+       bmq5-server [options] send <configuration> <client|tag|group> <command>
+       bmq5-server [options] test <configuration> <client> <spec>
+       bmq5-server [options] fmt <file>
+
+commands:
+    run                     serve the clients until SIGINT or SIGTERM, SIGHUP reloads the configuration
+    check                   parse the configuration without opening any device
+    list-clients            print the mode, channel, binary and device of every client
+    send                    write a command to the bootloader console of every matching client
+    test                    run a client against a spec and report the outcome
+    fmt                     print a configuration, client, event or script file in a normalized layout
+
+options:
+    -c, --client <name>     only enable the clients with this name, tag or group, may be repeated
+        --channel <name>    serve every client from 'stable', 'beta', 'nightly' or 'none'
+    -v, --verbose           print requests and parsed files, twice to print all traffic
+        --to <format>       let fmt convert to 'keywords', 'json' or 'toml'
+    -w, --write             let fmt replace the file instead of printing it
+    -h, --help              print this message
+    -V, --version           print the version
+
+formats:
+    files are read as keywords, json or toml, going by their extension or their first line. only a
+    subset of toml is understood: top level keys that are set once, single line strings, numbers,
+    booleans, arrays and inline tables. a 'calls' array of tables with a single key lists calls in
+    order, for keywords that are called again after another one
+";
+
+// what the server was asked to do
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Check,
+    ListClients,
+    Send(String, String),
+    Test(String, String),
+    Format(Option<Format>, bool),
+}
+
+// reasons to stop before a configuration is even read
+#[derive(Debug, PartialEq)]
+pub enum Usage {
+    Help,
+    Version,
+    Error(String),
+}
+
+//
+pub struct Arguments {
+    pub command:            Command,
+    pub configuration_path: String,
+    pub overrides:          Overrides,
+    pub verbosity:          usize,
+}
+
+// take the value of an option, either from '--option=value' or from the next argument
+fn value<'a, I: Iterator<Item = &'a String>>(option: &str, inline: Option<&str>, arguments: &mut I) -> Result<String, Usage> {
+    match inline {
+        Some(value) => Ok(String::from(value)),
+        None        => arguments.next().cloned().ok_or_else(| | Usage::Error(format!("option '{}' requires a value", option))),
+    }
+}
+
+// make sure an option that is only a switch wasn't given a value
+fn switch(option: &str, inline: Option<&str>) -> Result<(), Usage> {
+    match inline {
+        Some(_) => Err(Usage::Error(format!("option '{}' takes no value", option))),
+        None    => Ok(()),
+    }
+}
+
+//
+impl Arguments {
+
+    // parse the command line without the program name, options may appear anywhere
+    pub fn parse(arguments: &[String]) -> Result<Self, Usage> {
+        let mut positional = Vec::new();
+        let mut overrides = Overrides::new();
+        let mut verbosity = 0;
+        let mut options = true;
+        let mut target = None;
+        let mut write = false;
+
+        //
+        let mut arguments = arguments.iter();
+        while let Some(argument) = arguments.next() {
+            if !options || !argument.starts_with('-') || argument == "-" {
+                positional.push(argument.clone());
+                continue;
+            }
+
+            // long options may carry their value after a '='
+            let (option, inline) = match argument.find('=') {
+                Some(index) if argument.starts_with("--")   => (&argument[..index], Some(&argument[index + 1..])),
+                _                                           => (argument.as_str(), None),
+            };
+
+            //
+            match option {
+                "--"                => options = false,
+                "-h" | "--help"     => return Err(Usage::Help),
+                "-V" | "--version"  => return Err(Usage::Version),
+                "-c" | "--client"   => overrides.clients.push(value(option, inline, &mut arguments)?),
+
+                //
+                "-w" | "--write"    => {
+                    switch(option, inline)?;
+                    write = true;
+                },
+
+                //
+                "--to"              => {
+                    let format = value(option, inline, &mut arguments)?;
+                    match Format::from_name(&format) {
+                        Some(format)    => target = Some(format),
+                        None            => return Err(Usage::Error(format!("invalid format '{}'. valid options are 'keywords', 'json' or 'toml'", format))),
+                    }
+                },
+
+                //
+                "-v" | "--verbose"  => match inline {
+                    Some(level) => verbosity = level.parse().map_err(| _ | Usage::Error(format!("invalid verbosity level '{}'", level)))?,
+                    None        => verbosity += 1,
+                },
+
+                //
+                "--channel"         => {
+                    let channel = value(option, inline, &mut arguments)?;
+                    match Channel::from_name(&channel) {
+                        Some(channel)   => overrides.channel = Some(channel),
+                        None            => return Err(Usage::Error(format!("invalid channel '{}'. valid options are 'stable', 'beta', 'nightly' or 'none'", channel))),
+                    }
+                },
+
+                // repeated short switch like -vv
+                short if short.len() > 2 && !short.starts_with("--") && short[1..].chars().all(| character | character == 'v') => {
+                    switch(option, inline)?;
+                    verbosity += short.len() - 1;
+                },
+
+                //
+                _                   => return Err(Usage::Error(format!("unknown option '{}'", option))),
+            }
+        }
+
+        // without a command the first argument is the configuration
+        let mut positional = positional.into_iter();
+        let first = positional.next().ok_or_else(| | Usage::Error(String::from("no configuration file specified")))?;
+        let configuration_path = match first.as_str() {
+            "run" | "check" | "list-clients" | "send" | "test" | "fmt" => positional.next().ok_or_else(| | Usage::Error(String::from("no configuration file specified")))?,
+            _                                                           => first.clone(),
+        };
+
+        //
+        let command = match first.as_str() {
+            "check"         => Command::Check,
+            "list-clients"  => Command::ListClients,
+
+            //
+            "send"          => {
+                let client = positional.next().ok_or_else(| | Usage::Error(String::from("no client, tag or group specified")))?;
+                let words: Vec<String> = positional.by_ref().collect();
+                if words.is_empty() {
+                    return Err(Usage::Error(String::from("no command specified")));
+                }
+                Command::Send(client, words.join(" "))
+            },
+
+            // only the client under test is initialized and the controllers are left alone
+            "test"          => {
+                let client = positional.next().ok_or_else(| | Usage::Error(String::from("no test client specified")))?;
+                let spec = positional.next().ok_or_else(| | Usage::Error(String::from("no test spec specified")))?;
+                overrides.clients = vec![client.clone()];
+                overrides.controllers = false;
+                Command::Test(client, spec)
+            },
+
+            //
+            "fmt"           => Command::Format(target, write),
+            _               => Command::Run,
+        };
+
+        // the formatter options make no sense for anything else
+        if !matches!(command, Command::Format(..)) && (target.is_some() || write) {
+            return Err(Usage::Error(String::from("options '--to' and '--write' are only valid for 'fmt'")));
+        }
+
+        //
+        if let Some(argument) = positional.next() {
+            return Err(Usage::Error(format!("unexpected argument '{}'", argument)));
+        }
+
+        //
+        Ok(Self {
+            command:            command,
+            configuration_path: configuration_path,
+            overrides:          overrides,
+            verbosity:          verbosity,
+        })
+    }
+}
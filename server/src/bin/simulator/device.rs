@@ -0,0 +1,337 @@
+use bmq5::client::protocol::{ Request, Events };
+use std::sync::mpsc::Receiver;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::fs;
+
+// something that happened on either side of the board
+pub enum Input {
+    Byte(u8),
+    Command(String),
+    Closed,
+}
+
+// what the board is currently running
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Bootloader,
+    Kernel,
+}
+
+// emulation of the serial side of the bootloader and a minimal kernel
+pub struct Device {
+    receiver:   Receiver<Input>,
+    pending:    VecDeque<String>,
+    writer:     Box<dyn Write>,
+    command:    String,
+    kernel:     Vec<u8>,
+    loaded:     bool,
+    state:      State,
+    events:     Events,
+}
+
+//
+impl Device {
+
+    //
+    pub fn new(receiver: Receiver<Input>, writer: Box<dyn Write>) -> Self {
+        Self {
+            receiver:   receiver,
+            pending:    VecDeque::new(),
+            writer:     writer,
+            command:    String::new(),
+            kernel:     Vec::new(),
+            loaded:     false,
+            state:      State::Bootloader,
+            events:     Events::Characters,
+        }
+    }
+
+    // log a character the same way serial_log_character does
+    fn log_character(&mut self, character: u8) {
+        if character == b'\n' {
+            self.log_character(b'\r');
+        }
+        if self.writer.write_all(&[character]).is_err() {
+            println!("[ simulator ] server closed the serial connection");
+            std::process::exit(1);
+        }
+    }
+
+    //
+    fn log_string(&mut self, string: &str) {
+        self.log_bytes(string.as_bytes());
+    }
+
+    //
+    fn log_bytes(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(| character | self.log_character(*character));
+    }
+
+    // wait for the next byte from the server, commands that arrive in the meantime are queued
+    fn read_character(&mut self) -> u8 {
+        loop {
+            match self.receiver.recv() {
+                Ok(Input::Byte(byte))       => return byte,
+                Ok(Input::Command(command)) => self.pending.push_back(command),
+                Ok(Input::Closed) | Err(_)  => {
+                    println!("[ simulator ] server closed the serial connection");
+                    std::process::exit(0);
+                },
+            }
+        }
+    }
+
+    // read the payload that follows a '!', None if the server answered with anything else
+    fn read_transmission(&mut self) -> Option<Vec<u8>> {
+        if self.read_character() != b'!' {
+            return None;
+        }
+
+        //
+        let mut size = 0;
+        for _ in 0..4 {
+            size = size << 8 | self.read_character() as usize;
+        }
+        Some((0..size).map(| _ | self.read_character()).collect())
+    }
+
+    // serial_load_kernel
+    fn load_kernel(&mut self) -> bool {
+        match self.read_transmission() {
+            Some(kernel)    => {
+                println!("[ simulator ] received kernel of 0x{:x} bytes", kernel.len());
+                self.kernel = kernel;
+                self.loaded = true;
+                true
+            },
+            None            => false,
+        }
+    }
+
+    // serial_entry
+    fn reboot(&mut self) {
+        println!("[ simulator ] booting");
+        self.state = State::Bootloader;
+        self.log_bytes(&Request::TransmitKernel.encode());
+        match self.load_kernel() {
+            true    => self.boot_kernel(),
+            false   => self.clear(),
+        }
+    }
+
+    // boot_kernel
+    fn boot_kernel(&mut self) {
+        println!("[ simulator ] kernel started");
+        self.state = State::Kernel;
+        let banner = format!("kernel running, 0x{:x} bytes\n", self.kernel.len());
+        self.log_string(&banner);
+    }
+
+    // serial_clear
+    fn clear(&mut self) {
+        self.log_string("\n> ");
+        self.command.clear();
+    }
+
+    // serial_interface
+    fn interface(&mut self, character: u8) {
+        if character == 13 {
+            return self.evaluate();
+        }
+        if self.command.len() < 63 {
+            self.command.push(character as char);
+            self.log_character(character);
+        }
+    }
+
+    // serial_evaluate_command
+    fn evaluate(&mut self) {
+        self.log_character(b' ');
+        println!("[ simulator ] command '{}'", self.command);
+        match self.command.as_ref() {
+
+            //
+            "load"      => {
+                self.log_character(b'!');
+                self.load_kernel();
+            },
+
+            //
+            "dump"      => {
+                if !self.loaded {
+                    self.log_string("-> no kernel image loaded");
+                } else {
+                    self.log_character(b'!');
+                    let lines: Vec<String> = self.kernel.chunks(32).map(| line | {
+                        line.iter().map(| byte | format!("{:02x}", byte)).collect::<Vec<String>>().join(" ")
+                    }).collect();
+                    for line in lines {
+                        self.log_character(b'\n');
+                        self.log_string(&line);
+                    }
+                }
+            },
+
+            //
+            "boot"      => {
+                if !self.loaded {
+                    self.log_string("-> no kernel image loaded");
+                } else {
+                    self.log_string("!\n");
+                    return self.boot_kernel();
+                }
+            },
+
+            //
+            "reboot"    => {
+                self.log_string("!\n");
+                return self.reboot();
+            },
+
+            //
+            "command"   => self.log_character(b'!'),
+
+            //
+            "registers" => {
+                self.log_character(b'!');
+                for register in 0..31 {
+                    let value = if register == 29 { self.kernel.len() } else { 0 };
+                    let line = format!("\n{:02x} ................ {:016x}", register, value);
+                    self.log_string(&line);
+                }
+            },
+
+            //
+            "help"      => {
+                self.log_character(b'!');
+                self.log_string(concat!(
+                    "\nload .............. load the kernel",
+                    "\ndump .............. display a hexdump of the loaded kernel",
+                    "\nboot .............. boot the loaded kernel",
+                    "\nreboot ............ reboots the device",
+                    "\ncommand ........... print the command line passed to the bootloader",
+                    "\nregisters ......... display the contents of all registers",
+                    "\nhelp .............. list all avalible commands"));
+            },
+
+            //
+            _           => {},
+        }
+        self.clear();
+    }
+
+    // the kernel receives events as modifier and character and echoes the characters, key events come as
+    // state, key code, eight bytes of modifiers and character and may be followed by a sequence. axes come as
+    // their code and position
+    fn event(&mut self, first: u8) {
+        match self.events {
+            Events::Characters  => {
+                let character = self.read_character();
+                println!("[ simulator ] event {:08b} 0x{:02x}", first, character);
+                self.log_character(character);
+            },
+            Events::Keys if first == 3  => {
+                let length = self.read_character();
+                let sequence: Vec<u8> = (0..length).map(| _ | self.read_character()).collect();
+                println!("[ simulator ] sequence '{}'", sequence.escape_ascii());
+                self.log_bytes(&sequence);
+            },
+            Events::Keys if first == 4  => {
+                let code = (self.read_character() as u16) << 8 | self.read_character() as u16;
+                let value = ((self.read_character() as u16) << 8 | self.read_character() as u16) as i16;
+                println!("[ simulator ] axis {} {}", code, value);
+            },
+            Events::Keys        => {
+                let code = (self.read_character() as u16) << 8 | self.read_character() as u16;
+                let modifiers = (0..8).fold(0u64, | modifiers, _ | modifiers << 8 | self.read_character() as u64);
+                let character = self.read_character();
+                let state = match first {
+                    0   => "up",
+                    1   => "down",
+                    _   => "repeat",
+                };
+                println!("[ simulator ] key {} {} {:08b} 0x{:02x}", code, state, modifiers, character);
+            },
+        }
+    }
+
+    // commands from the simulator user
+    fn command(&mut self, command: &str) {
+        let (name, argument) = match command.find(' ') {
+            Some(index) => (&command[..index], &command[index + 1..]),
+            None        => (command, ""),
+        };
+
+        //
+        match name {
+
+            //
+            "reset"     => self.reboot(),
+
+            //
+            "print"     => {
+                let line = format!("{}\n", argument);
+                self.log_string(&line);
+            },
+
+            //
+            "fetch" if self.state == State::Kernel => {
+                self.log_bytes(&Request::TransmitFile(String::from(argument)).encode());
+                match self.read_transmission() {
+                    Some(file)  => println!("[ simulator ] received '{}' of 0x{:x} bytes", argument, file.len()),
+                    None        => println!("[ simulator ] request for '{}' was rejected", argument),
+                }
+            },
+
+            //
+            "save"      => {
+                match fs::write(argument, &self.kernel) {
+                    Ok(())  => println!("[ simulator ] saved kernel to '{}'", argument),
+                    Err(_)  => println!("[ simulator ] unable to save kernel to '{}'", argument),
+                }
+            },
+
+            // the protocol the server was configured with for this board
+            "events"    => match Events::from_name(argument) {
+                Some(events)    => {
+                    self.events = events;
+                    println!("[ simulator ] event protocol '{}'", argument);
+                },
+                None            => println!("[ simulator ] invalid event protocol '{}'", argument),
+            },
+
+            //
+            "quit"      => std::process::exit(0),
+
+            //
+            _           => println!("[ simulator ] invalid command '{}'", command),
+        }
+    }
+
+    // power on the board and run until the server goes away
+    pub fn run(mut self) -> ! {
+        self.reboot();
+        loop {
+
+            //
+            if let Some(command) = self.pending.pop_front() {
+                self.command(&command);
+                continue;
+            }
+
+            //
+            match self.receiver.recv() {
+                Ok(Input::Command(command)) => self.command(&command),
+                Ok(Input::Byte(byte))       => match self.state {
+                    State::Bootloader   => self.interface(byte),
+                    State::Kernel       => self.event(byte),
+                },
+                Ok(Input::Closed) | Err(_)  => {
+                    println!("[ simulator ] server closed the serial connection");
+                    std::process::exit(0);
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// the field names are spelled out like in the server
+#![allow(clippy::redundant_field_names)]
+
+extern crate libc;
+extern crate bmq5;
+
+mod device;
+mod pty;
+
+use device::{ Device, Input };
+use std::sync::mpsc::{ channel, Sender };
+use std::io::{ BufRead, Read };
+use std::fs::{ File, OpenOptions };
+use std::thread;
+
+// forward everything the server sends to the device
+fn forward_serial(mut source: File, sender: Sender<Input>) {
+    let mut buffer = [0; 256];
+    loop {
+        match source.read(&mut buffer) {
+            Ok(0) | Err(_)  => break,
+            Ok(length)      => buffer[..length].iter().for_each(| byte | { let _ = sender.send(Input::Byte(*byte)); }),
+        }
+    }
+    let _ = sender.send(Input::Closed);
+}
+
+// forward the commands typed into the simulator to the device
+fn forward_commands(sender: Sender<Input>) {
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines().map_while(Result::ok) {
+        let _ = sender.send(Input::Command(String::from(line.trim())));
+    }
+}
+
+// main
+fn main() {
+
+    // get command line arguments
+    let mut parameters: Vec<String> = std::env::args().rev().collect();
+    parameters.pop();
+
+    // the pseudo terminal has to stay open for as long as the simulator runs
+    let mut terminal: Option<pty::Pty> = None;
+    let mut pipes: Option<(File, File)> = None;
+
+    //
+    let (reader, writer) = match parameters.pop().as_deref() {
+
+        // bmq5-simulator pty
+        Some("pty")     => {
+            let pty = pty::open();
+            println!("[ simulator ] serial device is '{}'", pty.path);
+            let reader = pty.master.try_clone().expect("[ simulator ] unable to share pseudo terminal");
+            let writer = pty.master.try_clone().expect("[ simulator ] unable to share pseudo terminal");
+            terminal = Some(pty);
+            (reader, writer)
+        },
+
+        // bmq5-simulator pipe <input> <output>
+        Some("pipe")    => {
+            let input = parameters.pop().expect("[ simulator ] no input pipe specified");
+            let output = parameters.pop().expect("[ simulator ] no output pipe specified");
+            let reader = File::open(&input).unwrap_or_else(| _ | panic!("[ simulator ] unable to open '{}'", input));
+            let writer = OpenOptions::new().write(true).open(&output).unwrap_or_else(| _ | panic!("[ simulator ] unable to open '{}'", output));
+
+            // keep both ends of the pipes open, otherwise the board goes away every time the server reopens them
+            let input_writer = OpenOptions::new().write(true).open(&input).unwrap_or_else(| _ | panic!("[ simulator ] unable to open '{}'", input));
+            let output_reader = File::open(&output).unwrap_or_else(| _ | panic!("[ simulator ] unable to open '{}'", output));
+            pipes = Some((input_writer, output_reader));
+            (reader, writer)
+        },
+
+        //
+        _               => panic!("[ simulator ] usage: bmq5-simulator pty | bmq5-simulator pipe <input> <output>"),
+    };
+
+    //
+    let (sender, receiver) = channel();
+    let serial_sender = sender.clone();
+    thread::spawn(move || forward_serial(reader, serial_sender));
+    thread::spawn(move || forward_commands(sender));
+
+    //
+    let _terminal = terminal;
+    let _pipes = pipes;
+    Device::new(receiver, Box::new(writer)).run();
+}
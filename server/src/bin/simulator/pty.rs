@@ -0,0 +1,50 @@
+use libc;
+use std::ffi::CStr;
+use std::fs::File;
+use std::os::unix::io::FromRawFd;
+
+// pseudo terminal in raw mode
+pub struct Pty {
+    pub master:     File,
+    pub path:       String,
+    _slave:         File,
+}
+
+// open a pseudo terminal, the server is pointed to the slave side
+pub fn open() -> Pty {
+    unsafe {
+
+        //
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if master < 0 || libc::grantpt(master) != 0 || libc::unlockpt(master) != 0 {
+            panic!("[ simulator ] unable to open pseudo terminal");
+        }
+
+        //
+        let name = libc::ptsname(master);
+        if name.is_null() {
+            panic!("[ simulator ] unable to get pseudo terminal name");
+        }
+        let path = CStr::from_ptr(name).to_string_lossy().into_owned();
+
+        // the server expects a raw byte stream, so disable echo and line editing
+        let mut attributes: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(master, &mut attributes) == 0 {
+            libc::cfmakeraw(&mut attributes);
+            libc::tcsetattr(master, libc::TCSANOW, &attributes);
+        }
+
+        // keep the slave side open, otherwise reading from the master fails every time the server closes it
+        let slave = match File::open(&path) {
+            Ok(slave)   => slave,
+            Err(_)      => panic!("[ simulator ] unable to open '{}'", path),
+        };
+
+        //
+        Pty {
+            master:     File::from_raw_fd(master),
+            path:       path,
+            _slave:     slave,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use regex::Regex;
+use std::sync::{ Mutex, Condvar };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::time::{ Duration, Instant };
+
+// console output of a board that can be matched against while a script is running
+pub struct Console {
+    text:       Mutex<String>,
+    condition:  Condvar,
+    active:     AtomicBool,
+}
+
+//
+impl Console {
+
+    //
+    pub fn new() -> Self {
+        Self {
+            text:       Mutex::new(String::new()),
+            condition:  Condvar::new(),
+            active:     AtomicBool::new(false),
+        }
+    }
+
+    // start or stop capturing the console output
+    pub fn capture(&self, active: bool) {
+        self.text.lock().unwrap().clear();
+        self.active.store(active, Ordering::Relaxed);
+    }
+
+    //
+    pub fn active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    // add output from the board
+    pub fn push(&self, character: char) {
+        if self.active() {
+            self.text.lock().unwrap().push(character);
+            self.condition.notify_all();
+        }
+    }
+
+    // wait for the pattern to appear, everything up to the end of the match is consumed
+    pub fn expect(&self, pattern: &Regex, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut text = self.text.lock().unwrap();
+
+        //
+        loop {
+            if let Some(end) = pattern.find(&text).map(| found | found.end()) {
+                text.drain(..end);
+                return true;
+            }
+
+            //
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            text = self.condition.wait_timeout(text, deadline - now).unwrap().0;
+        }
+    }
+}
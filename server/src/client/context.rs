@@ -1,43 +1,56 @@
 pub use std::sync::Arc;
 pub use std::sync::atomic::{ AtomicBool, Ordering };
-pub use parser::{ file_size, checked_path };
+pub use parser::checked_path;
 pub use client::Client;
+pub use client::script::Script;
+pub use client::protocol::{ Request, Message, Decoder, Events };
+pub use client::groups::Assignments;
+pub use store::Store;
+pub use parser::{ Channel, BinaryFile, Revision };
+pub use std::time::Duration;
 pub use std::thread;
 
-use parser::{ Parser, Channel, BinaryFile, Item };
+use parser::{ Parser, Item };
 use std::net::Ipv4Addr;
-use std::fs;
-
-//
-pub enum Request {
-    TransmitKernel,
-    TransmitFile(String),
-    Terminate(String),
-
-}
 
 //
+#[derive(Clone)]
 pub enum Mode {
-    Serial(String),
+    Serial(String, String),
     Ethernet(Ipv4Addr),
 }
 
 //
+#[derive(Clone)]
 pub struct Context {
     pub name:       String,
-    pub binary:     Option<String>,
+    pub bare_name:  String,
+    pub binary:     Option<BinaryFile>,
+    pub channel:    Channel,
+    pub store:      Option<Arc<Store>>,
+    pub debounce:   Option<Duration>,
+    pub reset:      bool,
+    pub script:     Option<Arc<Script>>,
     pub index:      usize,
     pub locked:     Arc<AtomicBool>,
+    pub tags:       Vec<String>,
+    pub enabled:    bool,
+    pub events:     Events,
 }
 
 //
 impl Context {
 
     //
-    pub fn new(binary_files: &Vec<BinaryFile>, name: String, translation_path: String, index: usize) -> (Self, Mode) {
+    pub fn new(binary_files: &[BinaryFile], store: Option<Arc<Store>>, name: String, translation_path: String, index: usize, mut tags: Vec<String>, assignments: &Assignments) -> (Self, Mode) {
         let mut channel = Channel::Stable;
-        let mut mode: Item<Option<Mode>> = item!(None);
-        let mut binary: Option<String> = None;
+        let mode: Item<Option<Mode>> = item!(None);
+        let mut binary: Option<BinaryFile> = None;
+        let mut revision = Revision::Latest;
+        let mut reset = false;
+        let mut script: Option<Arc<Script>> = None;
+        let mut enabled = true;
+        let mut events = Events::Characters;
 
         //
         {
@@ -45,7 +58,15 @@ impl Context {
 
             parser.register("serial", true, Box::new(| stack, _ | {
                 stack.push_debug("no serial source specified");
-                *mode.borrow_mut() = Some(Mode::Serial(stack.pop()));
+                let source = stack.pop();
+                *mode.borrow_mut() = Some(Mode::Serial(source.clone(), source));
+            }));
+
+            parser.register("pipe", true, Box::new(| stack, _ | {
+                stack.push_debug("no input pipe specified");
+                let input = stack.pop();
+                stack.push_debug("no output pipe specified");
+                *mode.borrow_mut() = Some(Mode::Serial(input, stack.pop()));
             }));
 
             parser.register("ethernet", true, Box::new(| stack, _ | {
@@ -60,20 +81,76 @@ impl Context {
                 binary = Some(stack.pop_binary(binary_files));
             }));
 
+            parser.register("revision", true, Box::new(| stack, _ | {
+                revision = stack.pop_revision();
+            }));
+
+            parser.register("reset", true, Box::new(| stack, _ | {
+                reset = stack.pop_state();
+            }));
+
+            parser.register("tags", true, Box::new(| stack, _ | {
+                tags.extend(stack.pop_line().split_whitespace().map(String::from));
+            }));
+
+            parser.register("state", true, Box::new(| stack, _ | {
+                enabled = stack.pop_state();
+            }));
+
+            parser.register("events", true, Box::new(| stack, _ | {
+                stack.push_debug("no event protocol specified. valid options are 'characters' and 'keys'");
+                let name = stack.pop();
+                events = match Events::from_name(&name) {
+                    Some(events)    => events,
+                    None            => stack.panic(format!("invalid event protocol '{}'. valid options are 'characters' and 'keys'", name)),
+                };
+            }));
+
+            parser.register("script", true, Box::new(| stack, _ | {
+                stack.push_debug("no script name specified");
+                let index = translation_path.len() - translation_path.chars().rev().position(| character | character == '/').unwrap_or(translation_path.len());
+                let path = match checked_path(&translation_path[..index], &stack.pop(), "script", true) {
+                    Some(path)  => path,
+                    None        => panic!("[ server ] [ {} ] failed to find script path", name)
+                };
+                script = Some(Arc::new(Script::new(path)));
+            }));
+
             parser.parse();
         }
 
-        // set the release channel
-        if let Some(prefix) = binary {
-            binary = Some(prefix + &channel.suffix());
+        // binaries and channels assigned in the configuration or on the command line replace the ones of the client
+        if let Some(assigned) = assignments.binary(&name, &tags) {
+            binary = Some(assigned);
+        }
+        if let Some(assigned) = assignments.channel(&name, &tags) {
+            channel = assigned;
+        }
+
+        // set the release channel and the revision
+        if let Some(binary) = &mut binary {
+            binary.path.push_str(channel.suffix());
+            if !matches!(revision, Revision::Latest) {
+                assert!(store.is_some(), "[ server ] [ {} ] revisions other than 'latest' require an artifact store", name);
+            }
+            binary.revision = revision;
         }
 
         //
         (Self {
-            name:           name,
+            name:           name.clone(),
+            bare_name:      name,
             binary:         binary,
+            channel:        channel,
+            store:          store,
+            debounce:       None,
+            reset:          reset,
+            script:         script,
             index:          index,
             locked:         Arc::new(AtomicBool::new(false)),
+            tags:           tags,
+            enabled:        enabled,
+            events:         events,
         },
         unwrap_item!(mode).expect("[ server ] no client mode specified"))
     }
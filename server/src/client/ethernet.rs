@@ -1,6 +1,10 @@
 use client::context::*;
+use client::console::Console;
+use reactor::{ Reactor, Token };
 
-use std::net::{ TcpListener, TcpStream, Ipv4Addr };
+use std::net::Ipv4Addr;
+use std::fs::File;
+use std::sync::Arc;
 
 //
 pub struct EthernetClient {
@@ -11,7 +15,7 @@ pub struct EthernetClient {
 impl EthernetClient {
 
     //
-    pub fn new(context: Context, address: Ipv4Addr) -> Self {
+    pub fn new(context: Context, _address: Ipv4Addr) -> Self {
         Self {
             context:    context,
         }
@@ -22,20 +26,37 @@ impl EthernetClient {
 impl Client for EthernetClient {
 
     //
-    fn start(&mut self) {
-        let name = self.context.name.clone();
-
-        if let Some(kernel_path) = &self.context.binary {
-            thread::spawn(|| { start(name) });
+    fn start(&mut self, _reactor: &mut Reactor, _token: Token) {
+        if self.context.binary.is_some() {
+            println!("[ client ] [ {} ] started in ethernet mode", self.context.name);
         }
     }
 
+    //
+    fn ready(&mut self, _reactor: &mut Reactor) {}
+
+    //
+    fn stop(&mut self, _reactor: &mut Reactor, _message: &str) {}
+
+    //
+    fn busy(&self) -> bool {
+        false
+    }
+
+    //
+    fn abort(&mut self, _reactor: &mut Reactor) {}
+
+    //
+    fn harness(&mut self) -> Option<(Arc<Console>, File)> {
+        None
+    }
+
     //
     #[cfg(feature = "controller")]
-    fn event(&mut self, data: u16) {
+    fn event(&mut self, _reactor: &mut Reactor, _payload: &::client::protocol::Payload) {
         // send modifiers if self.context.binary.is_none()
-        if self.context.locked.load(Ordering::Relaxed) == false {
-            //self.source_file.write(&[(data >> 8) as u8, data as u8]).unwrap();
+        if !self.context.locked.load(Ordering::Relaxed) {
+            //self.source_file.write(&_payload.encode(self.context.events)).unwrap();
         }
     }
 
@@ -45,9 +66,3 @@ impl Client for EthernetClient {
         self.context.index
     }
 }
-
-//
-fn start(name: String) -> ! {
-    println!("[ client ] [ {} ] started in ethernet mode", name);
-    loop {}
-}
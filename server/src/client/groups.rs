@@ -0,0 +1,88 @@
+use parser::{ BinaryFile, Channel };
+
+// named sets of clients, a member is a client name, a tag or a group defined before
+#[derive(Clone)]
+pub struct Groups {
+    groups:         Vec<(String, Vec<String>)>,
+}
+
+//
+impl Groups {
+
+    //
+    pub fn new() -> Self {
+        Self {
+            groups:         Vec::new(),
+        }
+    }
+
+    //
+    pub fn define(&mut self, name: String, members: Vec<String>) {
+        assert!(!members.is_empty(), "[ server ] group '{}' has no members", name);
+        assert!(self.groups.iter().all(| (group, _) | group != &name), "[ server ] group names must be unique");
+        self.groups.push((name, members));
+    }
+
+    //
+    pub fn names(&self) -> Vec<String> {
+        self.groups.iter().map(| (name, _) | name.clone()).collect()
+    }
+
+    // every member of every group
+    pub fn members(&self) -> Vec<&String> {
+        self.groups.iter().flat_map(| (_, members) | members).collect()
+    }
+
+    // a selector is the name of a client, one of its tags or a group
+    pub fn matches(&self, name: &str, tags: &[String], selector: &str) -> bool {
+        self.matches_before(self.groups.len(), name, tags, selector)
+    }
+
+    // only the groups defined before are expanded, so groups can never contain themselves
+    fn matches_before(&self, limit: usize, name: &str, tags: &[String], selector: &str) -> bool {
+        if name == selector || tags.iter().any(| tag | tag == selector) {
+            return true;
+        }
+
+        //
+        match self.groups[..limit].iter().position(| (group, _) | group == selector) {
+            Some(position)  => self.groups[position].1.iter().any(| member | self.matches_before(position, name, tags, member)),
+            None            => false,
+        }
+    }
+}
+
+// settings the configuration gives every client matching a selector, later ones take precedence
+#[derive(Clone)]
+pub struct Assignments {
+    pub groups:     Groups,
+    pub channels:   Vec<(String, Channel)>,
+    pub binaries:   Vec<(String, BinaryFile)>,
+    pub channel:    Option<Channel>,
+}
+
+//
+impl Assignments {
+
+    //
+    pub fn new() -> Self {
+        Self {
+            groups:     Groups::new(),
+            channels:   Vec::new(),
+            binaries:   Vec::new(),
+            channel:    None,
+        }
+    }
+
+    // the channel given on the command line wins over every assignment
+    pub fn channel(&self, name: &str, tags: &[String]) -> Option<Channel> {
+        let assigned = self.channels.iter().rev().find(| (selector, _) | self.groups.matches(name, tags, selector));
+        self.channel.or_else(| | assigned.map(| (_, channel) | *channel))
+    }
+
+    //
+    pub fn binary(&self, name: &str, tags: &[String]) -> Option<BinaryFile> {
+        let assigned = self.binaries.iter().rev().find(| (selector, _) | self.groups.matches(name, tags, selector));
+        assigned.map(| (_, binary) | binary.clone())
+    }
+}
@@ -1,33 +1,217 @@
 mod serial;
 mod ethernet;
 mod context;
+mod watcher;
+mod console;
+mod script;
+mod groups;
+pub mod protocol;
 
 pub use self::serial::SerialClient;
 pub use self::ethernet::EthernetClient;
 pub use self::context::{ Context, Mode };
+pub use self::watcher::Watcher;
+pub use self::console::Console;
+pub use self::groups::{ Groups, Assignments };
 
-use parser::BinaryFile;
+use parser::{ BinaryFile, Revision, Channel };
+#[cfg(feature = "controller")]
+use client::protocol::Payload;
+use reactor::{ Reactor, Token };
+use store::Store;
+use std::sync::Arc;
+use std::fs::File;
+use std::time::Duration;
 
 // generic client
-pub trait Client {
+pub trait Client: Send {
 
-    // start the device
-    fn start(&mut self);
+    // start the device and register its sources with the reactor
+    fn start(&mut self, reactor: &mut Reactor, token: Token);
+
+    // one of the sources or timers of the device is ready
+    fn ready(&mut self, reactor: &mut Reactor);
+
+    // stop handling requests, the ones still waiting are declined with the message
+    fn stop(&mut self, reactor: &mut Reactor, message: &str);
+
+    // something is still being sent to the device
+    fn busy(&self) -> bool;
+
+    // give up on whatever is still being sent
+    fn abort(&mut self, reactor: &mut Reactor);
+
+    // hand the console and the device to the test harness, only serial clients have them
+    fn harness(&mut self) -> Option<(Arc<Console>, File)>;
 
     // send an event to the device
     #[cfg(feature = "controller")]
-    fn event(&mut self, data: u16);
+    fn event(&mut self, reactor: &mut Reactor, payload: &Payload);
 
     // get the client index
     #[cfg(feature = "controller")]
     fn index(&self) -> usize;
 }
 
+// a client of the configuration, it's only opened while enabled
+struct Slot {
+    context:            Context,
+    mode:               Mode,
+    client:             Option<Box<dyn Client>>,
+    enabled:            bool,
+}
+
+// open the device of a client, missing devices are skipped
+pub fn open(context: &Context, mode: &Mode, watcher: Option<&mut Watcher>) -> Option<Box<dyn Client>> {
+    println!("[ server ] [ {} ] client initialized", context.name);
+    match mode.clone() {
+        Mode::Serial(input, output) => {
+            let client = SerialClient::new(context.clone(), input, output)?;
+            if let Some(watcher) = watcher {
+                client.watch(watcher);
+            }
+            Some(Box::new(client))
+        },
+        Mode::Ethernet(ip_address)  => Some(Box::new(EthernetClient::new(context.clone(), ip_address))),
+    }
+}
+
+// started clients, driven by the reactor
+pub struct Clients {
+    slots:              Vec<Slot>,
+    watcher:            Option<Watcher>,
+    groups:             Groups,
+    #[cfg(feature = "controller")]
+    targets:            Vec<String>,
+}
+
+//
+impl Clients {
+
+    // handle a token reported by the reactor
+    pub fn ready(&mut self, token: Token, reactor: &mut Reactor) {
+        match token {
+            Token::Client(index)    => {
+                if let Some(client) = &mut self.slots[index].client {
+                    client.ready(reactor);
+                }
+            },
+            Token::Watcher          => {
+                if let Some(watcher) = &mut self.watcher {
+                    watcher.ready(reactor);
+                }
+            },
+            _                       => {},
+        }
+    }
+
+    // indices of the clients a selector refers to, reports selectors that match nothing
+    fn select(&self, selector: &str) -> Vec<usize> {
+        let indices: Vec<usize> = (0..self.slots.len()).filter(| index | {
+            let context = &self.slots[*index].context;
+            self.groups.matches(&context.bare_name, &context.tags, selector)
+        }).collect();
+        if indices.is_empty() {
+            println!("[ server ] no client, tag or group '{}' found", selector);
+        }
+        indices
+    }
+
+    // start the selected clients, their devices are opened again if they weren't available before
+    pub fn enable(&mut self, selectors: &[String], reactor: &mut Reactor) {
+        for selector in selectors {
+            for index in self.select(selector) {
+                let slot = &mut self.slots[index];
+                if slot.enabled {
+                    continue;
+                }
+
+                //
+                if slot.client.is_none() {
+                    slot.client = open(&slot.context, &slot.mode, self.watcher.as_mut());
+                }
+                if let Some(client) = &mut slot.client {
+                    client.start(reactor, Token::Client(index));
+                    slot.enabled = true;
+                    println!("[ server ] [ {} ] client enabled", slot.context.name);
+                }
+            }
+        }
+    }
+
+    // stop the selected clients, running transfers are finished first
+    pub fn disable(&mut self, selectors: &[String], reactor: &mut Reactor) {
+        for selector in selectors {
+            for index in self.select(selector) {
+                let slot = &mut self.slots[index];
+                if !slot.enabled {
+                    continue;
+                }
+
+                //
+                if let Some(client) = &mut slot.client {
+                    client.stop(reactor, "client disabled");
+                }
+                slot.enabled = false;
+                println!("[ server ] [ {} ] client disabled", slot.context.name);
+            }
+        }
+    }
+
+    // print the state of the selected clients, or of every client without selectors
+    pub fn status(&self, selectors: &[String]) {
+        let indices: Vec<usize> = match selectors.is_empty() {
+            true    => (0..self.slots.len()).collect(),
+            false   => selectors.iter().flat_map(| selector | self.select(selector)).collect(),
+        };
+
+        //
+        for index in indices {
+            let slot = &self.slots[index];
+            let state = if slot.enabled { "enabled" } else { "disabled" };
+            println!("[ server ] [ {} ] {}", slot.context.name, state);
+        }
+    }
+
+    //
+    pub fn stop(&mut self, reactor: &mut Reactor, message: &str) {
+        self.slots.iter_mut().filter_map(| slot | slot.client.as_mut()).for_each(| client | client.stop(reactor, message));
+    }
+
+    //
+    pub fn busy(&self) -> bool {
+        self.slots.iter().filter_map(| slot | slot.client.as_ref()).any(| client | client.busy())
+    }
+
+    //
+    pub fn abort(&mut self, reactor: &mut Reactor) {
+        self.slots.iter_mut().filter_map(| slot | slot.client.as_mut()).for_each(| client | client.abort(reactor));
+    }
+
+    // send an event to the client with the given index, targets after the clients are groups and tags
+    #[cfg(feature = "controller")]
+    pub fn event(&mut self, target: usize, payload: &Payload, reactor: &mut Reactor) {
+        let targets = &self.targets;
+        let selector = target.checked_sub(self.slots.len()).and_then(| index | targets.get(index));
+        for slot in self.slots.iter_mut().filter(| slot | slot.enabled) {
+            let targeted = match selector {
+                Some(selector)  => self.groups.matches(&slot.context.bare_name, &slot.context.tags, selector),
+                None            => slot.context.index == target,
+            };
+            if let (true, Some(client)) = (targeted, slot.client.as_mut()) {
+                client.event(reactor, payload);
+            }
+        }
+    }
+}
+
 // client manager
 pub struct Manager {
-    serial_clients:     Vec<SerialClient>,
-    ethernet_clients:   Vec<EthernetClient>,
+    settings:           Vec<(Context, Mode)>,
     binary_files:       Vec<BinaryFile>,
+    store:              Option<Arc<Store>>,
+    debounce:           Option<Duration>,
+    assignments:        Assignments,
 }
 
 // implement client manager
@@ -36,12 +220,44 @@ impl Manager {
     // create a new client manager
     pub fn new() -> Self {
         Self {
-            serial_clients:         Default::default(),
-            ethernet_clients:       Default::default(),
+            settings:               Default::default(),
             binary_files:           Default::default(),
+            store:                  None,
+            debounce:               None,
+            assignments:            Assignments::new(),
         }
     }
 
+    // serve every client from the same channel, no matter what their settings or assignments say
+    pub fn channel(&mut self, channel: Channel) {
+        self.assignments.channel = Some(channel);
+    }
+
+    //
+    pub fn group(&mut self, name: String, members: Vec<String>) {
+        self.assignments.groups.define(name, members);
+    }
+
+    // serve every client matching the selector from this channel
+    pub fn assign_channel(&mut self, selector: String, channel: Channel) {
+        self.assignments.channels.push((selector, channel));
+    }
+
+    // serve this binary to every client matching the selector
+    pub fn assign_binary(&mut self, selector: String, binary: BinaryFile) {
+        self.assignments.binaries.push((selector, binary));
+    }
+
+    //
+    pub fn binary_files(&self) -> &[BinaryFile] {
+        &self.binary_files
+    }
+
+    // keep a history of every binary served to the clients
+    pub fn store(&mut self, directory: String, retention: usize) {
+        self.store = Some(Arc::new(Store::new(directory, retention)));
+    }
+
     // add a binary file that can be loaded
     pub fn binary(&mut self, path: String, name: Option<String>) {
         assert!(self.binary_files.iter().find(| binary | binary.path == path).is_none(), "[ server ] binary paths must me unique");
@@ -59,40 +275,111 @@ impl Manager {
         }
 
         // store the new binary
-        self.binary_files.push(BinaryFile { path: path, name: name });
+        self.binary_files.push(BinaryFile { path: path, name: name, revision: Revision::Latest });
     }
 
-    //
-    pub fn initialize(&mut self, lookup_path: &str, name: String, index: usize) {
-        use parser::checked_path;
+    // watch the binary files for changes and only serve them once they stopped changing
+    pub fn debounce(&mut self, milliseconds: u64) {
+        assert!(milliseconds > 0, "[ server ] debounce duration must be greater than zero");
+        self.debounce = Some(Duration::from_millis(milliseconds));
+    }
 
+    // parse the settings of a client, its device is only opened once it's started
+    pub fn initialize(&mut self, lookup_path: &str, prefix: &str, name: String, index: usize, tags: Vec<String>) {
         //
-        let path = match context::checked_path(lookup_path, &name, "client", false) {
+        let prefixed = format!("{}{}", prefix, name);
+        let path = match context::checked_path(lookup_path, &prefixed, "client", false) {
             Some(path)  => path,
-            None        => panic!("[ server ] [ {} ] failed to find configuration path", name)
+            None        => panic!("[ server ] [ {} ] failed to find configuration path", prefixed)
         };
 
+        // selectors and assignments refer to the bare name, the prefixed one names the client everywhere else
+        let (mut context, mode) = Context::new(&self.binary_files, self.store.clone(), name, path, index, tags, &self.assignments);
+        context.name = prefixed;
+        context.debounce = self.debounce;
+        self.settings.push((context, mode));
+    }
 
-        let (context, mode) = Context::new(&self.binary_files, name, path, index);
-        println!("[ server ] [ {} ] client initialized", context.name);
+    // enable only the clients matching one of the selectors, without selectors the settings of the clients decide
+    pub fn select(&mut self, selectors: &[String]) {
+        let assignments = &self.assignments;
+        let mut used = assignments.groups.members();
+        used.extend(assignments.channels.iter().map(| (selector, _) | selector));
+        used.extend(assignments.binaries.iter().map(| (selector, _) | selector));
+        for selector in used.into_iter().chain(selectors) {
+            assert!(!self.matching(selector).is_empty(), "[ server ] no client, tag or group '{}' found", selector);
+        }
 
         //
-        match mode {
-            Mode::Serial(source_path)   => self.serial_clients.push(SerialClient::new(context, source_path)),
-            Mode::Ethernet(ip_address)  => self.ethernet_clients.push(EthernetClient::new(context, ip_address)),
+        if !selectors.is_empty() {
+            for (context, _) in &mut self.settings {
+                context.enabled = selectors.iter().any(| selector | assignments.groups.matches(&context.bare_name, &context.tags, selector));
+            }
         }
     }
 
-    //
-    pub fn clients(self) -> (Vec<SerialClient>, Vec<EthernetClient>) {
-        (self.serial_clients, self.ethernet_clients)
+    // settings of every client, whether it's enabled or not
+    pub fn settings(&self) -> &[(Context, Mode)] {
+        &self.settings
     }
 
-    //
-    #[cfg(not(feature = "controller"))]
-    pub fn start(mut self) -> ! {
-        self.serial_clients.iter_mut().for_each(| client | client.start());
-        self.ethernet_clients.iter_mut().for_each(| client | client.start());
-        loop {}
+    // settings of the clients a name, tag or group refers to
+    pub fn matching(&self, selector: &str) -> Vec<&(Context, Mode)> {
+        self.settings.iter().filter(| (context, _) | self.assignments.groups.matches(&context.bare_name, &context.tags, selector)).collect()
+    }
+
+    // controllers can target groups and tags as well, they are numbered after the clients
+    pub fn targets(&self) -> Vec<String> {
+        let mut targets = self.assignments.groups.names();
+        for (context, _) in &self.settings {
+            for tag in &context.tags {
+                if !targets.contains(tag) {
+                    targets.push(tag.clone());
+                }
+            }
+        }
+        targets
+    }
+
+    // start watching the binary files and every enabled client
+    pub fn start(self, reactor: &mut Reactor) -> Clients {
+        #[cfg(feature = "controller")]
+        let targets = self.targets();
+
+        //
+        let mut watcher = self.debounce.map(| debounce | Watcher::new(&self.binary_files, debounce));
+        let mut slots = Vec::new();
+        for (context, mode) in self.settings {
+            let client = match context.enabled {
+                true    => open(&context, &mode, watcher.as_mut()),
+                false   => None,
+            };
+            let enabled = client.is_some();
+            slots.push(Slot {
+                context:    context,
+                mode:       mode,
+                client:     client,
+                enabled:    enabled,
+            });
+        }
+
+        //
+        if let Some(watcher) = &watcher {
+            watcher.start(reactor);
+        }
+        for (index, slot) in slots.iter_mut().enumerate() {
+            if let Some(client) = &mut slot.client {
+                client.start(reactor, Token::Client(index));
+            }
+        }
+
+        //
+        Clients {
+            slots:      slots,
+            watcher:    watcher,
+            groups:     self.assignments.groups,
+            #[cfg(feature = "controller")]
+            targets:    targets,
+        }
     }
 }
@@ -0,0 +1,241 @@
+// longest path a board may request
+pub const PATH_LENGTH: usize = 128;
+
+// request sent by a board
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    TransmitKernel,
+    TransmitFile(String),
+    Terminate(String),
+}
+
+//
+impl Request {
+
+    // bytes a board sends to make this request
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Request::TransmitKernel         => b"?k".to_vec(),
+            Request::TransmitFile(path)     => format!("?f{}?", path).into_bytes(),
+            Request::Terminate(_)           => Vec::new(),
+        }
+    }
+}
+
+// something the decoder found in the byte stream of a board
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Console(u8),
+    Request(Request),
+}
+
+//
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Console,
+    Request,
+    Path,
+}
+
+// splits the byte stream of a board into console output and requests
+pub struct Decoder {
+    state:      State,
+    path:       String,
+}
+
+//
+impl Decoder {
+
+    //
+    pub fn new() -> Self {
+        Self {
+            state:      State::Console,
+            path:       String::new(),
+        }
+    }
+
+    // feed a single byte, returns a message once it's complete
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        match self.state {
+
+            //
+            State::Console  => match byte {
+                b'?'    => {
+                    self.state = State::Request;
+                    None
+                },
+                byte    => Some(Message::Console(byte)),
+            },
+
+            //
+            State::Request  => {
+                self.state = State::Console;
+                match byte {
+                    b'k'    => Some(Message::Request(Request::TransmitKernel)),
+                    b'f'    => {
+                        self.state = State::Path;
+                        self.path.clear();
+                        None
+                    },
+                    request => Some(Message::Request(Request::Terminate(format!("invalid request '{}'", request as char)))),
+                }
+            },
+
+            //
+            State::Path     => {
+                if byte == b'?' {
+                    self.state = State::Console;
+                    return Some(Message::Request(Request::TransmitFile(self.path.split_off(0))));
+                }
+
+                //
+                if self.path.len() >= PATH_LENGTH {
+                    self.state = State::Console;
+                    return Some(Message::Request(Request::Terminate(String::from("exeeded path length"))));
+                }
+                self.path.push(byte as char);
+                None
+            },
+        }
+    }
+}
+
+// the '!' and the size that come before the data of a transmission
+pub const TRANSMISSION_HEADER: usize = 5;
+
+// initial transmition character and the size followed by the binary
+pub fn encode_transmission(data: &[u8]) -> Vec<u8> {
+    let size = data.len() as u32;
+    let mut buffer = vec![b'!', (size >> 24) as u8, (size >> 16) as u8, (size >> 8) as u8, size as u8];
+    buffer.extend_from_slice(data);
+    buffer
+}
+
+// anything but '!' makes the board drop the request, the bootloader shows the rest as an ignored command
+pub fn encode_rejection(message: &str) -> Vec<u8> {
+    format!("-{}\r", message).into_bytes()
+}
+
+// a translated controller event as modifiers and character
+pub fn encode_event(modifiers: u8, character: u8) -> [u8; 2] {
+    [modifiers, character]
+}
+
+// how controller events are sent to a board
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Events {
+    Characters,
+    Keys,
+}
+
+//
+impl Events {
+
+    // get a protocol from its name as used in the configuration
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "characters"    => Some(Events::Characters),
+            "keys"          => Some(Events::Keys),
+            _               => None,
+        }
+    }
+}
+
+// whether a key went down, came back up or is held
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum KeyState {
+    Up,
+    Down,
+    Repeat,
+}
+
+// a translated controller event, keys without a character only reach boards that take key events
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub code:       u16,
+    pub state:      KeyState,
+    pub modifiers:  u64,
+    pub character:  Option<u8>,
+    pub sequence:   Vec<u8>,
+}
+
+//
+impl KeyEvent {
+
+    // the bytes of the event in the given protocol, boards that take characters never see releases and only
+    // the first eight modifiers. sequences are sent like characters typed one after the other
+    pub fn encode(&self, events: Events) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match events {
+            Events::Characters  => {
+                if self.state != KeyState::Up {
+                    for character in self.character.iter().chain(self.sequence.iter()) {
+                        bytes.extend_from_slice(&encode_event(self.modifiers as u8, *character));
+                    }
+                }
+            },
+            Events::Keys        => {
+                bytes.extend_from_slice(&encode_key(self));
+                if self.state != KeyState::Up {
+                    for chunk in self.sequence.chunks(255) {
+                        bytes.extend(encode_sequence(chunk));
+                    }
+                }
+            },
+        }
+        bytes
+    }
+}
+
+// position of an axis, from -32767 to 32767 with zero at its center
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisEvent {
+    pub code:       u16,
+    pub value:      i16,
+}
+
+// what a controller sends to a board
+#[derive(Clone, Debug, PartialEq)]
+pub enum Payload {
+    Key(KeyEvent),
+    Axis(AxisEvent),
+}
+
+//
+impl Payload {
+
+    // boards that take characters never see axes
+    pub fn encode(&self, events: Events) -> Vec<u8> {
+        match (self, events) {
+            (Payload::Key(key_event), events)       => key_event.encode(events),
+            (Payload::Axis(_), Events::Characters)  => Vec::new(),
+            (Payload::Axis(axis_event), Events::Keys) => encode_axis(axis_event).to_vec(),
+        }
+    }
+}
+
+// a key event as state, key code, every modifier and character, the character is zero for keys without one
+pub fn encode_key(event: &KeyEvent) -> [u8; 12] {
+    let state = match event.state {
+        KeyState::Up        => 0,
+        KeyState::Down      => 1,
+        KeyState::Repeat    => 2,
+    };
+    let mut buffer = [0; 12];
+    buffer[..3].copy_from_slice(&[state, (event.code >> 8) as u8, event.code as u8]);
+    buffer[3..11].copy_from_slice(&event.modifiers.to_be_bytes());
+    buffer[11] = event.character.unwrap_or(0);
+    buffer
+}
+
+// an axis event as a state no key can have, the axis code and its position
+pub fn encode_axis(event: &AxisEvent) -> [u8; 5] {
+    [4, (event.code >> 8) as u8, event.code as u8, (event.value >> 8) as u8, event.value as u8]
+}
+
+// a sequence that follows a key event, marked with a state no key can have and followed by its length
+pub fn encode_sequence(sequence: &[u8]) -> Vec<u8> {
+    let mut buffer = vec![3, sequence.len() as u8];
+    buffer.extend_from_slice(sequence);
+    buffer
+}
@@ -0,0 +1,178 @@
+use client::context::*;
+use client::console::Console;
+use client::serial::{ load_binary, transmit_file };
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::Write;
+use std::fs::File;
+
+// when to follow a jump
+#[derive(Clone, Copy)]
+pub enum Condition {
+    Always,
+    Matched,
+    Missed,
+}
+
+//
+pub enum Instruction {
+
+    // write a command followed by a carriage return
+    Send(String),
+
+    // send the kernel in the format expected by the bootloaders 'load' command
+    Transmit,
+
+    // wait for a pattern in the console output
+    Expect(Duration, Regex),
+
+    // continue at a label
+    Jump(Condition, String),
+
+    // stop the script
+    Pass,
+    Fail(String),
+}
+
+//
+pub struct Script {
+    pub path:           String,
+    instructions:       Vec<Instruction>,
+    labels:             HashMap<String, usize>,
+}
+
+//
+impl Script {
+
+    //
+    pub fn new(path: String) -> Self {
+        use parser::{ Parser, Item };
+
+        //
+        let instructions: Item<Vec<Instruction>> = item!(Vec::new());
+        let mut labels: HashMap<String, usize> = HashMap::new();
+
+        {
+            //
+            let mut parser = Parser::new(&path);
+
+            //
+            parser.register("send", false, Box::new(| stack, _ | {
+                instructions.borrow_mut().push(Instruction::Send(stack.pop_line()));
+            }));
+
+            //
+            parser.register("transmit", false, Box::new(| _, _ | {
+                instructions.borrow_mut().push(Instruction::Transmit);
+            }));
+
+            //
+            parser.register("expect", false, Box::new(| stack, _ | {
+                let timeout = Duration::from_millis(stack.pop_u64());
+                let pattern = stack.pop_line();
+                match Regex::new(&pattern) {
+                    Ok(pattern) => instructions.borrow_mut().push(Instruction::Expect(timeout, pattern)),
+                    Err(_)      => stack.panic(format!("invalid pattern '{}'", pattern)),
+                }
+            }));
+
+            //
+            parser.register("label", false, Box::new(| stack, _ | {
+                stack.push_debug("no label name specified");
+                let label = stack.pop();
+                if labels.insert(label.clone(), instructions.borrow().len()).is_some() {
+                    stack.panic(format!("label '{}' defined twice", label));
+                }
+            }));
+
+            //
+            for &(keyword, condition) in &[("goto", Condition::Always), ("matched", Condition::Matched), ("missed", Condition::Missed)] {
+                let instructions = instructions.clone();
+                parser.register(keyword, false, Box::new(move | stack, _ | {
+                    stack.push_debug("no label specified");
+                    instructions.borrow_mut().push(Instruction::Jump(condition, stack.pop()));
+                }));
+            }
+
+            //
+            parser.register("pass", false, Box::new(| _, _ | {
+                instructions.borrow_mut().push(Instruction::Pass);
+            }));
+
+            //
+            parser.register("fail", false, Box::new(| stack, _ | {
+                instructions.borrow_mut().push(Instruction::Fail(stack.pop_line()));
+            }));
+
+            //
+            parser.parse();
+        }
+
+        // make sure every jump goes somewhere
+        let instructions = unwrap_item!(instructions);
+        for instruction in &instructions {
+            if let Instruction::Jump(_, label) = instruction {
+                assert!(labels.contains_key(label), "[ script ] label '{}' not found in '{}'", label, path);
+            }
+        }
+
+        //
+        Self {
+            path:           path,
+            instructions:   instructions,
+            labels:         labels,
+        }
+    }
+
+    // run the script against a client, the console has to be capturing already
+    pub fn run(&self, context: &Context, console: &Console, source_path: &str) -> Result<(), String> {
+        let mut writer = File::create(source_path).map_err(| _ | String::from("unable to open serial source file"))?;
+        let mut matched = false;
+        let mut pointer = 0;
+
+        //
+        while let Some(instruction) = self.instructions.get(pointer) {
+            pointer += 1;
+            match instruction {
+
+                //
+                Instruction::Send(command)          => {
+                    writer.write_all(command.as_bytes()).and_then(| _ | writer.write_all(b"\r"))
+                        .map_err(| _ | format!("unable to send '{}'", command))?;
+                },
+
+                //
+                Instruction::Transmit               => {
+                    let binary = context.binary.as_ref().ok_or_else(| | String::from("no binary file specified"))?;
+                    let data = load_binary(context, binary, context.channel).ok_or_else(| | String::from("unable to load binary"))?;
+                    transmit_file(&context.name, source_path, &data)?;
+                },
+
+                //
+                Instruction::Expect(timeout, pattern) => {
+                    matched = console.expect(pattern, *timeout);
+                },
+
+                //
+                Instruction::Jump(condition, label) => {
+                    let jump = match condition {
+                        Condition::Always   => true,
+                        Condition::Matched  => matched,
+                        Condition::Missed   => !matched,
+                    };
+                    if jump {
+                        pointer = self.labels[label];
+                    }
+                },
+
+                //
+                Instruction::Pass                   => return Ok(()),
+                Instruction::Fail(message)          => return Err(message.clone()),
+            }
+        }
+
+        //
+        Ok(())
+    }
+}
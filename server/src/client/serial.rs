@@ -1,56 +1,383 @@
 use client::context::*;
+use client::watcher::{ Watcher, wait_stable, remaining_unstable };
+use client::console::Console;
+use client::protocol::{ TRANSMISSION_HEADER, encode_transmission, encode_rejection };
+use reactor::{ Reactor, Token, Interest };
+use verbosity;
 
-use std::io::{ Write, Read };
-use std::fs::File;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::collections::VecDeque;
+use std::io::{ Write, Read, ErrorKind };
+use std::fs::{ self, File, OpenOptions };
+use std::time::{ Duration, Instant };
+use std::thread;
+use libc;
+
+// how long an aborted transfer waits for a board that stopped reading before giving up on it
+const ABORT_STALL: Duration = Duration::from_millis(500);
+
+// binary or controller events that are being written to the board without blocking
+struct Transfer {
+    writer:         File,
+    data:           Vec<u8>,
+    offset:         usize,
+    time:           Instant,
+    events:         bool,
+}
 
 //
 pub struct SerialClient {
     context:        Context,
     source_file:    File,
-    source_path:    String,
+    input_path:     String,
+    output_path:    String,
+    console:        Arc<Console>,
+    input_file:     Option<File>,
+    decoder:        Decoder,
+    scripted:       Arc<AtomicBool>,
+    queue:          VecDeque<(BinaryFile, Channel)>,
+    transfer:       Option<Transfer>,
+    stopping:       Option<String>,
+    token:          Token,
 }
 
 //
 impl SerialClient {
 
-    // create new serial client
-    pub fn new(context: Context, source_path: String) -> Self {
+    // create new serial client, input and output are the same file for a real serial device. missing devices are skipped
+    pub fn new(context: Context, input_path: String, output_path: String) -> Option<Self> {
+
+        //
+        for path in &[&input_path, &output_path] {
+            if fs::metadata(path).is_err() {
+                println!("[ client ] [ {} ] device '{}' not found, client skipped", context.name, path);
+                return None;
+            }
+        }
 
         //
-        let mut source_file = match File::create(&source_path) {
+        let source_file = match File::create(&output_path) {
             Ok(file)    => file,
-            Err(_)      => panic!("[ client ] [ {} ] unable to open serial source file", context.name)
+            Err(_)      => {
+                println!("[ client ] [ {} ] unable to open serial source file, client skipped", context.name);
+                return None;
+            },
         };
 
         //
-        Self {
+        Some(Self {
             context:        context,
             source_file:    source_file,
-            source_path:    source_path,
+            input_path:     input_path,
+            output_path:    output_path,
+            console:        Arc::new(Console::new()),
+            input_file:     None,
+            decoder:        Decoder::new(),
+            scripted:       Arc::new(AtomicBool::new(false)),
+            queue:          VecDeque::new(),
+            transfer:       None,
+            stopping:       None,
+            token:          Token::Client(0),
+        })
+    }
+
+    // register the client for remote resets
+    pub fn watch(&self, watcher: &mut Watcher) {
+        if self.context.reset {
+            match self.source_file.try_clone() {
+                Ok(writer)  => watcher.reset(&self.context, writer),
+                Err(_)      => println!("[ client ] [ {} ] unable to share serial source file", self.context.name),
+            }
+        }
+    }
+
+    // stop reading from the board, nothing it sends is handled anymore
+    fn terminate(&mut self, reactor: &mut Reactor, message: &str) {
+        self.context.locked.store(true, Ordering::Relaxed);
+        if let Some(input_file) = self.input_file.take() {
+            reactor.deregister(input_file.as_raw_fd());
+        }
+        if let Some(transfer) = self.transfer.take() {
+            reactor.deregister(transfer.writer.as_raw_fd());
+        }
+        println!("[ client ] [ {} ] {}. client terminated", self.context.name, message);
+    }
+
+    // read everything the board sent so far
+    fn receive(&mut self, reactor: &mut Reactor) {
+        let mut buffer = [0; 256];
+        loop {
+            let length = match self.input_file.as_mut().map(| input_file | input_file.read(&mut buffer)) {
+                None                                                    => return,
+                Some(Ok(0))                                             => return self.terminate(reactor, "serial source closed"),
+                Some(Ok(length))                                        => length,
+                Some(Err(error)) if error.kind() == ErrorKind::WouldBlock => return,
+                Some(Err(error)) if error.kind() == ErrorKind::Interrupted => continue,
+                Some(Err(_))                                            => return self.terminate(reactor, "serial source closed"),
+            };
+
+            //
+            verbose!(verbosity::TRAFFIC, "[ client ] [ {} ] received 0x{:x} bytes", self.context.name, length);
+            for byte in &buffer[..length] {
+                match self.decoder.push(*byte) {
+
+                    // a running script handles the kernel request itself
+                    Some(Message::Request(Request::TransmitKernel)) if self.scripted.load(Ordering::Relaxed) => {
+                        self.console.push('?');
+                        self.console.push('k');
+                    },
+
+                    //
+                    Some(Message::Request(Request::TransmitKernel)) if self.context.script.is_some() => {
+                        run_script(&self.context, self.console.clone(), self.scripted.clone(), self.output_path.clone());
+                    },
+
+                    //
+                    Some(Message::Request(Request::TransmitKernel)) => {
+                        verbose!(verbosity::DETAILS, "[ client ] [ {} ] kernel requested", self.context.name);
+                        let kernel = self.context.binary.clone().unwrap();
+                        self.queue.push_back((kernel, self.context.channel));
+                    },
+
+                    //
+                    Some(Message::Request(Request::TransmitFile(path))) => {
+                        verbose!(verbosity::DETAILS, "[ client ] [ {} ] file '{}' requested", self.context.name, path);
+                        let binary = BinaryFile { path: path, name: None, revision: Revision::Latest };
+                        self.queue.push_back((binary, Channel::None));
+                    },
+
+                    //
+                    Some(Message::Request(Request::Terminate(message))) => return self.terminate(reactor, &message),
+
+                    //
+                    Some(Message::Console(byte))    => {
+                        print!("{}", byte as char);
+                        self.console.push(byte as char);
+                    },
+
+                    //
+                    None                            => {},
+                }
+            }
         }
     }
+
+    // start sending the next requested binary once it settled
+    fn serve(&mut self, reactor: &mut Reactor) {
+
+        // nothing new is sent once the server is stopping
+        if let Some(message) = &self.stopping {
+            while self.transfer.is_none() {
+                let (binary, _) = match self.queue.pop_front() {
+                    Some(request)   => request,
+                    None            => return,
+                };
+                println!("[ client ] [ {} ] declined request for '{}'", self.context.name, binary.path);
+                let _ = self.source_file.write_all(&encode_rejection(message));
+            }
+            return;
+        }
+
+        //
+        while self.transfer.is_none() {
+            let (binary, channel) = match self.queue.front() {
+                Some(request)   => request.clone(),
+                None            => return,
+            };
+
+            //
+            let path = match resolve_binary(&self.context, &binary) {
+                Some(path)  => path,
+                None        => {
+                    self.queue.pop_front();
+                    continue;
+                },
+            };
+
+            // never send a binary that is still being written
+            if let Some(remaining) = self.context.debounce.and_then(| debounce | remaining_unstable(&path, debounce)) {
+                return reactor.timer(remaining, self.token);
+            }
+            self.queue.pop_front();
+
+            //
+            let data = match read_binary(&self.context, &binary, &path, channel) {
+                Some(data)  => data,
+                None        => continue,
+            };
+
+            //
+            let writer = match OpenOptions::new().write(true).create(true).truncate(true).custom_flags(libc::O_NONBLOCK).open(&self.output_path) {
+                Ok(file)    => file,
+                Err(_)      => {
+                    println!("[ client ] [ {} ] unable to open serial source file", self.context.name);
+                    continue;
+                },
+            };
+
+            //
+            println!("[ client ] [ {} ] transmitting 0x{:x} bytes", self.context.name, data.len());
+            self.context.locked.store(true, Ordering::Relaxed);
+            reactor.register(writer.as_raw_fd(), self.token, Interest::Write);
+            self.transfer = Some(Transfer {
+                writer:     writer,
+                data:       encode_transmission(&data),
+                offset:     0,
+                time:       Instant::now(),
+                events:     false,
+            });
+            self.send(reactor);
+        }
+    }
+
+    // write as much of the current transfer as the board accepts
+    fn send(&mut self, reactor: &mut Reactor) {
+        let name = &self.context.name;
+        let finished = match self.transfer.as_mut() {
+            Some(transfer)  => loop {
+                if transfer.offset == transfer.data.len() {
+                    if !transfer.events {
+                        println!("[ client ] [ {} ] transmitted in {} second/s", name, transfer.time.elapsed().as_secs());
+                    }
+                    break true;
+                }
+                match transfer.writer.write(&transfer.data[transfer.offset..]) {
+                    Ok(length)                                          => {
+                        verbose!(verbosity::TRAFFIC, "[ client ] [ {} ] sent 0x{:x} bytes", name, length);
+                        transfer.offset += length;
+                    },
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => break false,
+                    Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                    Err(_)                                              => {
+                        println!("[ client ] [ {} ] transmission failed after 0x{:x} bytes", name, transfer.offset);
+                        break true;
+                    },
+                }
+            },
+            None            => false,
+        };
+
+        // events never lock the client, so they don't unlock it either
+        if finished {
+            if let Some(transfer) = self.transfer.take() {
+                reactor.deregister(transfer.writer.as_raw_fd());
+                if !transfer.events {
+                    self.context.locked.store(self.stopping.is_some(), Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    // events are appended to the ones that are still being written, a board that doesn't read doesn't hold up the server
+    #[cfg(feature = "controller")]
+    fn queue_events(&mut self, reactor: &mut Reactor, bytes: Vec<u8>) {
+        if let Some(transfer) = self.transfer.as_mut().filter(| transfer | transfer.events) {
+            transfer.data.extend(bytes);
+            return;
+        }
+
+        //
+        let writer = match OpenOptions::new().write(true).custom_flags(libc::O_NONBLOCK).open(&self.output_path) {
+            Ok(file)    => file,
+            Err(_)      => {
+                println!("[ client ] [ {} ] unable to open serial source file", self.context.name);
+                return;
+            },
+        };
+        reactor.register(writer.as_raw_fd(), self.token, Interest::Write);
+        self.transfer = Some(Transfer {
+            writer:     writer,
+            data:       bytes,
+            offset:     0,
+            time:       Instant::now(),
+            events:     true,
+        });
+        self.send(reactor);
+    }
 }
 
 //
 impl Client for SerialClient {
 
     //
-    fn start(&mut self) {
-        let name = self.context.name.clone();
-        let source_path = self.source_path.clone();
-        let kernel_path = match &self.context.binary {
-            Some(binary)    => binary.clone(),
-            None            => panic!("[ client ] [ {} ] no binary file specified", name)
-        };
-        let locked = self.context.locked.clone();
-        thread::spawn(|| { start(name, source_path, kernel_path, locked) });
+    fn start(&mut self, reactor: &mut Reactor, token: Token) {
+        if self.context.binary.is_none() {
+            panic!("[ client ] [ {} ] no binary file specified", self.context.name);
+        }
+        self.token = token;
+        self.stopping = None;
+        self.decoder = Decoder::new();
+        if self.transfer.is_none() {
+            self.context.locked.store(false, Ordering::Relaxed);
+        }
+
+        // keep the serial source open so nothing the board sends in between requests gets lost
+        match OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(&self.input_path) {
+            Ok(file)    => {
+                println!("[ client ] [ {} ] started in serial mode", self.context.name);
+                reactor.register(file.as_raw_fd(), token, Interest::Read);
+                self.input_file = Some(file);
+            },
+            Err(_)      => println!("[ client ] [ {} ] unable to open serial source file. client terminated", self.context.name),
+        }
+    }
+
+    //
+    fn ready(&mut self, reactor: &mut Reactor) {
+        self.send(reactor);
+        self.receive(reactor);
+        self.serve(reactor);
+    }
+
+    //
+    fn stop(&mut self, reactor: &mut Reactor, message: &str) {
+        if let Some(input_file) = self.input_file.take() {
+            reactor.deregister(input_file.as_raw_fd());
+        }
+        self.stopping = Some(String::from(message));
+        self.context.locked.store(true, Ordering::Relaxed);
+        self.serve(reactor);
+    }
+
+    //
+    fn busy(&self) -> bool {
+        self.transfer.is_some() || self.scripted.load(Ordering::Relaxed)
+    }
+
+    //
+    fn abort(&mut self, reactor: &mut Reactor) {
+        if let Some(mut transfer) = self.transfer.take() {
+            reactor.deregister(transfer.writer.as_raw_fd());
+            println!("[ client ] [ {} ] transmission aborted after 0x{:x} of 0x{:x} bytes", self.context.name, transfer.offset, transfer.data.len());
+            if !finish_frame(&mut transfer) {
+                println!("[ client ] [ {} ] board stopped reading, the frame is left unfinished", self.context.name);
+            }
+        }
+        if self.scripted.load(Ordering::Relaxed) {
+            println!("[ client ] [ {} ] script interrupted", self.context.name);
+        }
     }
 
     //
     #[cfg(feature = "controller")]
-    fn event(&mut self, data: u16) {
-        if self.context.locked.load(Ordering::Relaxed) == false {
-            self.source_file.write(&[(data >> 8) as u8, data as u8]).unwrap();
+    fn event(&mut self, reactor: &mut Reactor, payload: &::client::protocol::Payload) {
+        if !self.context.locked.load(Ordering::Relaxed) {
+            self.queue_events(reactor, payload.encode(self.context.events));
+        }
+    }
+
+    // capture the entire console output and hand it to the test harness instead of a script
+    fn harness(&mut self) -> Option<(Arc<Console>, File)> {
+        if self.context.script.take().is_some() {
+            println!("[ client ] [ {} ] script disabled in test mode", self.context.name);
+        }
+        self.console.capture(true);
+
+        //
+        match self.source_file.try_clone() {
+            Ok(writer)  => Some((self.console.clone(), writer)),
+            Err(_)      => panic!("[ client ] [ {} ] unable to share serial source file", self.context.name),
         }
     }
 
@@ -61,118 +388,129 @@ impl Client for SerialClient {
     }
 }
 
-//
-fn read_request(source_path: &str) -> Request {
-
-    // open serial source file
-    let mut source_file = match File::open(source_path) {
-        Ok(file)    => file,
-        Err(_)      => return Request::Terminate(String::from("unable to open serial source file"))
+// find the path of the binary that should be sent
+pub fn resolve_binary(context: &Context, binary: &BinaryFile) -> Option<String> {
+    let path = match &context.store {
+        Some(store) => store.resolve(&context.name, binary),
+        None        => Some(binary.path.clone()),
     };
 
     //
-    let mut buffer = [0];
+    if path.is_none() {
+        println!("[ client ] [ {} ] no stored revision of '{}' found", context.name, binary.path);
+    }
+    path
+}
 
-    // read incoming characters and break for transmition to start
-    loop {
-        source_file.read_exact(&mut buffer).unwrap();
-        match buffer[0] {
-            b'?'    => {
-                source_file.read_exact(&mut buffer).unwrap();
-                return match buffer[0] {
+// read and record the binary that should be sent
+pub fn read_binary(context: &Context, binary: &BinaryFile, path: &str, channel: Channel) -> Option<Vec<u8>> {
 
-                    //
-                    b'k'    => Request::TransmitKernel,
+    //
+    let data = match fs::read(path) {
+        Ok(data)    => data,
+        Err(_)      => {
+            println!("[ client ] [ {} ] unable to open binary file '{}'", context.name, path);
+            return None
+        },
+    };
 
-                    //
-                    b'f'    => {
-                        let mut path = String::new();
-                        loop {
-
-                            //
-                            if path.len() >= 128 {
-                                return Request::Terminate(String::from("exeeded path length"))
-                            }
-
-                            //
-                            source_file.read_exact(&mut buffer).unwrap();
-                            match buffer[0] {
-                                b'?'    => break,
-                                byte    => path.push(byte as char),
-                            }
-                        }
-                        Request::TransmitFile(path)
-                    },
+    // keep a copy of everything that is sent to the board
+    if let Some(store) = &context.store {
+        let hash = store.insert(&context.name, &binary.path, channel, &data);
+        println!("[ client ] [ {} ] serving '{}' ({})", context.name, binary.path, &hash[..12]);
+    }
+
+    Some(data)
+}
 
-                    // invalid request
-                    request => return Request::Terminate(format!("invalid request '{}'", request as char))
+// find, read and record the binary that should be sent once it settled
+pub fn load_binary(context: &Context, binary: &BinaryFile, channel: Channel) -> Option<Vec<u8>> {
+    let path = resolve_binary(context, binary)?;
+    if let Some(debounce) = context.debounce {
+        wait_stable(&path, debounce);
+    }
+    read_binary(context, binary, &path, channel)
+}
+
+// the board has no way to cancel a frame, so binaries are padded with zeros and events are sent in full
+fn finish_frame(transfer: &mut Transfer) -> bool {
+    if !transfer.events {
+        let offset = transfer.offset.max(TRANSMISSION_HEADER);
+        transfer.data[offset..].iter_mut().for_each(| byte | *byte = 0);
+    }
+
+    //
+    let mut stalled = Instant::now();
+    while transfer.offset < transfer.data.len() {
+        match transfer.writer.write(&transfer.data[transfer.offset..]) {
+            Ok(length)                                          => {
+                transfer.offset += length;
+                stalled = Instant::now();
+            },
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                if stalled.elapsed() > ABORT_STALL {
+                    return false;
                 }
+                thread::sleep(Duration::from_millis(1));
             },
-            byte    => print!("{}", byte as char),
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(_)                                              => return false,
         }
     }
+    true
 }
 
-//
-fn transmit_file(name: &str, source_path: &str, path: &str) {
-    use std::time::Instant;
-
-    // open serial source file and kernel binary file
-    if let Ok(binary) = File::open(path) {
-        let time = Instant::now();
+// send a binary and block until it's written
+pub fn transmit_file(name: &str, source_path: &str, data: &[u8]) -> Result<(), String> {
 
-        // send initial transmition character and send the size
-        let size = file_size(path);
-        println!("[ client ] [ {} ] transmitting 0x{:x} bytes", name, size);
-
-        //
-        let mut source_file = match File::create(source_path) {
-            Ok(file)    => file,
-            Err(_)      => panic!("[ client ] [ {} ] unable to open serial source file", name)
-        };
-
-        //
-        let buffer: [u8; 5] = [b'!', (size >> 24) as u8, (size >> 16) as u8, (size >> 8) as u8, size as u8];
-        source_file.write_all(&buffer).unwrap();
+    //
+    let time = Instant::now();
+    println!("[ client ] [ {} ] transmitting 0x{:x} bytes", name, data.len());
 
-        // iterate over every byte in the binary and send it
-        for byte in binary.bytes() {
-            source_file.write(&[byte.unwrap()]).unwrap();
-        }
+    //
+    let mut source_file = File::create(source_path).map_err(| _ | String::from("unable to open serial source file"))?;
+    source_file.write_all(&encode_transmission(data)).map_err(| _ | String::from("unable to transmit the binary"))?;
+    println!("[ client ] [ {} ] transmitted in {} second/s", name, time.elapsed().as_secs());
+    Ok(())
+}
 
-        println!("[ client ] [ {} ] transmitted in {} second/s", name, time.elapsed().as_secs());
-    } else {
-        println!("[ client ] [ {} ] unable to open binary file '{}'", name, path);
-    }
+// gives the client back once its script is done, even if the script panicked
+struct ScriptGuard {
+    console:        Arc<Console>,
+    scripted:       Arc<AtomicBool>,
+    locked:         Arc<AtomicBool>,
 }
 
 //
-fn start(name: String, source_path: String, kernel_path: String, locked: Arc<AtomicBool>) -> ! {
-    println!("[ client ] [ {} ] started in serial mode", name);
-
-    // wait for a new event to write over serial
-    loop {
-        match read_request(&source_path) {
+impl Drop for ScriptGuard {
+    fn drop(&mut self) {
+        self.console.capture(false);
+        self.scripted.store(false, Ordering::Relaxed);
+        self.locked.store(false, Ordering::Relaxed);
+    }
+}
 
-            //
-            Request::TransmitKernel     => {
-                locked.store(true, Ordering::Relaxed);
-                transmit_file(&name, &source_path, &kernel_path);
-                locked.store(false, Ordering::Relaxed);
-            },
+// run the script in the background so the console can be read in the meantime
+fn run_script(context: &Context, console: Arc<Console>, scripted: Arc<AtomicBool>, source_path: String) {
+    let context = context.clone();
+    let script = context.script.clone().unwrap();
 
-            //
-            Request::TransmitFile(path) => {
-                locked.store(true, Ordering::Relaxed);
-                transmit_file(&name, &source_path, &path);
-                locked.store(false, Ordering::Relaxed);
-            },
+    //
+    console.capture(true);
+    scripted.store(true, Ordering::Relaxed);
+    context.locked.store(true, Ordering::Relaxed);
+    println!("[ client ] [ {} ] running script '{}'", context.name, script.path);
 
-            //
-            Request::Terminate(message) => {
-                locked.store(true, Ordering::Relaxed);
-                panic!("[ client ] [ {} ] {}. client terminated", name, message)
-            },
+    //
+    thread::spawn(move || {
+        let _guard = ScriptGuard {
+            console:        console.clone(),
+            scripted:       scripted,
+            locked:         context.locked.clone(),
+        };
+        match script.run(&context, &console, &source_path) {
+            Ok(())          => println!("[ client ] [ {} ] script passed", context.name),
+            Err(message)    => println!("[ client ] [ {} ] script failed: {}", context.name, message),
         }
-    }
+    });
 }
@@ -0,0 +1,167 @@
+use client::context::*;
+use reactor::{ Reactor, Token, Interest };
+
+use std::os::unix::io::{ AsRawFd, FromRawFd };
+use std::time::{ Duration, Instant, SystemTime };
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io::{ Read, Write };
+use std::fs::{ self, File };
+use libc;
+
+// time left until a file hasn't been modified for the debounce duration
+pub fn remaining_unstable(path: &str, debounce: Duration) -> Option<Duration> {
+    let modified = fs::metadata(path).and_then(| metadata | metadata.modified()).ok()?;
+    let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+    debounce.checked_sub(age).filter(| remaining | *remaining > Duration::default())
+}
+
+// block until a file hasn't been modified for the debounce duration
+pub fn wait_stable(path: &str, debounce: Duration) {
+    while let Some(remaining) = remaining_unstable(path, debounce) {
+        thread::sleep(remaining);
+    }
+}
+
+// client that may be reset once its binary changes
+struct Target {
+    name:       String,
+    path:       String,
+    writer:     File,
+    locked:     Arc<AtomicBool>,
+}
+
+//
+pub struct Watcher {
+    notify:         File,
+    directories:    HashMap<i32, String>,
+    files:          Vec<String>,
+    changed:        HashMap<String, Instant>,
+    targets:        Vec<Target>,
+    debounce:       Duration,
+}
+
+//
+impl Watcher {
+
+    // watch every binary file including all of its channel variants
+    pub fn new(binary_files: &[BinaryFile], debounce: Duration) -> Self {
+        let descriptor = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if descriptor < 0 {
+            panic!("[ watcher ] unable to initialize inotify");
+        }
+        let notify = unsafe { File::from_raw_fd(descriptor) };
+
+        //
+        let mut files = Vec::new();
+        for binary in binary_files {
+            for channel in &[Channel::None, Channel::Stable, Channel::Beta, Channel::Nightly] {
+                files.push(format!("{}{}", binary.path, channel.suffix()));
+            }
+        }
+
+        // the files themselves are replaced by most build tools, so their directories are watched
+        let mut directories = HashMap::new();
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MODIFY | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM;
+        for file in &files {
+            let directory = match file.rfind('/') {
+                Some(index) => String::from(&file[..index + 1]),
+                None        => String::new(),
+            };
+            if directories.values().any(| watched | watched == &directory) {
+                continue;
+            }
+
+            //
+            let path = CString::new(if directory.is_empty() { "." } else { directory.as_str() }).expect("[ watcher ] invalid directory name");
+            match unsafe { libc::inotify_add_watch(descriptor, path.as_ptr(), mask) } {
+                watch if watch >= 0 => { directories.insert(watch, directory); },
+                _                   => println!("[ watcher ] unable to watch '{}'", directory),
+            }
+        }
+
+        //
+        Self {
+            notify:         notify,
+            directories:    directories,
+            files:          files,
+            changed:        HashMap::new(),
+            targets:        Vec::new(),
+            debounce:       debounce,
+        }
+    }
+
+    // reboot a client through the bootloader console whenever its binary changes
+    pub fn reset(&mut self, context: &Context, writer: File) {
+        if let Some(binary) = &context.binary {
+            if let Revision::Latest = binary.revision {
+                self.targets.push(Target {
+                    name:       context.name.clone(),
+                    path:       binary.path.clone(),
+                    writer:     writer,
+                    locked:     context.locked.clone(),
+                });
+            }
+        }
+    }
+
+    //
+    pub fn start(&self, reactor: &mut Reactor) {
+        println!("[ watcher ] watching {} binary file/s", self.files.len());
+        reactor.register(self.notify.as_raw_fd(), Token::Watcher, Interest::Read);
+    }
+
+    // collect the changes and reboot the clients once a file settled
+    pub fn ready(&mut self, reactor: &mut Reactor) {
+        let mut buffer = [0; 4096];
+
+        // restart the debounce timer every time a file is touched
+        while let Ok(length) = self.notify.read(&mut buffer) {
+            let mut offset = 0;
+            while offset + 16 <= length {
+                let watch = i32::from_ne_bytes([buffer[offset], buffer[offset + 1], buffer[offset + 2], buffer[offset + 3]]);
+                let size = u32::from_ne_bytes([buffer[offset + 12], buffer[offset + 13], buffer[offset + 14], buffer[offset + 15]]) as usize;
+                let name = buffer[offset + 16..offset + 16 + size].split(| byte | *byte == 0).next().unwrap_or(&[]);
+                offset += 16 + size;
+
+                //
+                if let Some(directory) = self.directories.get(&watch) {
+                    let path = format!("{}{}", directory, String::from_utf8_lossy(name));
+                    if self.files.contains(&path) {
+                        self.changed.insert(path, Instant::now());
+                    }
+                }
+            }
+        }
+
+        //
+        let debounce = self.debounce;
+        let settled: Vec<String> = self.changed.iter().filter(| (_, changed) | changed.elapsed() >= debounce).map(| (path, _) | path.clone()).collect();
+        for path in settled {
+            self.changed.remove(&path);
+
+            //
+            if fs::metadata(&path).is_err() {
+                println!("[ watcher ] '{}' removed", path);
+                continue;
+            }
+
+            //
+            println!("[ watcher ] '{}' changed", path);
+            for target in self.targets.iter_mut().filter(| target | target.path == path) {
+                if target.locked.load(Ordering::Relaxed) {
+                    println!("[ watcher ] [ {} ] client is busy, skipping reset", target.name);
+                } else if target.writer.write_all(b"reboot\r").is_err() {
+                    println!("[ watcher ] [ {} ] unable to send reset", target.name);
+                } else {
+                    println!("[ watcher ] [ {} ] reset sent", target.name);
+                }
+            }
+        }
+
+        // check again once the next file settled
+        if let Some(changed) = self.changed.values().min() {
+            reactor.timer(debounce.saturating_sub(changed.elapsed()), Token::Watcher);
+        }
+    }
+}
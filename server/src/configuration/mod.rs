@@ -0,0 +1,292 @@
+use parser::{ Parser, Item, Channel };
+use client::{ self, Mode };
+use harness;
+#[cfg(feature = "controller")]
+use controller;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+
+// settings from the command line that take precedence over the configuration file, clients are selected by name or tag
+#[derive(Clone)]
+pub struct Overrides {
+    pub clients:        Vec<String>,
+    pub channel:        Option<Channel>,
+    pub controllers:    bool,
+}
+
+//
+impl Overrides {
+
+    // every client and controller as configured
+    pub fn new() -> Self {
+        Self {
+            clients:        Vec::new(),
+            channel:        None,
+            controllers:    true,
+        }
+    }
+}
+
+// write a command followed by a carriage return to the serial device of a client
+fn send_command(client_name: &str, mode: &Mode, command: &str) -> bool {
+    let output_path = match mode {
+        Mode::Serial(_, output_path)    => output_path,
+        Mode::Ethernet(_)               => {
+            println!("[ client ] [ {} ] commands can only be sent to serial clients", client_name);
+            return false;
+        },
+    };
+
+    //
+    let mut writer = match OpenOptions::new().write(true).open(output_path) {
+        Ok(file)    => file,
+        Err(_)      => {
+            println!("[ client ] [ {} ] unable to open serial source file", client_name);
+            return false;
+        },
+    };
+
+    //
+    match writer.write_all(format!("{}\r", command).as_bytes()) {
+        Ok(())  => {
+            println!("[ client ] [ {} ] sent '{}'", client_name, command);
+            true
+        },
+        Err(_)  => {
+            println!("[ client ] [ {} ] unable to send '{}'", client_name, command);
+            false
+        },
+    }
+}
+
+// everything a configuration file describes
+pub struct Configuration {
+    pub client_manager:     client::Manager,
+    #[cfg(feature = "controller")]
+    pub controller_manager: controller::Manager,
+    pub client_names:       Vec<String>,
+    pub control_path:       Option<String>,
+    #[cfg(feature = "driver")]
+    pub driver:             bool,
+    #[cfg(feature = "system")]
+    pub system_path:        Option<String>,
+}
+
+//
+impl Configuration {
+
+    // parse the configuration, only the clients selected by the overrides are enabled
+    pub fn new(configuration_path: &str, overrides: &Overrides) -> Self {
+
+        // managers
+        let client_manager = item!(client::Manager::new());
+        if let Some(channel) = overrides.channel {
+            client_manager.borrow_mut().channel(channel);
+        }
+        #[cfg(feature = "controller")]
+        let mut controller_manager = controller::Manager::new();
+
+        // keyboard driver
+        #[cfg(feature = "driver")]
+        let mut driver = false;
+
+        // devices lookup path
+        let lookup_path = item!(String::new());
+
+        // named pipe for commands at runtime
+        let mut control_path: Option<String> = None;
+
+        // file system
+        #[cfg(feature = "system")]
+        let mut system_path: Option<String> = None;
+
+        //
+        let client_names: Item<Vec<String>> = item!(Default::default());
+
+        // clients and controllers are set up once the groups and assignments are known
+        let mut clients: Vec<(String, String, String, Vec<String>)> = Vec::new();
+        #[cfg(feature = "controller")]
+        let mut controllers: Vec<(String, String)> = Vec::new();
+        #[cfg(feature = "controller")]
+        let mut shares: Vec<(String, Vec<String>)> = Vec::new();
+
+        {
+            //
+            let mut parser = Parser::new(configuration_path);
+
+            //
+            parser.register("binary", false, Box::new(| stack, prefix | {
+                stack.push_debug("no binary file specified");
+                client_manager.borrow_mut().binary(format!("{}{}", prefix.unwrap(), stack.pop()), stack.pop_name());
+            }));
+
+            //
+            parser.register("lookup", true, Box::new(| stack, _ | {
+                *lookup_path.borrow_mut() = stack.pop_directory();
+            }));
+
+            //
+            parser.register("store", true, Box::new(| stack, _ | {
+                let directory = stack.pop_directory();
+                client_manager.borrow_mut().store(directory, stack.pop_usize());
+            }));
+
+            //
+            parser.register("watch", true, Box::new(| stack, _ | {
+                client_manager.borrow_mut().debounce(stack.pop_u64());
+            }));
+
+            //
+            parser.register("control", true, Box::new(| stack, _ | {
+                stack.push_debug("no control pipe specified");
+                control_path = Some(stack.pop());
+            }));
+
+            // the words after the name are tags
+            parser.register("client", false, Box::new(| stack, prefix | {
+                stack.push_debug("no client name specified");
+                let client_name = stack.pop();
+                assert!(client_names.borrow().iter().find(| name | name == &&client_name).is_none(), "[ server ] client names must be unique");
+                let tags = stack.pop_line().split_whitespace().map(String::from).collect();
+                clients.push((lookup_path.borrow().clone(), prefix.unwrap().to_string(), client_name.clone(), tags));
+                client_names.borrow_mut().push(client_name);
+            }));
+
+            // members are client names, tags or groups defined before
+            parser.register("group", false, Box::new(| stack, prefix | {
+                stack.push_debug("no group name specified");
+                let group_name = format!("{}{}", prefix.unwrap(), stack.pop());
+                let members = stack.pop_line().split_whitespace().map(String::from).collect();
+                client_manager.borrow_mut().group(group_name, members);
+            }));
+
+            //
+            parser.register("channel", false, Box::new(| stack, _ | {
+                stack.push_debug("no client, tag or group specified");
+                let selector = stack.pop();
+                client_manager.borrow_mut().assign_channel(selector, stack.pop_channel());
+            }));
+
+            //
+            parser.register("use", false, Box::new(| stack, _ | {
+                stack.push_debug("no client, tag or group specified");
+                let selector = stack.pop();
+                let binary = stack.pop_binary(client_manager.borrow().binary_files());
+                client_manager.borrow_mut().assign_binary(selector, binary);
+            }));
+
+            //
+            #[cfg(feature = "controller")]
+            // controllers are named after their file unless a name follows
+            parser.register("controller", false, Box::new(| stack, prefix | {
+                stack.push_debug("no controller translation specified");
+                let file = stack.pop();
+                let name = match stack.pop_line().trim() {
+                    ""      => file.rsplit('/').next().and_then(| file | file.split('.').next()).map(String::from).unwrap_or_default(),
+                    name    => String::from(name),
+                };
+                controllers.push((format!("{}{}", prefix.unwrap(), file), name));
+            }));
+
+            // members are names of controllers, they share their target, modifiers and layers
+            #[cfg(feature = "controller")]
+            parser.register("share", false, Box::new(| stack, _ | {
+                stack.push_debug("no state group name specified");
+                let group_name = stack.pop();
+                let members = stack.pop_line().split_whitespace().map(String::from).collect();
+                shares.push((group_name, members));
+            }));
+
+            //
+            #[cfg(feature = "driver")]
+            parser.register("driver", true, Box::new(| stack, _ | {
+                driver = stack.pop_state();
+            }));
+
+            //
+            #[cfg(feature = "system")]
+            parser.register("system", true, Box::new(| stack, _ | {
+                system_path = Some(stack.pop_directory());
+            }));
+
+            //
+            parser.parse();
+        }
+
+        //
+        let mut client_manager = unwrap_item!(client_manager);
+        for (index, (lookup_path, prefix, client_name, tags)) in clients.into_iter().enumerate() {
+            client_manager.initialize(&lookup_path, &prefix, client_name, index, tags);
+        }
+        client_manager.select(&overrides.clients);
+
+        // controllers can target clients by index or name, followed by groups and tags
+        #[cfg(feature = "controller")]
+        if overrides.controllers {
+            let mut targets = client_names.borrow().clone();
+            targets.extend(client_manager.targets());
+            for (translation_path, name) in controllers {
+                controller_manager.initialize(name, &targets, translation_path);
+            }
+            for (group_name, members) in shares {
+                controller_manager.share(&group_name, &members);
+            }
+        }
+
+        //
+        Self {
+            client_manager:     client_manager,
+            #[cfg(feature = "controller")]
+            controller_manager: controller_manager,
+            client_names:       unwrap_item!(client_names),
+            control_path:       control_path,
+            #[cfg(feature = "driver")]
+            driver:             driver,
+            #[cfg(feature = "system")]
+            system_path:        system_path,
+        }
+    }
+
+    // run the client under test against a spec
+    pub fn test(self, selector: String, spec_path: String) -> ! {
+        harness::run(self.client_manager, selector, spec_path);
+    }
+
+    // summary of a configuration, no device is opened
+    pub fn check(self) {
+        let enabled = self.client_manager.settings().iter().filter(| (context, _) | context.enabled).count();
+        println!("[ server ] configuration is valid, {} of {} client/s enabled", enabled, self.client_names.len());
+    }
+
+    // every client of the configuration, one per line
+    pub fn list_clients(self) {
+        for (context, mode) in self.client_manager.settings() {
+            let (mode, device) = match mode {
+                Mode::Serial(input, output) if input == output  => ("serial", input.clone()),
+                Mode::Serial(input, output)                     => ("pipe", format!("{} {}", input, output)),
+                Mode::Ethernet(ip_address)                      => ("ethernet", ip_address.to_string()),
+            };
+            let binary = context.binary.as_ref().map(| binary | binary.path.as_str()).unwrap_or("-");
+            let state = if context.enabled { "enabled" } else { "disabled" };
+            let tags = if context.tags.is_empty() { String::from("-") } else { context.tags.join(",") };
+            println!("{:<16} {:<8} {:<8} {:<8} {:<32} {:<24} {}", context.name, state, mode, context.channel.name(), binary, device, tags);
+        }
+    }
+
+    // write a command to the bootloader console of every client a name, tag or group refers to without starting them, returns false if any failed
+    pub fn send(self, selector: &str, command: &str) -> bool {
+        let clients = self.client_manager.matching(selector);
+        if clients.is_empty() {
+            println!("[ server ] no client, tag or group '{}' found", selector);
+            return false;
+        }
+
+        //
+        let mut sent = true;
+        for (context, mode) in clients {
+            sent &= send_command(&context.name, mode, command);
+        }
+        sent
+    }
+}
@@ -0,0 +1,129 @@
+use reactor::{ Reactor, Token, Interest };
+
+use std::os::unix::fs::{ FileTypeExt, OpenOptionsExt };
+use std::os::unix::io::AsRawFd;
+use std::io::{ Read, ErrorKind };
+use std::fs::{ self, File, OpenOptions };
+use std::ffi::CString;
+use libc;
+
+// longest line accepted on the control pipe
+const LINE_LENGTH: usize = 1024;
+
+// something written to the control pipe
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Enable(Vec<String>),
+    Disable(Vec<String>),
+    Status(Vec<String>),
+}
+
+//
+impl Command {
+
+    // parse a single line, the selectors are client names, tags or groups
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or_else(| | String::from("empty command"))?;
+        let selectors: Vec<String> = words.map(String::from).collect();
+
+        //
+        match command {
+            "enable" | "disable" if selectors.is_empty()    => Err(format!("no client, tag or group specified for '{}'", command)),
+            "enable"                                        => Ok(Command::Enable(selectors)),
+            "disable"                                       => Ok(Command::Disable(selectors)),
+            "status"                                        => Ok(Command::Status(selectors)),
+            command                                         => Err(format!("invalid command '{}'. valid options are 'enable', 'disable' or 'status'", command)),
+        }
+    }
+}
+
+// named pipe the server reads commands from while it's running
+pub struct Control {
+    path:       String,
+    file:       File,
+    buffer:     Vec<u8>,
+}
+
+//
+impl Control {
+
+    // the pipe is created if it doesn't exist yet
+    pub fn new(path: &str) -> Option<Self> {
+        let is_fifo = fs::metadata(path).map(| metadata | metadata.file_type().is_fifo());
+        match is_fifo {
+            Ok(true)    => {},
+            Ok(false)   => {
+                println!("[ control ] '{}' is not a named pipe", path);
+                return None;
+            },
+            Err(_)      => {
+                let name = CString::new(path).expect("[ control ] invalid pipe name");
+                if unsafe { libc::mkfifo(name.as_ptr(), 0o600) } != 0 {
+                    println!("[ control ] unable to create '{}'", path);
+                    return None;
+                }
+            },
+        }
+
+        // opened for writing as well, so the pipe never reaches the end of file when a writer closes it
+        match OpenOptions::new().read(true).write(true).custom_flags(libc::O_NONBLOCK).open(path) {
+            Ok(file)    => Some(Self {
+                path:       String::from(path),
+                file:       file,
+                buffer:     Vec::new(),
+            }),
+            Err(_)      => {
+                println!("[ control ] unable to open '{}'", path);
+                None
+            },
+        }
+    }
+
+    //
+    pub fn start(&self, reactor: &mut Reactor) {
+        println!("[ control ] listening on '{}'", self.path);
+        reactor.register(self.file.as_raw_fd(), Token::Control, Interest::Read);
+    }
+
+    //
+    pub fn stop(&self, reactor: &mut Reactor) {
+        reactor.deregister(self.file.as_raw_fd());
+    }
+
+    // take every complete line written so far, invalid commands are reported and dropped
+    pub fn ready(&mut self) -> Vec<Command> {
+        let mut chunk = [0; 256];
+        loop {
+            match self.file.read(&mut chunk) {
+                Ok(0)                                               => break,
+                Ok(length)                                          => self.buffer.extend_from_slice(&chunk[..length]),
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(_)                                              => break,
+            }
+        }
+
+        //
+        let mut commands = Vec::new();
+        while let Some(index) = self.buffer.iter().position(| byte | *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..index + 1).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            //
+            match Command::parse(&line) {
+                Ok(command) => commands.push(command),
+                Err(error)  => println!("[ control ] {}", error),
+            }
+        }
+
+        //
+        if self.buffer.len() > LINE_LENGTH {
+            println!("[ control ] exceeded line length");
+            self.buffer.clear();
+        }
+        commands
+    }
+}
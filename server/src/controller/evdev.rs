@@ -0,0 +1,59 @@
+use std::os::unix::io::AsRawFd;
+use std::mem::size_of;
+use std::fs::File;
+use std::ptr;
+use libc;
+
+// event types of the linux input subsystem
+pub const EV_SYN: u16                   = 0x00;
+pub const EV_KEY: u16                   = 0x01;
+pub const EV_REL: u16                   = 0x02;
+pub const EV_ABS: u16                   = 0x03;
+pub const EV_MSC: u16                   = 0x04;
+
+// values of key events
+pub const VALUE_UP: u64                 = 0;
+pub const VALUE_DOWN: u64               = 1;
+pub const VALUE_REPEAT: u64             = 2;
+
+// size of a struct input_event, the timestamp depends on the architecture
+pub const WIDTH: usize                  = size_of::<libc::input_event>();
+
+// _IOW('E', 0x90, int), the direction bits are laid out differently on some architectures
+#[cfg(any(target_arch = "mips", target_arch = "mips64", target_arch = "powerpc", target_arch = "powerpc64", target_arch = "sparc64"))]
+const EVIOCGRAB: libc::c_ulong          = 0x8004_4590;
+#[cfg(not(any(target_arch = "mips", target_arch = "mips64", target_arch = "powerpc", target_arch = "powerpc64", target_arch = "sparc64")))]
+const EVIOCGRAB: libc::c_ulong          = 0x4004_4590;
+
+// get an event type from its name as used in the translation files
+pub fn event_type(name: &str) -> Option<u64> {
+    match name {
+        "EV_KEY" | "key"    => Some(EV_KEY as u64),
+        "EV_REL" | "rel"    => Some(EV_REL as u64),
+        "EV_ABS" | "abs"    => Some(EV_ABS as u64),
+        "EV_MSC" | "msc"    => Some(EV_MSC as u64),
+        _                   => None,
+    }
+}
+
+// type, code and value of a single event, the buffer has to be WIDTH bytes long
+pub fn decode(buffer: &[u8]) -> (u16, u16, i32) {
+    assert!(buffer.len() >= WIDTH, "[ controller ] incomplete input event");
+    let event: libc::input_event = unsafe { ptr::read_unaligned(buffer.as_ptr() as *const libc::input_event) };
+    (event.type_, event.code, event.value)
+}
+
+// bytes of an event as the kernel would write them
+pub fn encode(kind: u16, code: u16, value: i32) -> Vec<u8> {
+    let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+    event.type_ = kind;
+    event.code = code;
+    event.value = value;
+    let bytes = unsafe { std::slice::from_raw_parts(&event as *const libc::input_event as *const u8, WIDTH) };
+    bytes.to_vec()
+}
+
+// take the device for ourselves, so no other reader sees its events
+pub fn grab(file: &File) -> bool {
+    unsafe { libc::ioctl(file.as_raw_fd(), EVIOCGRAB as _, 1 as libc::c_int) == 0 }
+}
@@ -1,8 +1,48 @@
-use std::sync::mpsc::Sender;
+use controller::keys;
+use controller::layout::Layout;
+use client::protocol::{ KeyEvent, KeyState, AxisEvent, Payload };
+use controller::modifiers::{ self, Modifiers, Expression };
 use parser::Stack;
+use std::time::Duration;
 
-//
-const TRANSLATION_SIZE: usize       = 128;
+// every key code of the linux input subsystem, KEY_MAX + 1
+const TRANSLATION_SIZE: usize       = 0x300;
+
+// most layers an event can declare
+pub const LAYERS: usize             = 64;
+
+// key identifiers may be given by their name, 'KEY_A' instead of 30
+fn pop_key(stack: &mut Stack) -> usize {
+    stack.pop_counter_or(| name | keys::code(name).map(| code | code as usize))
+}
+
+// modifiers may be given by their name or their offset
+fn pop_modifier(stack: &mut Stack, names: &[String]) -> u8 {
+    stack.push_debug("no modifier specified");
+    let name = stack.pop();
+    modifiers::offset(&name, names).unwrap_or_else(| message | stack.panic(message))
+}
+
+// values of axes may be negative
+fn pop_value(stack: &mut Stack) -> i64 {
+    stack.push_debug("no value specified");
+    let value = stack.pop();
+    value.parse().unwrap_or_else(| _ | stack.panic(format!("invalid value '{}'", value)))
+}
+
+// layers are given by their name and may be defined after they are used
+fn pop_layer(stack: &mut Stack, layers: &mut Vec<Layer>) -> u8 {
+    stack.push_debug("no layer specified");
+    let name = stack.pop();
+    if let Some(position) = layers.iter().position(| layer | layer.name == name) {
+        return position as u8;
+    }
+    if layers.len() == LAYERS {
+        stack.panic(format!("there can only be {} layers", LAYERS));
+    }
+    layers.push(Layer::new(name));
+    (layers.len() - 1) as u8
+}
 
 //
 pub type Field = u16;
@@ -11,21 +51,39 @@ pub type Field = u16;
 #[derive(Copy, Clone)]
 pub enum Action {
 
-    // character, down, repeat
+    // character, down, repeat and up
     Press(u8),
 
-    // mode bit offset, down
+    // key without a character, down, repeat and up
+    Key,
+
+    // sequence index, down, repeat and up
+    Send(u16),
+
+    // modifier offset, down
     Toggle(u8),
 
-    // mode bit offset, up, down
+    // modifier offset, up, down
     Set(u8),
 
     // client index
     Target(u8),
 
-    // modifier index
+    // modifier offset
     Push(u8),
 
+    // layer index, active while the key is held
+    Hold(u8),
+
+    // layer index, down
+    Lock(u8),
+
+    // layer index, active until the next key is pressed
+    OneShot(u8),
+
+    // whatever the layer below does
+    Transparent,
+
     //
     None,
 }
@@ -43,6 +101,8 @@ pub struct Rules {
      pub value_down:    Value,
      pub value_repeat:  Value,
      pub value_center:  Value,
+     pub evdev:         bool,
+     pub modifiers:     Vec<String>,
 }
 
 //
@@ -57,54 +117,287 @@ impl Rules {
             value_down:     None,
             value_repeat:   None,
             value_center:   None,
+            evdev:          false,
+            modifiers:      Vec::new(),
         }
     }
 }
 
+// where repeats of a held key come from
+#[derive(Copy, Clone, PartialEq)]
+pub enum Repeat {
+    Device,
+    Host(Duration, Duration),
+    None,
+}
+
+// what happens to repeats and releases of the keys of an event
+#[derive(Copy, Clone)]
+pub struct Typematic {
+    pub repeat:         Repeat,
+    pub release:        bool,
+}
+
 //
 pub type Translation = [Action; TRANSLATION_SIZE];
 
 //
 pub struct Mode {
     pub translation:    Translation,
-    pub mask:           u8,
+    pub expression:     Expression,
 }
 
 //
 impl Mode {
 
     //
-    pub fn new(translation: Translation, mask: u8) -> Self {
+    pub fn new(translation: Translation, expression: Expression) -> Self {
         Self {
             translation:    translation,
-            mask:           mask,
+            expression:     expression,
+        }
+    }
+}
+
+// layers are stacked on top of the modes, entries that aren't set fall through to the layers below
+pub struct Layer {
+    pub name:           String,
+    pub translation:    Translation,
+    pub defined:        bool,
+}
+
+//
+impl Layer {
+
+    //
+    pub fn new(name: String) -> Self {
+        Self {
+            name:           name,
+            translation:    [Action::Transparent; TRANSLATION_SIZE],
+            defined:        false,
+        }
+    }
+}
+
+// state a controller keeps between events
+#[derive(Clone, Default)]
+pub struct State {
+    pub target:         u8,
+    pub modifiers:      Modifiers,
+    pub layers:         u64,
+    pub oneshot:        u64,
+
+    // actions of the keys that are held along with their controller and event, so they are released by the layer
+    // they were pressed in
+    held:               Vec<(usize, u64, u64, Action)>,
+
+    //
+    positions:          Vec<Position>,
+}
+
+//
+impl State {
+
+    //
+    pub fn new(target: u8) -> Self {
+        Self {
+            target:         target,
+            ..Self::default()
+        }
+    }
+
+    //
+    pub fn layer_active(&self, layer: u8) -> bool {
+        (self.layers | self.oneshot) & 1 << layer != 0
+    }
+
+    // let go of every key held on a controller, when its source is gone their releases never arrive
+    pub fn release(&mut self, source: usize) {
+        let (released, held) = self.held.drain(..).partition(| (controller, _, _, _) | *controller == source);
+        self.held = held;
+        self.positions.retain(| position | position.source != source);
+        for (_, _, _, action) in released {
+            match action {
+                Action::Set(offset) => self.modifiers.set(offset, false),
+                Action::Hold(layer) => self.layers &= !(1 << layer),
+                _                   => {},
+            }
+        }
+    }
+}
+
+// largest position an axis is scaled to
+pub const AXIS_MAXIMUM: i64         = 32767;
+
+// what the position of an axis turns into
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Output {
+
+    // the keys on the low and high side are pressed once the axis leaves the deadzone
+    Keys(usize, usize),
+
+    // the position is sent as it changes
+    Analog,
+
+    //
+    None,
+}
+
+// analog axis, positions within the deadzone around the center count as the center and the hysteresis keeps
+// a noisy axis from flipping back and forth
+#[derive(Clone, Debug)]
+pub struct Axis {
+    pub code:           u64,
+    pub minimum:        i64,
+    pub maximum:        i64,
+    pub center:         i64,
+    pub deadzone:       i64,
+    pub hysteresis:     i64,
+    pub output:         Output,
+}
+
+//
+impl Axis {
+
+    //
+    pub fn new(code: u64, minimum: i64, maximum: i64, center: i64) -> Self {
+        Self {
+            code:           code,
+            minimum:        minimum,
+            maximum:        maximum,
+            center:         center,
+            deadzone:       0,
+            hysteresis:     0,
+            output:         Output::None,
+        }
+    }
+
+    // the side of the center the axis is on, it has to come back past the hysteresis to leave a side
+    pub fn zone(&self, value: i64, zone: i8) -> i8 {
+        let offset = value - self.center;
+        let (high, low) = match zone {
+            1   => (self.deadzone - self.hysteresis, self.deadzone),
+            -1  => (self.deadzone, self.deadzone - self.hysteresis),
+            _   => (self.deadzone, self.deadzone),
+        };
+        if offset > high {
+            1
+        } else if -offset > low {
+            -1
+        } else {
+            0
+        }
+    }
+
+    // position scaled from the edge of the deadzone to the end of the range
+    pub fn scale(&self, value: i64) -> i16 {
+        let offset = value - self.center;
+        let span = match offset > 0 {
+            true    => self.maximum - self.center - self.deadzone,
+            false   => self.center - self.minimum - self.deadzone,
+        };
+        if offset.abs() <= self.deadzone {
+            return 0;
         }
+        let scaled = (offset.abs() - self.deadzone) * AXIS_MAXIMUM / span.max(1);
+        (scaled.min(AXIS_MAXIMUM) * offset.signum()) as i16
     }
 }
 
+// last known position of an axis of a controller
+#[derive(Clone, Debug)]
+struct Position {
+    source:             usize,
+    identifier:         u64,
+    code:               u64,
+    zone:               i8,
+    value:              i64,
+    scaled:             i16,
+}
+
+// where the entries of the file go
+#[derive(Copy, Clone)]
+enum Section {
+    Base,
+    Mode(usize),
+    Layer(usize),
+}
+
 //
 pub struct Event {
     pub modes:          Vec<Mode>,
-    pub base_mode:   Option<Mode>,
+    pub base_mode:      Option<Mode>,
+    pub layers:         Vec<Layer>,
+    pub axes:           Vec<Axis>,
+    pub sequences:      Vec<Vec<u8>>,
+    pub typematic:      Typematic,
     pub rules:          Rules,
     pub identifier:     u64,
+
+    // index of the controller, keys held on controllers that share their state are told apart by it
+    pub source:         usize,
 }
 
 //
 impl Event {
 
     //
-    pub fn new(rules: Rules, event_path: String, identifier: u64, client_names: &Vec<String>) -> Self {
+    pub fn new(rules: Rules, event_path: String, identifier: u64, client_names: &[String]) -> Self {
+        Self::with_layout(rules, Some(event_path), None, identifier, client_names)
+    }
+
+    // the layout is read before the event file, which may change or add to it
+    pub fn with_layout(rules: Rules, event_path: Option<String>, layout: Option<&Layout>, identifier: u64, client_names: &[String]) -> Self {
         use parser::{ Parser, Item };
 
+        // the modifiers of a layout come before the ones of the controller
+        let mut rules = rules;
+        if let Some(layout) = layout {
+            for name in layout.modifiers.iter().rev() {
+                if !rules.modifiers.iter().any(| declared | declared == name) {
+                    rules.modifiers.insert(0, String::from(*name));
+                }
+            }
+        }
+        assert!(rules.modifiers.len() <= modifiers::MODIFIERS, "[ controller ] there can only be {} modifiers", modifiers::MODIFIERS);
+        let names = rules.modifiers.clone();
+
         //
-        let mut modes: Item<Vec<Mode>> = item!(Vec::new());
-        let mut base_mode: Item<Option<Mode>> = item!(None);
-        let mut rules = item!(rules);
+        let modes: Item<Vec<Mode>> = item!(Vec::new());
+        let base_mode: Item<Option<Mode>> = item!(None);
+        let layers: Item<Vec<Layer>> = item!(Vec::new());
+        let current: Item<Section> = item!(Section::Base);
+        let sequences: Item<Vec<Vec<u8>>> = item!(Vec::new());
+        let axes: Item<Vec<Axis>> = item!(Vec::new());
+        let mut repeat = Repeat::Device;
+        let mut release = false;
+        let rules = item!(rules);
+
+        // entries go into the mode or layer that was selected last
+        let assign = | index: usize, action: Action | {
+            if index >= TRANSLATION_SIZE {
+                panic!("[ controller ] key identifier {} is out of range", index);
+            }
+            match *current.borrow() {
+                Section::Mode(mode)     => modes.borrow_mut()[mode].translation[index] = action,
+                Section::Layer(layer)   => layers.borrow_mut()[layer].translation[index] = action,
+                Section::Base           => match *base_mode.borrow_mut() {
+                    Some(ref mut mode)  => mode.translation[index] = action,
+                    None                => panic!("[ controller ] no mode initialized"),
+                },
+            }
+        };
 
         {
             //
-            let mut parser = Parser::new(&event_path);
+            let mut parser = match (&event_path, layout) {
+                (Some(event_path), _)   => Parser::new(event_path),
+                (None, Some(layout))    => Parser::from_text(&format!("layouts/{}.event", layout.name), layout.content),
+                (None, None)            => panic!("[ controller ] no event file or layout specified"),
+            };
+            if let (Some(_), Some(layout)) = (&event_path, layout) {
+                parser.prelude(&format!("layouts/{}.event", layout.name), layout.content);
+            }
 
             //
             parser.register("byte", false, Box::new(| stack, _ | {
@@ -128,227 +421,467 @@ impl Event {
                 }
             }));
 
+            // the device repeats held keys by itself, the server does it after a delay in milliseconds and
+            // with a rate in repeats per second, or held keys aren't repeated at all
+            parser.register("repeat", true, Box::new(| stack, _ | {
+                stack.push_debug("no repeat specified. valid options are 'device', 'host' or 'none'");
+                repeat = match stack.pop().as_ref() {
+                    "device"    => Repeat::Device,
+                    "host"      => {
+                        let delay = Duration::from_millis(stack.pop_u64());
+                        let rate = stack.pop_u64();
+                        if rate == 0 {
+                            stack.panic(String::from("repeat rate must be at least one per second"));
+                        }
+                        Repeat::Host(delay, Duration::from_secs(1) / rate as u32)
+                    },
+                    "none"      => Repeat::None,
+                    repeat      => stack.panic(format!("invalid repeat '{}'. valid options are 'device', 'host' or 'none'", repeat)),
+                };
+            }));
+
             //
+            parser.register("release", true, Box::new(| stack, _ | {
+                release = stack.pop_state();
+            }));
+
+            // modes are selected by an expression on the modifiers, a mode that already exists is selected again,
+            // so files can add to a layout. keys a mode doesn't set fall through to the next mode that matches
             parser.register("mode", false, Box::new(| stack, _ | {
-                match stack.pop_mode() {
-                    Some(mask)  => {
-                        let translation = match *base_mode.borrow() {
-                            Some(ref mode)  => mode.translation.clone(),
-                            None        => [Action::None; TRANSLATION_SIZE],
-                        };
-                        modes.borrow_mut().push(Mode::new(translation, mask))
+                let expression = match stack.pop_line().as_ref() {
+                    ""      => stack.panic(String::from("no mode specified")),
+                    "base"  => None,
+                    line    => Some(Expression::parse(line, &names).unwrap_or_else(| message | stack.panic(message))),
+                };
+                match expression {
+                    Some(expression)    => {
+                        let existing = modes.borrow().iter().position(| mode | mode.expression == expression);
+                        let position = existing.unwrap_or_else(| | {
+                            modes.borrow_mut().push(Mode::new([Action::Transparent; TRANSLATION_SIZE], expression));
+                            modes.borrow().len() - 1
+                        });
+                        *current.borrow_mut() = Section::Mode(position);
+                    },
+                    None                => {
+                        if base_mode.borrow().is_none() {
+                            *base_mode.borrow_mut() = Some(Mode::new([Action::None; TRANSLATION_SIZE], Expression::Exact(0)));
+                        }
+                        *current.borrow_mut() = Section::Base;
                     },
-                    None        => *base_mode.borrow_mut() = Some(Mode::new([Action::None; TRANSLATION_SIZE], 0)),
                 }
             }));
 
+            // layers declared later are stacked higher
+            parser.register("layer", false, Box::new(| stack, _ | {
+                let position = pop_layer(stack, &mut layers.borrow_mut()) as usize;
+                layers.borrow_mut()[position].defined = true;
+                *current.borrow_mut() = Section::Layer(position);
+            }));
+
             //
             parser.register("press", false, Box::new(| stack, _ | {
-                let index = stack.pop_counter();
-                let action = Action::Press(stack.pop_ascii());
-                match modes.borrow_mut().last_mut() {
-                    Some(mode)  => mode.translation[index] = action, // TODO: assure it's unique
-                    None        => {
-                        match *base_mode.borrow_mut() {
-                            Some(ref mut mode)  => mode.translation[index] = action,
-                            None        => panic!("[ controller ] no mode initialized"),
-                        }
+                let index = pop_key(stack);
+                assign(index, Action::Press(stack.pop_ascii()));
+            }));
+
+            //
+            parser.register("key", false, Box::new(| stack, _ | {
+                let index = pop_key(stack);
+                assign(index, Action::Key);
+            }));
+
+            // strings are sent byte by byte, so escape sequences and utf-8 characters both work
+            parser.register("send", false, Box::new(| stack, _ | {
+                let index = pop_key(stack);
+                stack.push_debug("no sequence specified");
+                let sequence = stack.pop().into_bytes();
+                if sequence.is_empty() {
+                    stack.panic(String::from("empty sequence"));
+                }
+                let mut sequences = sequences.borrow_mut();
+                let position = match sequences.iter().position(| existing | *existing == sequence) {
+                    Some(position)  => position,
+                    None            => {
+                        sequences.push(sequence);
+                        sequences.len() - 1
                     },
                 };
+                assign(index, Action::Send(position as u16));
             }));
 
             //
             parser.register("toggle", false, Box::new(| stack, _ | {
-                let index = stack.pop_counter();
-                let action = Action::Toggle(stack.pop_u8());
-                match modes.borrow_mut().last_mut() {
-                    Some(mode)  => mode.translation[index] = action, // TODO: assure it's unique
-                    None        => {
-                        match *base_mode.borrow_mut() {
-                            Some(ref mut mode)  => mode.translation[index] = action,
-                            None        => panic!("[ controller ] no mode initialized"),
-                        }
-                    },
-                };
+                let index = pop_key(stack);
+                assign(index, Action::Toggle(pop_modifier(stack, &names)));
             }));
 
             //
             parser.register("set", false, Box::new(| stack, _ | {
-                let index = stack.pop_counter();
-                let action = Action::Set(stack.pop_u8());
-                match modes.borrow_mut().last_mut() {
-                    Some(mode)  => mode.translation[index] = action, // TODO: assure it's unique
-                    None        => {
-                        match *base_mode.borrow_mut() {
-                            Some(ref mut mode)  => mode.translation[index] = action,
-                            None        => panic!("[ controller ] no mode initialized"),
-                        }
-                    },
-                };
+                let index = pop_key(stack);
+                assign(index, Action::Set(pop_modifier(stack, &names)));
             }));
 
             //
             parser.register("target", false, Box::new(| stack, _ | {
-                let index = stack.pop_counter();
-                let action = Action::Target(stack.pop_client(client_names));
-                match modes.borrow_mut().last_mut() {
-                    Some(mode)  => mode.translation[index] = action, // TODO: assure it's unique
-                    None        => {
-                        match *base_mode.borrow_mut() {
-                            Some(ref mut mode)  => mode.translation[index] = action,
-                            None        => panic!("[ controller ] no mode initialized"),
-                        }
-                    },
-                };
+                let index = pop_key(stack);
+                assign(index, Action::Target(stack.pop_client(client_names)));
             }));
 
             //
             parser.register("push", false, Box::new(| stack, _ | {
-                let index = stack.pop_counter();
-                let action = Action::Push(stack.pop_u8());
-                match modes.borrow_mut().last_mut() {
-                    Some(mode)  => mode.translation[index] = action, // TODO: assure it's unique
-                    None        => {
-                        match *base_mode.borrow_mut() {
-                            Some(ref mut mode)  => mode.translation[index] = action,
-                            None        => panic!("[ controller ] no mode initialized"),
-                        }
-                    },
+                let index = pop_key(stack);
+                assign(index, Action::Push(pop_modifier(stack, &names)));
+            }));
+
+            //
+            parser.register("hold", false, Box::new(| stack, _ | {
+                let index = pop_key(stack);
+                assign(index, Action::Hold(pop_layer(stack, &mut layers.borrow_mut())));
+            }));
+
+            //
+            parser.register("lock", false, Box::new(| stack, _ | {
+                let index = pop_key(stack);
+                assign(index, Action::Lock(pop_layer(stack, &mut layers.borrow_mut())));
+            }));
+
+            //
+            parser.register("oneshot", false, Box::new(| stack, _ | {
+                let index = pop_key(stack);
+                assign(index, Action::OneShot(pop_layer(stack, &mut layers.borrow_mut())));
+            }));
+
+            // the key does nothing, even if a layer below would
+            parser.register("block", false, Box::new(| stack, _ | {
+                let index = pop_key(stack);
+                assign(index, Action::None);
+            }));
+
+            // the key does whatever the layer below does
+            parser.register("transparent", false, Box::new(| stack, _ | {
+                let index = pop_key(stack);
+                assign(index, Action::Transparent);
+            }));
+
+            // range of an axis, its center is in the middle unless it's given
+            parser.register("axis", false, Box::new(| stack, _ | {
+                let code = pop_key(stack) as u64;
+                let (minimum, maximum) = (pop_value(stack), pop_value(stack));
+                let center = match stack.pop_line().trim() {
+                    ""      => minimum + (maximum - minimum) / 2,
+                    center  => center.parse().unwrap_or_else(| _ | stack.panic(format!("invalid value '{}'", center))),
                 };
+                if minimum >= maximum || center < minimum || center > maximum {
+                    stack.panic(format!("invalid range {} to {} with center {}", minimum, maximum, center));
+                }
+                let mut axes = axes.borrow_mut();
+                axes.retain(| axis | axis.code != code);
+                axes.push(Axis::new(code, minimum, maximum, center));
+            }));
+
+            //
+            parser.register("deadzone", false, Box::new(| stack, _ | {
+                let code = pop_key(stack) as u64;
+                let deadzone = stack.pop_u64() as i64;
+                match axes.borrow_mut().iter_mut().find(| axis | axis.code == code) {
+                    Some(axis)  => axis.deadzone = deadzone,
+                    None        => stack.panic(format!("axis {} has no range", code)),
+                }
+            }));
+
+            //
+            parser.register("hysteresis", false, Box::new(| stack, _ | {
+                let code = pop_key(stack) as u64;
+                let hysteresis = stack.pop_u64() as i64;
+                match axes.borrow_mut().iter_mut().find(| axis | axis.code == code) {
+                    Some(axis)  => axis.hysteresis = hysteresis,
+                    None        => stack.panic(format!("axis {} has no range", code)),
+                }
+            }));
+
+            // the keys are translated like any other, so they may be set in every mode and layer
+            parser.register("direction", false, Box::new(| stack, _ | {
+                let code = pop_key(stack) as u64;
+                let (low, high) = (pop_key(stack), pop_key(stack));
+                match axes.borrow_mut().iter_mut().find(| axis | axis.code == code) {
+                    Some(axis)  => axis.output = Output::Keys(low, high),
+                    None        => stack.panic(format!("axis {} has no range", code)),
+                }
+            }));
+
+            //
+            parser.register("analog", false, Box::new(| stack, _ | {
+                let code = pop_key(stack) as u64;
+                match axes.borrow_mut().iter_mut().find(| axis | axis.code == code) {
+                    Some(axis)  => axis.output = Output::Analog,
+                    None        => stack.panic(format!("axis {} has no range", code)),
+                }
+            }));
+
+            // hat switches are axes that only report -1, 0 and 1
+            parser.register("hat", false, Box::new(| stack, _ | {
+                let code = pop_key(stack) as u64;
+                let (low, high) = (pop_key(stack), pop_key(stack));
+                let mut axis = Axis::new(code, -1, 1, 0);
+                axis.output = Output::Keys(low, high);
+                let mut axes = axes.borrow_mut();
+                axes.retain(| axis | axis.code != code);
+                axes.push(axis);
             }));
 
             parser.parse();
         }
 
         //
-        assert!(rules.borrow().bytes_id.is_some(), "[ controller ] no bytes for id specified");
+        // input devices bring their own codes and values
+        assert!(rules.borrow().evdev || rules.borrow().bytes_id.is_some(), "[ controller ] no bytes for id specified");
+        if let Some(layer) = layers.borrow().iter().find(| layer | !layer.defined) {
+            panic!("[ controller ] layer '{}' is used but never defined", layer.name);
+        }
 
         //
         Self {
             modes:          unwrap_item!(modes),
             base_mode:      unwrap_item!(base_mode),
+            layers:         unwrap_item!(layers),
+            axes:           unwrap_item!(axes),
+            sequences:      unwrap_item!(sequences),
+            typematic:      Typematic {
+                repeat:     repeat,
+                release:    release,
+            },
             rules:          unwrap_item!(rules),
             identifier:     identifier,
+            source:         0,
         }
     }
 
-    //
-    pub fn translate(&self, buffer: &Vec<u8>, target: &mut u8, modifiers: &mut u8, verbose: bool) -> Option<u8> {
+    // take the identifier and value out of a raw record
+    pub fn decode(&self, buffer: &[u8]) -> Option<(u64, Option<i64>)> {
         use parser::unwrap_sequence;
 
-        if let Some(bytes_id) = &self.rules.bytes_id {
+        //
+        let bytes_id = unwrap_sequence(buffer, self.rules.bytes_id.as_ref()?);
+        let bytes_value = self.rules.bytes_value.as_ref().map(| bytes_value | unwrap_sequence(buffer, bytes_value) as i64);
+        Some((bytes_id, bytes_value))
+    }
 
-            //
-            let bytes_id = unwrap_sequence(buffer, bytes_id);
+    //
+    pub fn translate(&self, buffer: &[u8], state: &mut State, verbose: bool) -> Option<KeyEvent> {
+        let (bytes_id, bytes_value) = self.decode(buffer)?;
+        self.translate_input(bytes_id, bytes_value, state, verbose)
+    }
+
+    // axes may turn into several key events at once, everything else into one at most
+    pub fn translate_events(&self, bytes_id: u64, bytes_value: Option<i64>, state: &mut State, verbose: bool) -> Vec<Payload> {
+        match (self.axes.iter().find(| axis | axis.code == bytes_id), bytes_value) {
+            (Some(axis), Some(bytes_value)) => self.translate_axis(axis, bytes_value, state, verbose),
+            _                               => self.translate_input(bytes_id, bytes_value, state, verbose).map(Payload::Key).into_iter().collect(),
+        }
+    }
+
+    // keys are released when the axis leaves their side and pressed when it enters it, analog positions are only
+    // sent once they moved further than the hysteresis or reached the center or an end
+    pub fn translate_axis(&self, axis: &Axis, value: i64, state: &mut State, verbose: bool) -> Vec<Payload> {
+        let value = value.clamp(axis.minimum, axis.maximum);
+        if verbose {
+            println!("[ controller ] axis {} at {}", axis.code, value);
+        }
+        let index = match state.positions.iter().position(| position | position.source == self.source && position.identifier == self.identifier && position.code == axis.code) {
+            Some(index) => index,
+            None        => {
+                state.positions.push(Position {
+                    source:     self.source,
+                    identifier: self.identifier,
+                    code:       axis.code,
+                    zone:       0,
+                    value:      axis.center,
+                    scaled:     0,
+                });
+                state.positions.len() - 1
+            },
+        };
+
+        //
+        let mut payloads = Vec::new();
+        match axis.output {
+            Output::Keys(low, high)     => {
+                let previous = state.positions[index].zone;
+                let zone = axis.zone(value, previous);
+                state.positions[index].zone = zone;
+                if zone != previous {
+                    let key = | zone: i8 | if zone < 0 { low } else { high };
+                    if previous != 0 {
+                        payloads.extend(self.act(key(previous) as u64, Some(KeyState::Up), value, state, verbose).map(Payload::Key));
+                    }
+                    if zone != 0 {
+                        payloads.extend(self.act(key(zone) as u64, Some(KeyState::Down), value, state, verbose).map(Payload::Key));
+                    }
+                }
+            },
+            Output::Analog              => {
+                let scaled = axis.scale(value);
+                let position = &mut state.positions[index];
+                let settled = scaled == 0 || scaled.unsigned_abs() as i64 == AXIS_MAXIMUM;
+                if scaled != position.scaled && (settled || (value - position.value).abs() > axis.hysteresis) {
+                    position.value = value;
+                    position.scaled = scaled;
+                    payloads.push(Payload::Axis(AxisEvent {
+                        code:   axis.code as u16,
+                        value:  scaled,
+                    }));
+                }
+            },
+            Output::None                => {},
+        }
+        payloads
+    }
+
+    // the active layers from the top down, then every mode that matches in order and the base mode last
+    fn resolve(&self, index: usize, state: &State) -> Action {
+        let layers = self.layers.iter().enumerate().rev().filter(| (layer, _) | state.layer_active(*layer as u8)).map(| (_, layer) | &layer.translation);
+        let modes = self.modes.iter().filter(| mode | mode.expression.matches(state.modifiers)).map(| mode | &mode.translation);
+        let base = self.base_mode.iter().map(| mode | &mode.translation);
+        match layers.chain(modes).chain(base).map(| translation | translation[index]).find(| action | !matches!(action, Action::Transparent)) {
+            Some(action)    => action,
+            None            => Action::None,
+        }
+    }
+
+    // translate a key identifier and its value, which is only compared against the value rules
+    pub fn translate_input(&self, bytes_id: u64, bytes_value: Option<i64>, state: &mut State, verbose: bool) -> Option<KeyEvent> {
+        if verbose {
+            println!("[ controller ] key identifier: {}", bytes_id);
+        }
+
+        //
+        let bytes_value = bytes_value?;
+        let is = | rule: Value | rule.is_some_and(| rule | rule as i64 == bytes_value);
+        let key_state = if is(self.rules.value_down) {
+            Some(KeyState::Down)
+        } else if is(self.rules.value_repeat) {
+            Some(KeyState::Repeat)
+        } else if is(self.rules.value_up) {
+            Some(KeyState::Up)
+        } else {
+            None
+        };
+        self.act(bytes_id, key_state, bytes_value, state, verbose)
+    }
+
+    // run the action of a key in the state it's in, keys that aren't pressed, released or repeated only push
+    fn act(&self, bytes_id: u64, key_state: Option<KeyState>, bytes_value: i64, state: &mut State, verbose: bool) -> Option<KeyEvent> {
+        if bytes_id as usize >= TRANSLATION_SIZE {
             if verbose {
-                println!("[ controller ] key identifier: {}", bytes_id);
+                println!("[ controller ] key identifier out of range: {}", bytes_id);
             }
+            return None;
+        }
 
-            let mode = match self.modes.iter().find(| mode | mode.mask == *modifiers) {
-                Some(mode)  => mode,
-                None        => match &self.base_mode {
-                    Some(mode)  => mode,
-                    None        => {
-                        if verbose {
-                            println!("[ controller ] unhandeled mode: {:b}", modifiers);
-                        }
-                        return None
-                    },
-                },
-            };
+        // held keys keep the action they were pressed with, even if the layers changed since
+        let held = state.held.iter().position(| (source, identifier, code, _) | *source == self.source && *identifier == self.identifier && *code == bytes_id);
+        let action = match (key_state, held) {
+            (Some(KeyState::Down), _)       => {
+                let action = self.resolve(bytes_id as usize, state);
+                if let Some(held) = held {
+                    state.held.remove(held);
+                }
+                state.held.push((self.source, self.identifier, bytes_id, action));
+                action
+            },
+            (Some(KeyState::Up), Some(held))    => state.held.remove(held).3,
+            (_, Some(held))                 => state.held[held].3,
+            (_, None)                       => self.resolve(bytes_id as usize, state),
+        };
+        if verbose {
+            println!("[ controller ] active layers: {:b}", state.layers | state.oneshot);
+        }
 
-            match mode.translation[bytes_id as usize] {
+        //
+        let modifiers = state.modifiers.bits();
+        let key_event = | character: Option<u8>, sequence: &[u8] | key_state.map(| key_state | KeyEvent {
+            code:       bytes_id as u16,
+            state:      key_state,
+            modifiers:  modifiers,
+            character:  character,
+            sequence:   sequence.to_vec(),
+        });
+
+        // one shot layers are used up by the next key that is pressed
+        if key_state == Some(KeyState::Down) && matches!(action, Action::Press(_) | Action::Key | Action::Send(_)) {
+            state.oneshot = 0;
+        }
 
-                //
-                Action::Press(character)    => {
-                    if let Some(bytes_value) = &self.rules.bytes_value {
-                        let bytes_value = unwrap_sequence(buffer, bytes_value);
+        //
+        match action {
 
-                        //
-                        if let Some(value_down) = self.rules.value_down {
-                            if bytes_value == value_down {
-                                return Some(character)
-                            }
-                        }
+            //
+            Action::Press(character)    => return key_event(Some(character), &[]),
 
-                        //
-                        if let Some(value_repeat) = self.rules.value_repeat {
-                            if bytes_value == value_repeat {
-                                return Some(character)
-                            }
-                        }
-                    }
-                },
+            //
+            Action::Key                 => return key_event(None, &[]),
 
-                // TODO: check offset bounds
-                Action::Set(offset)         => {
-                    if let Some(bytes_value) = &self.rules.bytes_value {
-                        let bytes_value = unwrap_sequence(buffer, bytes_value);
+            //
+            Action::Send(index)         => return key_event(None, &self.sequences[index as usize]),
 
-                        //
-                        if let Some(value_down) = self.rules.value_down {
-                            if bytes_value == value_down {
-                                *modifiers |= 1 << offset;
-                            }
-                        }
+            // offsets are checked when the file is parsed
+            Action::Set(offset)         => {
+                if key_state == Some(KeyState::Down) {
+                    state.modifiers.set(offset, true);
+                }
+                if key_state == Some(KeyState::Up) {
+                    state.modifiers.set(offset, false);
+                }
+            },
 
-                        //
-                        if let Some(value_up) = self.rules.value_up {
-                            if bytes_value == value_up {
-                                *modifiers &= !(1 << offset);
-                            }
-                        }
-                    }
-                },
+            //
+            Action::Toggle(offset)      => {
+                if key_state == Some(KeyState::Down) {
+                    state.modifiers.toggle(offset);
+                }
+            },
 
-                //
-                Action::Toggle(offset)      => {
-                    if let Some(bytes_value) = &self.rules.bytes_value {
-                        let bytes_value = unwrap_sequence(buffer, bytes_value);
+            //
+            Action::Target(new_target)  => {
+                if key_state == Some(KeyState::Down) {
+                    state.target = new_target;
+                    println!("[ controller ] switched target to {}", state.target);
+                }
+            },
 
-                        //
-                        if let Some(value_down) = self.rules.value_down {
-                            if bytes_value == value_down {
-                                *modifiers ^= 1 << offset;
-                            }
-                        }
-                    }
-                },
+            //
+            Action::Push(offset)        => {
+                if let Some(value_center) = self.rules.value_center {
+                    state.modifiers.set(offset, bytes_value < value_center as i64);
+                }
+            },
 
-                //
-                Action::Target(new_target)  => {
-                    if let Some(bytes_value) = &self.rules.bytes_value {
-                        let bytes_value = unwrap_sequence(buffer, bytes_value);
-
-                        //
-                        if let Some(value_down) = self.rules.value_down {
-                            if bytes_value == value_down {
-                                *target = new_target;
-                                println!("[ controller ] switched target to {}", target);
-                            }
-                        }
-                    }
-                },
+            //
+            Action::Hold(layer)         => {
+                if key_state == Some(KeyState::Down) {
+                    state.layers |= 1 << layer;
+                }
+                if key_state == Some(KeyState::Up) {
+                    state.layers &= !(1 << layer);
+                }
+            },
 
-                //
-                Action::Push(offset)        => {
-                    if let Some(bytes_value) = &self.rules.bytes_value {
-                        let bytes_value = unwrap_sequence(buffer, bytes_value);
-
-                        //
-                        if let Some(value_center) = self.rules.value_center {
-                            if bytes_value < value_center {
-                                *modifiers |= 1 << offset;
-                            } else {
-                                *modifiers &= !(1 << offset);
-                            }
-                        }
-                    }
-                },
+            //
+            Action::Lock(layer)         => {
+                if key_state == Some(KeyState::Down) {
+                    state.layers ^= 1 << layer;
+                }
+            },
 
-                //
-                _           => return None
-            }
+            //
+            Action::OneShot(layer)      => {
+                if key_state == Some(KeyState::Down) {
+                    state.oneshot |= 1 << layer;
+                }
+            },
+
+            //
+            Action::Transparent | Action::None => {},
         }
         None
     }
@@ -0,0 +1,186 @@
+// codes of the linux input subsystem by the names used in input-event-codes.h
+pub const CODES: &[(&str, u64)] = &[
+
+    // keys
+    ("KEY_ESC",           1),
+    ("KEY_1",             2),
+    ("KEY_2",             3),
+    ("KEY_3",             4),
+    ("KEY_4",             5),
+    ("KEY_5",             6),
+    ("KEY_6",             7),
+    ("KEY_7",             8),
+    ("KEY_8",             9),
+    ("KEY_9",             10),
+    ("KEY_0",             11),
+    ("KEY_MINUS",         12),
+    ("KEY_EQUAL",         13),
+    ("KEY_BACKSPACE",     14),
+    ("KEY_TAB",           15),
+    ("KEY_Q",             16),
+    ("KEY_W",             17),
+    ("KEY_E",             18),
+    ("KEY_R",             19),
+    ("KEY_T",             20),
+    ("KEY_Y",             21),
+    ("KEY_U",             22),
+    ("KEY_I",             23),
+    ("KEY_O",             24),
+    ("KEY_P",             25),
+    ("KEY_LEFTBRACE",     26),
+    ("KEY_RIGHTBRACE",    27),
+    ("KEY_ENTER",         28),
+    ("KEY_LEFTCTRL",      29),
+    ("KEY_A",             30),
+    ("KEY_S",             31),
+    ("KEY_D",             32),
+    ("KEY_F",             33),
+    ("KEY_G",             34),
+    ("KEY_H",             35),
+    ("KEY_J",             36),
+    ("KEY_K",             37),
+    ("KEY_L",             38),
+    ("KEY_SEMICOLON",     39),
+    ("KEY_APOSTROPHE",    40),
+    ("KEY_GRAVE",         41),
+    ("KEY_LEFTSHIFT",     42),
+    ("KEY_BACKSLASH",     43),
+    ("KEY_Z",             44),
+    ("KEY_X",             45),
+    ("KEY_C",             46),
+    ("KEY_V",             47),
+    ("KEY_B",             48),
+    ("KEY_N",             49),
+    ("KEY_M",             50),
+    ("KEY_COMMA",         51),
+    ("KEY_DOT",           52),
+    ("KEY_SLASH",         53),
+    ("KEY_RIGHTSHIFT",    54),
+    ("KEY_KPASTERISK",    55),
+    ("KEY_LEFTALT",       56),
+    ("KEY_SPACE",         57),
+    ("KEY_CAPSLOCK",      58),
+    ("KEY_F1",            59),
+    ("KEY_F2",            60),
+    ("KEY_F3",            61),
+    ("KEY_F4",            62),
+    ("KEY_F5",            63),
+    ("KEY_F6",            64),
+    ("KEY_F7",            65),
+    ("KEY_F8",            66),
+    ("KEY_F9",            67),
+    ("KEY_F10",           68),
+    ("KEY_NUMLOCK",       69),
+    ("KEY_SCROLLLOCK",    70),
+    ("KEY_KP7",           71),
+    ("KEY_KP8",           72),
+    ("KEY_KP9",           73),
+    ("KEY_KPMINUS",       74),
+    ("KEY_KP4",           75),
+    ("KEY_KP5",           76),
+    ("KEY_KP6",           77),
+    ("KEY_KPPLUS",        78),
+    ("KEY_KP1",           79),
+    ("KEY_KP2",           80),
+    ("KEY_KP3",           81),
+    ("KEY_KP0",           82),
+    ("KEY_KPDOT",         83),
+    ("KEY_102ND",         86),
+    ("KEY_F11",           87),
+    ("KEY_F12",           88),
+    ("KEY_KPENTER",       96),
+    ("KEY_RIGHTCTRL",     97),
+    ("KEY_KPSLASH",       98),
+    ("KEY_SYSRQ",         99),
+    ("KEY_RIGHTALT",      100),
+    ("KEY_HOME",          102),
+    ("KEY_UP",            103),
+    ("KEY_PAGEUP",        104),
+    ("KEY_LEFT",          105),
+    ("KEY_RIGHT",         106),
+    ("KEY_END",           107),
+    ("KEY_DOWN",          108),
+    ("KEY_PAGEDOWN",      109),
+    ("KEY_INSERT",        110),
+    ("KEY_DELETE",        111),
+    ("KEY_MUTE",          113),
+    ("KEY_VOLUMEDOWN",    114),
+    ("KEY_VOLUMEUP",      115),
+    ("KEY_POWER",         116),
+    ("KEY_KPEQUAL",       117),
+    ("KEY_PAUSE",         119),
+    ("KEY_KPCOMMA",       121),
+    ("KEY_LEFTMETA",      125),
+    ("KEY_RIGHTMETA",     126),
+    ("KEY_COMPOSE",       127),
+    ("KEY_F13",           183),
+    ("KEY_F14",           184),
+    ("KEY_F15",           185),
+    ("KEY_F16",           186),
+    ("KEY_F17",           187),
+    ("KEY_F18",           188),
+    ("KEY_F19",           189),
+    ("KEY_F20",           190),
+    ("KEY_F21",           191),
+    ("KEY_F22",           192),
+    ("KEY_F23",           193),
+    ("KEY_F24",           194),
+    ("KEY_PRINT",         210),
+
+    // buttons of mice and gamepads
+    ("BTN_LEFT",          0x110),
+    ("BTN_RIGHT",         0x111),
+    ("BTN_MIDDLE",        0x112),
+    ("BTN_TRIGGER",       0x120),
+    ("BTN_THUMB",         0x121),
+    ("BTN_THUMB2",        0x122),
+    ("BTN_TOP",           0x123),
+    ("BTN_TOP2",          0x124),
+    ("BTN_PINKIE",        0x125),
+    ("BTN_BASE",          0x126),
+    ("BTN_SOUTH",         0x130),
+    ("BTN_A",             0x130),
+    ("BTN_EAST",          0x131),
+    ("BTN_B",             0x131),
+    ("BTN_C",             0x132),
+    ("BTN_NORTH",         0x133),
+    ("BTN_X",             0x133),
+    ("BTN_WEST",          0x134),
+    ("BTN_Y",             0x134),
+    ("BTN_Z",             0x135),
+    ("BTN_TL",            0x136),
+    ("BTN_TR",            0x137),
+    ("BTN_TL2",           0x138),
+    ("BTN_TR2",           0x139),
+    ("BTN_SELECT",        0x13a),
+    ("BTN_START",         0x13b),
+    ("BTN_MODE",          0x13c),
+    ("BTN_THUMBL",        0x13d),
+    ("BTN_THUMBR",        0x13e),
+    ("BTN_DPAD_UP",       0x220),
+    ("BTN_DPAD_DOWN",     0x221),
+    ("BTN_DPAD_LEFT",     0x222),
+    ("BTN_DPAD_RIGHT",    0x223),
+
+    // absolute axes
+    ("ABS_X",             0),
+    ("ABS_Y",             1),
+    ("ABS_Z",             2),
+    ("ABS_RX",            3),
+    ("ABS_RY",            4),
+    ("ABS_RZ",            5),
+    ("ABS_THROTTLE",      6),
+    ("ABS_RUDDER",        7),
+    ("ABS_WHEEL",         8),
+    ("ABS_GAS",           9),
+    ("ABS_BRAKE",         10),
+    ("ABS_HAT0X",         16),
+    ("ABS_HAT0Y",         17),
+    ("ABS_HAT1X",         18),
+    ("ABS_HAT1Y",         19),
+];
+
+// get a code from its name, 'KEY_A' or 'BTN_SOUTH'
+pub fn code(name: &str) -> Option<u64> {
+    CODES.iter().find(| (code_name, _) | *code_name == name).map(| (_, code) | *code)
+}
@@ -0,0 +1,39 @@
+// keyboard layout that ships with the server, written as an event file
+pub struct Layout {
+    pub name:       &'static str,
+    pub modifiers:  &'static [&'static str],
+    pub content:    &'static str,
+}
+
+// modifiers every layout declares, in this order
+const MODIFIERS: &[&str] = &["shift", "control", "altgr", "capslock"];
+
+//
+pub const LAYOUTS: &[Layout] = &[
+    Layout {
+        name:       "us",
+        modifiers:  MODIFIERS,
+        content:    include_str!("layouts/us.event"),
+    },
+    Layout {
+        name:       "de",
+        modifiers:  MODIFIERS,
+        content:    include_str!("layouts/de.event"),
+    },
+    Layout {
+        name:       "uk",
+        modifiers:  MODIFIERS,
+        content:    include_str!("layouts/uk.event"),
+    },
+];
+
+// get a bundled layout from its name as used in the controller file
+pub fn find(name: &str) -> Option<&'static Layout> {
+    LAYOUTS.iter().find(| layout | layout.name == name)
+}
+
+// names of every layout, the way they are listed in error messages
+pub fn names() -> String {
+    let names: Vec<String> = LAYOUTS.iter().map(| layout | format!("'{}'", layout.name)).collect();
+    names.join(", ")
+}
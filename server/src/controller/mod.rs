@@ -1,34 +1,78 @@
-mod event;
+pub mod event;
+pub mod evdev;
+pub mod keys;
+pub mod layout;
+pub mod modifiers;
+
+use controller::event::{ Event, Rules, State, Typematic, Repeat };
+use controller::layout::Layout;
+use controller::modifiers::MODIFIERS;
+use client::protocol::{ KeyEvent, KeyState, Payload };
+use reactor::{ Reactor, Token, Interest };
+use verbosity;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::io::{ Read, ErrorKind };
+use std::fs::{ File, OpenOptions };
+use std::time::{ Duration, Instant };
+use libc;
+
+// how long to wait before opening a missing source again
+const RETRY: Duration               = Duration::from_secs(2);
+
+// translated event along with the name of the controller it came from and the client it's sent to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub source:     String,
+    pub target:     u8,
+    pub payload:    Payload,
+}
 
-use controller::event::{ Event, Action, Rules };
-use std::sync::mpsc::{ channel, Sender, Receiver };
-use std::time::Duration;
-use std::thread;
-use client;
+// key the server repeats while it's held
+struct Repeating {
+    target:         u8,
+    key_event:      KeyEvent,
+    deadline:       Instant,
+    rate:           Duration,
+}
 
 //
 pub struct Context {
-    sender:         Sender<u32>,
+    name:           String,
     events:         Vec<Event>,
     bytes_event:    Vec<usize>,
     source:         String,
     width:          usize,
+    evdev:          bool,
+    grab:           bool,
     verbose:        bool,
+    target:         u8,
+    modifiers:      Vec<String>,
+    state:          usize,
+    group:          Option<String>,
+    source_file:    Option<File>,
+    buffer:         Vec<u8>,
+    repeating:      Option<Repeating>,
+    token:          Token,
 }
 
 //
 impl Context {
 
     //
-    pub fn new(sender: Sender<u32>, translation_path: String, client_names: &Vec<String>) -> (Self, u8) {
+    pub fn new(name: String, index: usize, translation_path: String, client_names: &[String]) -> Self {
         use parser::{ Parser, checked_path };
 
         //
-        let mut rules = item!(Rules::new());
-        let mut events: Vec<Event> = Vec::new();
+        let rules = item!(Rules::new());
+        let mut events: Vec<(Rules, String, u64)> = Vec::new();
+        let mut layout: Option<&Layout> = None;
+        let mut modifiers: Vec<String> = Vec::new();
         let mut bytes_event: Vec<usize> = Vec::new();
         let mut source: Option<String> = None;
         let mut width: Option<usize> = None;
+        let mut device: Option<String> = None;
+        let mut grab = false;
         let mut verbose = false;
         let mut target = 0;
 
@@ -42,6 +86,48 @@ impl Context {
                 source = Some(stack.pop());
             }));
 
+            // input device, the layout of its events is known so neither bytes nor width are needed
+            parser.register("evdev", true, Box::new(| stack, _ | {
+                stack.push_debug("no device specified");
+                device = Some(stack.pop());
+                let mut rules = rules.borrow_mut();
+                rules.evdev = true;
+                rules.value_up.get_or_insert(evdev::VALUE_UP);
+                rules.value_down.get_or_insert(evdev::VALUE_DOWN);
+                rules.value_repeat.get_or_insert(evdev::VALUE_REPEAT);
+            }));
+
+            // bundled keyboard layout every event starts from
+            parser.register("layout", true, Box::new(| stack, _ | {
+                stack.push_debug("no layout specified");
+                let name = stack.pop();
+                layout = match layout::find(&name) {
+                    Some(layout)    => Some(layout),
+                    None            => stack.panic(format!("no layout called '{}'. valid options are {}", name, layout::names())),
+                };
+            }));
+
+            // modifiers are numbered in the order they are declared
+            parser.register("modifier", false, Box::new(| stack, _ | {
+                stack.push_debug("no modifier name specified");
+                let name = stack.pop();
+                if name.parse::<usize>().is_ok() || !name.chars().all(| character | character.is_alphanumeric() || character == '_') {
+                    stack.panic(format!("invalid modifier name '{}'", name));
+                }
+                if modifiers.contains(&name) {
+                    stack.panic(format!("modifier '{}' is declared twice", name));
+                }
+                if modifiers.len() == MODIFIERS {
+                    stack.panic(format!("there can only be {} modifiers", MODIFIERS));
+                }
+                modifiers.push(name);
+            }));
+
+            //
+            parser.register("grab", true, Box::new(| stack, _ | {
+                grab = stack.pop_state();
+            }));
+
             //
             parser.register("verbose", true, Box::new(| stack, _ | {
                 verbose = stack.pop_state();
@@ -69,13 +155,18 @@ impl Context {
                 };
 
                 //
-                let identifier = stack.pop_u64();
-                assert!(events.iter().find(| event | event.identifier == identifier).is_none(), "[ controller ] event identifier must be unique");
-                events.push(Event::new(rules.borrow().clone(), path, identifier, client_names));
+                stack.push_debug("no event identifier specified");
+                let identifier = stack.pop();
+                let identifier = match evdev::event_type(&identifier) {
+                    Some(identifier)    => identifier,
+                    None                => identifier.parse().unwrap_or_else(| _ | stack.panic(format!("invalid event identifier '{}'", identifier))),
+                };
+                assert!(events.iter().find(| (_, _, event) | *event == identifier).is_none(), "[ controller ] event identifier must be unique");
+                events.push((rules.borrow().clone(), path, identifier));
             }));
 
             //
-            parser.register("byte", false, Box::new(| stack, prefix | {
+            parser.register("byte", false, Box::new(| stack, _ | {
                 stack.push_debug("no byte rule specified");
                 match stack.pop().as_ref() {
                     "event"     => bytes_event = stack.pop_sequence(),
@@ -86,7 +177,7 @@ impl Context {
             }));
 
             //
-            parser.register("value", false, Box::new(| stack, prefix | {
+            parser.register("value", false, Box::new(| stack, _ | {
                 stack.push_debug("no value rule specified");
                 match stack.pop().as_ref() {
                     "up"        => rules.borrow_mut().value_up = Some(stack.pop_u64()),
@@ -101,28 +192,225 @@ impl Context {
             parser.parse();
         }
 
+        // input devices always use the size of struct input_event
+        let (source, width, evdev) = match (source, device) {
+            (Some(_), Some(_))      => panic!("[ controller ] either specify a source or an input device"),
+            (None, Some(device))    => (device, evdev::WIDTH, true),
+            (source, None)          => (source.expect("[ controller ] no source file specified"), width.expect("[ controller ] no event width specified"), false),
+        };
+        assert!(!grab || evdev, "[ controller ] only input devices can be grabbed");
+
+        // events are only read once the layout is known, input devices get their key events from the layout alone
+        rules.borrow_mut().modifiers = modifiers.clone();
+        let mut events: Vec<Event> = events.into_iter().map(| (mut rules, path, identifier) | {
+            rules.modifiers = modifiers.clone();
+            Event::with_layout(rules, Some(path), layout, identifier, client_names)
+        }).collect();
+        if let Some(layout) = layout.filter(| _ | evdev && !events.iter().any(| event | event.identifier == evdev::EV_KEY as u64)) {
+            events.push(Event::with_layout(rules.borrow().clone(), None, Some(layout), evdev::EV_KEY as u64, client_names));
+        }
+        let modifiers = events.first().map(| event | event.rules.modifiers.clone()).unwrap_or(modifiers);
+        events.iter_mut().for_each(| event | event.source = index);
+
         //
-        (Self {
-            sender:         sender,
+        Self {
+            name:           name,
             events:         events,
             bytes_event:    bytes_event,
-            source:         source.expect("[ controller ] no source file specified"),
-            width:          width.expect("[ controller ] no event width specified"),
-            verbose:        verbose,
-        },
-        target)
+            source:         source,
+            width:          width,
+            evdev:          evdev,
+            grab:           grab,
+            verbose:        verbose || verbosity::enabled(verbosity::TRAFFIC),
+            target:         target,
+            modifiers:      modifiers,
+            state:          index,
+            group:          None,
+            source_file:    None,
+            buffer:         Vec::new(),
+            repeating:      None,
+            token:          Token::Controller(0),
+        }
+    }
+
+    // open the source and wait for events
+    pub fn start(&mut self, reactor: &mut Reactor, token: Token) {
+        self.token = token;
+        self.open(reactor);
+    }
+
+    // try again later if the source doesn't exist yet
+    fn open(&mut self, reactor: &mut Reactor) {
+        match OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(&self.source) {
+            Ok(file)    => {
+                if self.grab && !evdev::grab(&file) {
+                    println!("[ controller ] unable to grab '{}', its events reach other readers as well", self.source);
+                }
+                reactor.register(file.as_raw_fd(), self.token, Interest::Read);
+                self.source_file = Some(file);
+            },
+            Err(_)      => reactor.timer(RETRY, self.token),
+        }
+    }
+
+    // keys held on the controller are let go, the state it shares with others is kept
+    pub fn close(&mut self, state: &mut State, reactor: &mut Reactor) {
+        if let Some(source_file) = self.source_file.take() {
+            reactor.deregister(source_file.as_raw_fd());
+        }
+        self.buffer.clear();
+        self.repeating = None;
+        state.release(self.state);
+    }
+
+    // read the available events and translate them
+    pub fn ready(&mut self, state: &mut State, reactor: &mut Reactor) -> Vec<Message> {
+        use parser::unwrap_sequence;
+
+        //
+        let source_file = match self.source_file.as_mut() {
+            Some(source_file)   => source_file,
+            None                => {
+                self.open(reactor);
+                return Vec::new();
+            },
+        };
+
+        //
+        let mut chunk = [0; 256];
+        loop {
+            match source_file.read(&mut chunk) {
+                Ok(0)                                               => {
+
+                    // every writer is gone, reopening resets the end of file for fifos
+                    self.close(state, reactor);
+                    self.open(reactor);
+                    break;
+                },
+                Ok(length)                                          => self.buffer.extend_from_slice(&chunk[..length]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(_)                                              => {
+                    self.close(state, reactor);
+                    reactor.timer(RETRY, self.token);
+                    break;
+                },
+            }
+        }
+
+        //
+        let mut translated = Vec::new();
+        while self.buffer.len() >= self.width {
+            let buffer: Vec<u8> = self.buffer.drain(..self.width).collect();
+            if self.verbose {
+                println!("\n[ controller ] event buffer: {:?}", &buffer);
+            }
+
+            // input devices are decoded right away, synchronization events carry nothing to translate
+            let (event_identifier, input) = match self.evdev {
+                true    => {
+                    let (kind, code, value) = evdev::decode(&buffer);
+                    if kind == evdev::EV_SYN {
+                        continue;
+                    }
+                    (kind as u64, Some((code as u64, value as i64)))
+                },
+                false   => (unwrap_sequence(&buffer, &self.bytes_event), None),
+            };
+            if self.verbose {
+                println!("[ controller ] event number: {}", event_identifier);
+            }
+
+            //
+            if let Some(event) = self.events.iter().find(| event | event.identifier == event_identifier) {
+                let input = match input {
+                    Some((code, value)) => Some((code, Some(value))),
+                    None                => event.decode(&buffer),
+                };
+                let payloads = match input {
+                    Some((code, value)) => event.translate_events(code, value, state, self.verbose),
+                    None                => Vec::new(),
+                };
+                for payload in payloads {
+                    let payload = match payload {
+                        Payload::Key(key_event) => typematic(event.typematic, key_event, state.target, &mut self.repeating).map(Payload::Key),
+                        payload                 => Some(payload),
+                    };
+                    if let Some(payload) = payload {
+                        if self.verbose {
+                            println!("[ controller ] [ {} ] event sent: {:?}", self.name, payload);
+                        }
+                        translated.push(self.message(state.target, payload));
+                    }
+                }
+            } else if self.verbose {
+                println!("[ controller ] unhandeled event: {}", event_identifier);
+            }
+        }
+
+        // a held key is repeated once per expired timer, so a busy server doesn't send a burst
+        let open = self.source_file.is_some();
+        if let Some(repeating) = self.repeating.as_mut().filter(| _ | open) {
+            let now = Instant::now();
+            if repeating.deadline <= now {
+                translated.push(Message {
+                    source:     self.name.clone(),
+                    target:     repeating.target,
+                    payload:    Payload::Key(repeating.key_event.clone()),
+                });
+                repeating.deadline = now + repeating.rate;
+            }
+            reactor.timer(repeating.deadline - now, self.token);
+        }
+        translated
     }
 
     //
-    pub fn start(self, target: u8) {
-        thread::spawn(move || { start_controller(self, target) });
+    fn message(&self, target: u8, payload: Payload) -> Message {
+        Message {
+            source:     self.name.clone(),
+            target:     target,
+            payload:    payload,
+        }
     }
 }
 
-//
+// apply the repeat and release settings of an event, keys repeated by the server are remembered until they
+// are released or another key is pressed
+fn typematic(typematic: Typematic, key_event: KeyEvent, target: u8, repeating: &mut Option<Repeating>) -> Option<KeyEvent> {
+    if key_event.state == KeyState::Down {
+        *repeating = None;
+    }
+
+    //
+    match (key_event.state, typematic.repeat) {
+        (KeyState::Repeat, Repeat::Device)      => Some(key_event),
+        (KeyState::Repeat, _)                   => None,
+        (KeyState::Down, Repeat::Host(delay, rate)) => {
+            let mut repeated = key_event.clone();
+            repeated.state = KeyState::Repeat;
+            *repeating = Some(Repeating {
+                target:     target,
+                key_event:  repeated,
+                deadline:   Instant::now() + delay,
+                rate:       rate,
+            });
+            Some(key_event)
+        },
+        (KeyState::Down, _)                     => Some(key_event),
+        (KeyState::Up, _)                       => {
+            if repeating.as_ref().is_some_and(| repeating | repeating.key_event.code == key_event.code) {
+                *repeating = None;
+            }
+            Some(key_event).filter(| _ | typematic.release)
+        },
+    }
+}
+
+// controllers in a group share their target, modifiers and layers
 pub struct Manager {
-    sender:         Sender<u32>,
-    receiver:       Receiver<u32>,
+    controllers:    Vec<Context>,
+    states:         Vec<State>,
 }
 
 //
@@ -130,85 +418,70 @@ impl Manager {
 
     //
     pub fn new() -> Self {
-        let (sender, receiver) = channel();
         Self {
-            sender:     sender,
-            receiver:   receiver,
+            controllers:    Vec::new(),
+            states:         Vec::new(),
         }
     }
 
-    //
-    pub fn initialize(&mut self, client_names: &Vec<String>, translation_path: String) {
-        let (context, target) = Context::new(self.sender.clone(), translation_path, client_names);
-        context.start(target);
+    // every controller starts out with a state of its own
+    pub fn initialize(&mut self, name: String, client_names: &[String], translation_path: String) {
+        assert!(self.controllers.iter().all(| controller | controller.name != name), "[ controller ] controller names must be unique");
+        let index = self.controllers.len();
+        let controller = Context::new(name, index, translation_path, client_names);
+        self.states.push(State::new(controller.target));
+        self.controllers.push(controller);
     }
 
-    //
-    pub fn start(self, client_manager: client::Manager) -> ! {
-        use client::Client;
-
-        // get the clients from the client manager
-        let (mut serial_clients, mut ethernet_clients) = client_manager.clients();
-
-        // setup vector for collected clients
-        let mut clients: Vec<&mut client::Client> = Default::default();
-        let length = serial_clients.len() + ethernet_clients.len();
-        clients.reserve(length);
-        unsafe { clients.set_len(length) };
-
-        // fill clients vector and start each client
-        serial_clients.iter_mut().for_each(| client | { let index = client.index(); clients[index] = client });
-        ethernet_clients.iter_mut().for_each(| client | { let index = client.index(); clients[index] = client });
-        clients.iter_mut().for_each(| client | client.start());
+    // the members take over the state of the first one, modifiers are shared by their offset so every member has
+    // to declare them in the same order
+    pub fn share(&mut self, group: &str, members: &[String]) {
+        let mut indices = Vec::new();
+        for member in members {
+            match self.controllers.iter().position(| controller | &controller.name == member) {
+                Some(index) => indices.push(index),
+                None        => panic!("[ controller ] no controller called '{}' in group '{}'", member, group),
+            }
+        }
+        let first = match indices.first() {
+            Some(first) => *first,
+            None        => panic!("[ controller ] group '{}' has no controllers", group),
+        };
+        let state = self.controllers[first].state;
 
-        // event loop
-        loop {
-            let data = self.receiver.recv().unwrap();
-            clients[(data >> 24) as usize].event(data as u16);
+        //
+        for index in indices {
+            let (shorter, longer) = match self.controllers[index].modifiers.len() < self.controllers[first].modifiers.len() {
+                true    => (&self.controllers[index].modifiers, &self.controllers[first].modifiers),
+                false   => (&self.controllers[first].modifiers, &self.controllers[index].modifiers),
+            };
+            assert!(longer.starts_with(shorter), "[ controller ] controllers in group '{}' have to declare their modifiers in the same order", group);
+            let controller = &mut self.controllers[index];
+            if let Some(existing) = &controller.group {
+                panic!("[ controller ] controller '{}' is already in group '{}'", controller.name, existing);
+            }
+            controller.group = Some(String::from(group));
+            controller.state = state;
         }
     }
-}
-
-//
-pub fn start_controller(context: Context, mut target: u8) -> ! {
-    use parser::unwrap_sequence;
-    use std::fs::File;
-    use std::io::Read;
 
-    //
-    let mut modifiers: u8 = 0;
-    let mut buffer: Vec<u8> = Vec::with_capacity(context.width);
-    unsafe { buffer.set_len(context.width) };
-
-    //
-    loop {
-        if let Ok(mut source_file) = File::open(&context.source) {
-            while let Ok(_) = source_file.read_exact(&mut buffer) {
-                if context.verbose {
-                    println!("\n[ controller ] event buffer: {:?}", &buffer);
-                }
+    // open the sources of every controller
+    pub fn start(&mut self, reactor: &mut Reactor) {
+        for (index, controller) in self.controllers.iter_mut().enumerate() {
+            controller.start(reactor, Token::Controller(index));
+        }
+    }
 
-                //
-                let event_identifier = unwrap_sequence(&buffer, &context.bytes_event);
-                if context.verbose {
-                    println!("[ controller ] event number: {}", event_identifier);
-                }
+    // translate the events of a controller
+    pub fn ready(&mut self, index: usize, reactor: &mut Reactor) -> Vec<Message> {
+        let controller = &mut self.controllers[index];
+        controller.ready(&mut self.states[controller.state], reactor)
+    }
 
-                //
-                if let Some(event) = context.events.iter().find(| event | event.identifier == event_identifier) {
-                    if let Some(data) = event.translate(&buffer, &mut target, &mut modifiers, context.verbose) {
-                        if context.verbose {
-                            println!("[ controller ] character sent: {}", data);
-                        }
-                        let combined = (target as u32) << 24 | (modifiers as u32) << 8 | (data as u32);
-                        context.sender.send(combined).unwrap();
-                    }
-                } else if context.verbose {
-                    println!("[ controller ] unhandeled event: {}", event_identifier);
-                }
-            }
-        } else {
-            thread::sleep(Duration::new(2, 0));
+    // close the sources of every controller
+    pub fn stop(&mut self, reactor: &mut Reactor) {
+        for controller in self.controllers.iter_mut() {
+            controller.close(&mut self.states[controller.state], reactor);
         }
     }
 }
@@ -0,0 +1,170 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+// most modifiers a controller can declare
+pub const MODIFIERS: usize          = 64;
+
+// state of every modifier of a controller, one bit each
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Modifiers {
+    bits:           u64,
+}
+
+//
+impl Modifiers {
+
+    //
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //
+    pub fn from_bits(bits: u64) -> Self {
+        Self {
+            bits:           bits,
+        }
+    }
+
+    //
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    //
+    pub fn contains(&self, offset: u8) -> bool {
+        self.bits & 1 << offset != 0
+    }
+
+    //
+    pub fn set(&mut self, offset: u8, state: bool) {
+        match state {
+            true    => self.bits |= 1 << offset,
+            false   => self.bits &= !(1 << offset),
+        }
+    }
+
+    //
+    pub fn toggle(&mut self, offset: u8) {
+        self.bits ^= 1 << offset;
+    }
+}
+
+// get the offset of a modifier from its name, plain offsets are taken as long as they are in range
+pub fn offset(name: &str, names: &[String]) -> Result<u8, String> {
+    if let Some(offset) = names.iter().position(| declared | declared == name) {
+        return Ok(offset as u8);
+    }
+    let limit = if names.is_empty() { MODIFIERS } else { names.len() };
+    match name.parse::<usize>() {
+        Ok(offset) if offset < limit    => Ok(offset as u8),
+        Ok(offset)                      => Err(format!("modifier offset {} is out of range, there are only {} modifiers", offset, limit)),
+        Err(_)                          => Err(format!("no modifier called '{}'", name)),
+    }
+}
+
+// condition on the modifiers that selects a mode
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Modifier(u8),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+
+    // the old eight bit identifiers, every other modifier has to be released
+    Exact(u64),
+}
+
+//
+impl Expression {
+
+    // 'shift', '!shift', 'shift & !control', '(shift | capslock) & !control' or the old '10000000'
+    pub fn parse(text: &str, names: &[String]) -> Result<Self, String> {
+        let text = text.trim();
+        if text.len() == 8 && text.chars().all(| character | character == '0' || character == '1') {
+            let mask = text.chars().enumerate().filter(| (_, character) | *character == '1').fold(0, | mask, (offset, _) | mask | 1 << offset);
+            return Ok(Expression::Exact(mask));
+        }
+
+        //
+        let mut characters = text.chars().peekable();
+        let expression = Self::or(&mut characters, names)?;
+        skip(&mut characters);
+        match characters.next() {
+            Some(character) => Err(format!("unexpected '{}' in mode expression", character)),
+            None            => Ok(expression),
+        }
+    }
+
+    //
+    fn or(characters: &mut Peekable<Chars>, names: &[String]) -> Result<Self, String> {
+        let mut expression = Self::and(characters, names)?;
+        while next_is(characters, '|') {
+            expression = Expression::Or(Box::new(expression), Box::new(Self::and(characters, names)?));
+        }
+        Ok(expression)
+    }
+
+    //
+    fn and(characters: &mut Peekable<Chars>, names: &[String]) -> Result<Self, String> {
+        let mut expression = Self::unary(characters, names)?;
+        while next_is(characters, '&') {
+            expression = Expression::And(Box::new(expression), Box::new(Self::unary(characters, names)?));
+        }
+        Ok(expression)
+    }
+
+    //
+    fn unary(characters: &mut Peekable<Chars>, names: &[String]) -> Result<Self, String> {
+        if next_is(characters, '!') {
+            return Ok(Expression::Not(Box::new(Self::unary(characters, names)?)));
+        }
+        if next_is(characters, '(') {
+            let expression = Self::or(characters, names)?;
+            return match next_is(characters, ')') {
+                true    => Ok(expression),
+                false   => Err(String::from("expected ')' in mode expression")),
+            };
+        }
+
+        //
+        let mut name = String::new();
+        while let Some(character) = characters.peek().copied().filter(| character | character.is_alphanumeric() || *character == '_') {
+            name.push(character);
+            characters.next();
+        }
+        match name.is_empty() {
+            true    => Err(String::from("expected a modifier in mode expression")),
+            false   => offset(&name, names).map(Expression::Modifier),
+        }
+    }
+
+    //
+    pub fn matches(&self, modifiers: Modifiers) -> bool {
+        match self {
+            Expression::Modifier(offset)    => modifiers.contains(*offset),
+            Expression::Not(expression)     => !expression.matches(modifiers),
+            Expression::And(left, right)    => left.matches(modifiers) && right.matches(modifiers),
+            Expression::Or(left, right)     => left.matches(modifiers) || right.matches(modifiers),
+            Expression::Exact(mask)         => modifiers.bits() == *mask,
+        }
+    }
+}
+
+//
+fn skip(characters: &mut Peekable<Chars>) {
+    while characters.peek().is_some_and(| character | character.is_whitespace()) {
+        characters.next();
+    }
+}
+
+// take the next character if it's the expected one
+fn next_is(characters: &mut Peekable<Chars>, expected: char) -> bool {
+    skip(characters);
+    match characters.peek() == Some(&expected) {
+        true    => {
+            characters.next();
+            true
+        },
+        false   => false,
+    }
+}
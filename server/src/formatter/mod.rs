@@ -0,0 +1,446 @@
+use parser::{ Word, Value, Format, Entries, CALLS, CHARACTERS, document_calls, join_lines, tokenize_line, parse_json, parse_toml };
+
+use std::fs;
+
+// item keywords that take their index from the counter of the stack
+const COUNTED: &[&str] = &["press", "key", "send", "toggle", "set", "target", "push", "hold", "lock", "oneshot", "block", "transparent", "axis", "deadzone", "hysteresis", "direction", "analog", "hat"];
+
+// directives resolved by the parser before any keyword is seen
+const DIRECTIVES: &[&str] = &["include", "include?", "set", "default", "if", "else", "end"];
+
+// variables are kept as they are written, these mark them inside of a word
+const VARIABLE_START: char = '\u{e000}';
+const VARIABLE_END: char = '\u{e001}';
+
+// a keyword call, keys of structured documents without a call identifier can't always be told apart
+#[derive(Clone)]
+struct Call {
+    identifier:     Option<char>,
+    keyword:        String,
+    arguments:      Vec<Word>,
+    comment:        Option<String>,
+}
+
+//
+#[derive(Clone)]
+enum Line {
+    Blank,
+    Comment(String),
+    Directive(Vec<Word>, Option<String>),
+    Call(Call),
+}
+
+// read a keyword file without resolving anything
+fn read_keywords(content: &str) -> Result<Vec<Line>, String> {
+    let joined = join_lines(content).map_err(| number | format!("[ line : {} ] line continues past the end of the file", number))?;
+    let mut lines = Vec::new();
+    for (number, line) in joined {
+        if line.trim().is_empty() {
+            if !matches!(lines.last(), Some(Line::Blank) | None) {
+                lines.push(Line::Blank);
+            }
+            continue;
+        }
+        if line.trim_start().starts_with('#') {
+            lines.push(Line::Comment(String::from(line.trim())));
+            continue;
+        }
+
+        //
+        let marker = | name: &str | Ok(format!("{}{}{}", VARIABLE_START, name, VARIABLE_END));
+        let (mut words, comment) = tokenize_line(&line, marker).map_err(| error | format!("[ line : {} ] {}", number, error))?;
+        let first = words.remove(0);
+        match first.text.chars().next().unwrap() {
+            identifier @ ('?' | ':' | '+' | '@')    => lines.push(Line::Call(Call {
+                identifier:     Some(identifier),
+                keyword:        String::from(&first.text[1..]),
+                arguments:      words,
+                comment:        comment,
+            })),
+            _ if DIRECTIVES.contains(&first.text.as_str()) => {
+                words.insert(0, first);
+                lines.push(Line::Directive(words, comment));
+            },
+            _                                       => return Err(format!("[ line : {} ] invalid keyword '{}'", number, first.text)),
+        }
+    }
+
+    //
+    if matches!(lines.last(), Some(Line::Blank)) {
+        lines.pop();
+    }
+    Ok(lines)
+}
+
+// read the keys of a structured document as calls, the keywords aren't known so some keys stay undecided
+fn read_document(entries: Entries) -> Result<Vec<Line>, String> {
+    let mut lines = Vec::new();
+    for (key, value, line) in entries {
+        let (identifier, keyword, calls) = document_calls(&key, value, | _ | None).map_err(| error | format!("[ line : {} ] {}", line, error))?;
+        for arguments in calls {
+            lines.push(Line::Call(Call {
+                identifier:     identifier,
+                keyword:        keyword.clone(),
+                arguments:      arguments,
+                comment:        None,
+            }));
+        }
+    }
+    Ok(lines)
+}
+
+// replace '*' with the index it stands for, as long as the counter is known
+fn expand_counters(lines: &mut [Line]) {
+    let mut counter = Some(0);
+    for line in lines.iter_mut() {
+        match line {
+            Line::Directive(words, _) if words[0].text != "set" && words[0].text != "default" => counter = None,
+            Line::Call(call)            => {
+                if !matches!(call.identifier, Some(':') | Some('+') | None) || !COUNTED.contains(&call.keyword.as_str()) {
+                    continue;
+                }
+                let index = match call.arguments.first_mut() {
+                    Some(index) if !index.quoted    => index,
+                    _                               => continue,
+                };
+                match index.text.as_str() {
+                    "*"     => if let Some(value) = counter.as_mut() {
+                        *value += 1;
+                        index.text = value.to_string();
+                    },
+                    text    => counter = text.parse::<usize>().ok(),
+                }
+            },
+            _                           => {},
+        }
+    }
+}
+
+// write the characters of press entries as quoted characters instead of the short forms, the same way
+// the parser reads them. names and numbers are kept, so they mean the same in every format
+fn normalize_characters(lines: &mut [Line]) {
+    for line in lines.iter_mut() {
+        if let Line::Call(call) = line {
+            if call.keyword != "press" {
+                continue;
+            }
+            if let Some(character) = call.arguments.get_mut(1).filter(| character | !character.quoted) {
+                let named = CHARACTERS.iter().any(| (name, _) | *name == character.text);
+                let text = match character.text.chars().next() {
+                    _ if named                                          => None,
+                    Some('b') if character.text.chars().count() >= 2    => character.text.chars().nth(1).map(String::from),
+                    Some('s')                                           => Some(String::from(" ")),
+                    _                                                   => None,
+                };
+                if let Some(text) = text {
+                    *character = Word {
+                        text:       text,
+                        quoted:     true,
+                        leading:    character.leading.clone(),
+                    };
+                }
+            }
+        }
+    }
+}
+
+// sort every run of press entries by their index, runs with an unknown index stay as they are
+fn sort_presses(lines: &mut [Line]) {
+    let index = | line: &Line | match line {
+        Line::Call(call) if call.keyword == "press" => Some(call.arguments.first().and_then(| index | index.text.parse::<usize>().ok())),
+        _                                           => None,
+    };
+
+    //
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start;
+        while end < lines.len() && index(&lines[end]).is_some() {
+            end += 1;
+        }
+        if end == start {
+            start += 1;
+            continue;
+        }
+        if lines[start..end].iter().all(| line | index(line) != Some(None)) {
+            lines[start..end].sort_by_key(| line | index(line));
+        }
+        start = end;
+    }
+}
+
+// a word as it has to be written in a keyword file
+fn render_word(word: &Word) -> String {
+    let special = | character: char | character.is_whitespace() || character.is_control() || character == '"' || character == '\'';
+    let needs_quotes = word.text.is_empty() || word.text.starts_with('#') || word.text.ends_with('\\') || word.text.chars().any(special);
+    let keep_quotes = word.quoted && word.text.chars().count() == 1;
+
+    //
+    let mut rendered = String::new();
+    let quoted = needs_quotes || keep_quotes;
+    if quoted {
+        rendered.push('"');
+    }
+    let mut characters = word.text.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            VARIABLE_START                          => rendered.push_str("${"),
+            VARIABLE_END                            => rendered.push('}'),
+            '$' if characters.peek() == Some(&'{')  => rendered.push_str(if quoted { "\\$" } else { "$$" }),
+            '"'                                     => rendered.push_str("\\\""),
+            '\\' if quoted                          => rendered.push_str("\\\\"),
+            '\n'                                    => rendered.push_str("\\n"),
+            '\r'                                    => rendered.push_str("\\r"),
+            '\t'                                    => rendered.push_str("\\t"),
+            character if (character as u32) < 0x80 && character.is_control() => rendered.push_str(&format!("\\x{:02x}", character as u32)),
+            character if character.is_control()    => rendered.push_str(&format!("\\u{{{:x}}}", character as u32)),
+            character                               => rendered.push(character),
+        }
+    }
+    if quoted {
+        rendered.push('"');
+    }
+    rendered
+}
+
+// consecutive calls of the same keyword are aligned in columns
+fn write_keywords(lines: &[Line]) -> Result<String, String> {
+    let mut output = String::new();
+    let mut index = 0;
+    while index < lines.len() {
+        match &lines[index] {
+            Line::Blank                     => output.push('\n'),
+            Line::Comment(comment)          => output.push_str(&format!("{}\n", comment)),
+            Line::Directive(words, comment) => {
+                let words: Vec<String> = words.iter().map(render_word).collect();
+                output.push_str(&words.join(" "));
+                if let Some(comment) = comment {
+                    output.push_str(&format!(" {}", comment));
+                }
+                output.push('\n');
+            },
+            Line::Call(call)                => {
+                let block: Vec<&Call> = lines[index..].iter().map_while(| line | match line {
+                    Line::Call(other) if other.keyword == call.keyword && other.identifier == call.identifier => Some(other),
+                    _                                                                                       => None,
+                }).collect();
+
+                //
+                let mut rows = Vec::new();
+                for call in &block {
+                    let identifier = call.identifier.ok_or_else(| | format!("can't tell if '{0}' is called once or for every item, write it as '?{0}' or ':{0}'", call.keyword))?;
+                    let mut row = vec![format!("{}{}", identifier, call.keyword)];
+                    row.extend(call.arguments.iter().map(render_word));
+                    rows.push(row);
+                }
+
+                // the last word is only padded if a comment follows
+                let columns = rows.iter().map(Vec::len).max().unwrap();
+                let mut widths = vec![0; columns];
+                for row in &rows {
+                    for (column, word) in row.iter().enumerate() {
+                        widths[column] = widths[column].max(word.chars().count());
+                    }
+                }
+                let commented = block.iter().any(| call | call.comment.is_some());
+                for (row, call) in rows.iter().zip(&block) {
+                    let mut line = String::new();
+                    for (column, width) in widths.iter().enumerate() {
+                        let word = row.get(column).map(String::as_str).unwrap_or("");
+                        if column + 1 == columns && !commented {
+                            line.push_str(word);
+                        } else {
+                            line.push_str(&format!("{:width$} ", word, width = width));
+                        }
+                    }
+                    if let Some(comment) = &call.comment {
+                        line.push_str(comment);
+                    }
+                    output.push_str(line.trim_end());
+                    output.push('\n');
+                }
+                index += block.len();
+                continue;
+            },
+        }
+        index += 1;
+    }
+    Ok(output)
+}
+
+// a word as a structured value, variables have no meaning outside of keyword files
+fn value(word: &Word) -> Result<Value, String> {
+    if word.text.contains(VARIABLE_START) {
+        return Err(String::from("variables can only be used in keyword files"));
+    }
+    let number = word.text.strip_prefix('-').unwrap_or(&word.text);
+    let integer = !number.is_empty() && number.chars().all(| character | character.is_ascii_digit()) && (number == "0" || !number.starts_with('0'));
+    match integer && !word.quoted {
+        true    => Ok(Value::Number(word.text.clone())),
+        false   => Ok(Value::Text(word.text.clone())),
+    }
+}
+
+// arguments of a single call, a single argument isn't wrapped
+fn call_value(call: &Call, bare: bool) -> Result<Value, String> {
+    let mut values = call.arguments.iter().map(value).collect::<Result<Vec<Value>, String>>()?;
+    match values.len() {
+        0 if bare   => Ok(Value::Null),
+        1           => Ok(values.remove(0)),
+        _           => Ok(Value::Array(values)),
+    }
+}
+
+// keys of a structured document, consecutive item calls are merged into one array
+fn document(lines: &[Line]) -> Result<Vec<(String, Value)>, String> {
+    let mut entries: Vec<(String, Value)> = Vec::new();
+    let mut merge = false;
+    for line in lines {
+        let call = match line {
+            Line::Call(call)        => call,
+            Line::Directive(..)     => return Err(String::from("directives can only be used in keyword files")),
+            _                       => continue,
+        };
+
+        //
+        match call.identifier {
+            Some(':') | Some('+')   => {
+                let key = format!(":{}", call.keyword);
+                let value = call_value(call, false)?;
+                match entries.last_mut() {
+                    Some((last, Value::Array(values))) if merge && *last == key => values.push(value),
+                    _                                                           => entries.push((key, Value::Array(vec![value]))),
+                }
+                merge = true;
+                continue;
+            },
+            Some(identifier)        => entries.push((format!("{}{}", identifier, call.keyword), call_value(call, true)?)),
+            None                    => entries.push((call.keyword.clone(), call_value(call, true)?)),
+        }
+        merge = false;
+    }
+    Ok(entries)
+}
+
+//
+fn json_string(text: &str) -> String {
+    let mut string = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"'                                 => string.push_str("\\\""),
+            '\\'                                => string.push_str("\\\\"),
+            '\n'                                => string.push_str("\\n"),
+            '\r'                                => string.push_str("\\r"),
+            '\t'                                => string.push_str("\\t"),
+            character if character.is_control() => string.push_str(&format!("\\u{:04x}", character as u32)),
+            character                           => string.push(character),
+        }
+    }
+    string.push('"');
+    string
+}
+
+// values are written on a single line, except for the arrays of item calls
+fn inline_value(value: &Value) -> String {
+    match value {
+        Value::Text(text)       => json_string(text),
+        Value::Number(number)   => number.clone(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Null             => String::from("null"),
+        Value::Array(values)    => format!("[{}]", values.iter().map(inline_value).collect::<Vec<String>>().join(", ")),
+        Value::Table(_)         => String::from("{}"),
+    }
+}
+
+// a keyword called again after another one can't get a key of its own
+fn repeated(entries: &[(String, Value)]) -> bool {
+    entries.iter().enumerate().any(| (index, (key, _)) | entries[..index].iter().any(| (other, _) | other == key))
+}
+
+//
+fn write_json(entries: &[(String, Value)]) -> String {
+    if repeated(entries) {
+        let calls: Vec<String> = entries.iter().map(| (key, value) | format!("        {{{}: {}}}", json_string(key), inline_value(value))).collect();
+        return format!("{{\n    {}: [\n{}\n    ]\n}}\n", json_string(CALLS), calls.join(",\n"));
+    }
+
+    //
+    let mut members = Vec::new();
+    for (key, value) in entries {
+        let value = match value {
+            Value::Array(values) if key.starts_with(':') => {
+                let values: Vec<String> = values.iter().map(| value | format!("        {}", inline_value(value))).collect();
+                format!("[\n{}\n    ]", values.join(",\n"))
+            },
+            value                                       => inline_value(value),
+        };
+        members.push(format!("    {}: {}", json_string(key), value));
+    }
+    match members.is_empty() {
+        true    => String::from("{}\n"),
+        false   => format!("{{\n{}\n}}\n", members.join(",\n")),
+    }
+}
+
+//
+fn toml_key(key: &str) -> String {
+    match key.chars().all(| character | character.is_ascii_alphanumeric() || character == '_' || character == '-') {
+        true    => String::from(key),
+        false   => json_string(key),
+    }
+}
+
+// toml has no null, calls without arguments get an empty array
+fn write_toml(entries: &[(String, Value)]) -> String {
+    if repeated(entries) {
+        let calls: Vec<String> = entries.iter().map(| (key, value) | {
+            let value = match value {
+                Value::Null => String::from("[]"),
+                value       => inline_value(value),
+            };
+            format!("    {{ {} = {} }},\n", toml_key(key), value)
+        }).collect();
+        return format!("{} = [\n{}]\n", CALLS, calls.concat());
+    }
+
+    //
+    let mut output = String::new();
+    for (key, value) in entries {
+        let items = key.starts_with(':');
+        let key = toml_key(key);
+        let value = match value {
+            Value::Null                         => String::from("[]"),
+            Value::Array(values) if items       => {
+                let values: Vec<String> = values.iter().map(| value | format!("    {},\n", inline_value(value))).collect();
+                format!("[\n{}]", values.concat())
+            },
+            value                               => inline_value(value),
+        };
+        output.push_str(&format!("{} = {}\n", key, value));
+    }
+    output
+}
+
+// parse a keyword, json or toml file and write it in a normalized layout, in the same or another format
+pub fn format(path: &str, target: Option<Format>) -> Result<String, String> {
+    let content = fs::read_to_string(path).map_err(| _ | format!("unable to open '{}'", path))?;
+    let source = Format::detect(path, &content);
+    let structured = | entries: Result<Entries, (usize, String)> | entries.map_err(| (line, message) | format!("[ line : {} ] {}", line, message));
+    let mut lines = match source {
+        Format::Keywords    => read_keywords(&content)?,
+        Format::Json        => read_document(structured(parse_json(&content))?)?,
+        Format::Toml        => read_document(structured(parse_toml(&content))?)?,
+    };
+
+    //
+    expand_counters(&mut lines);
+    normalize_characters(&mut lines);
+    sort_presses(&mut lines);
+
+    //
+    match target.unwrap_or(source) {
+        Format::Keywords    => write_keywords(&lines),
+        Format::Json        => document(&lines).map(| entries | write_json(&entries)),
+        Format::Toml        => document(&lines).map(| entries | write_toml(&entries)),
+    }
+}
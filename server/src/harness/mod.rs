@@ -0,0 +1,233 @@
+use client::{ self, Manager, Console };
+use reactor::{ Reactor, Token };
+
+use regex::Regex;
+use std::time::{ Duration, Instant };
+use std::io::Write;
+use std::fs::{ self, File };
+use std::thread;
+use std::process::exit;
+
+//
+pub enum Step {
+
+    // write a line to the board
+    Send(String),
+
+    // name, timeout and pattern that has to show up on the console
+    Expect(String, Duration, Regex),
+}
+
+// report formats
+pub enum Format {
+    Tap,
+    Junit,
+}
+
+//
+pub struct Outcome {
+    pub name:       String,
+    pub passed:     Option<bool>,
+    pub time:       Duration,
+}
+
+//
+pub struct Spec {
+    pub path:       String,
+    steps:          Vec<Step>,
+    reports:        Vec<(Format, String)>,
+}
+
+//
+impl Spec {
+
+    //
+    pub fn new(path: String) -> Self {
+        use parser::{ Parser, Item };
+
+        //
+        let steps: Item<Vec<Step>> = item!(Vec::new());
+        let mut reports: Vec<(Format, String)> = Vec::new();
+
+        {
+            //
+            let mut parser = Parser::new(&path);
+
+            //
+            parser.register("send", false, Box::new(| stack, _ | {
+                steps.borrow_mut().push(Step::Send(stack.pop_line()));
+            }));
+
+            //
+            parser.register("expect", false, Box::new(| stack, _ | {
+                stack.push_debug("no step name specified");
+                let name = stack.pop();
+                let timeout = Duration::from_millis(stack.pop_u64());
+                let pattern = stack.pop_line();
+                match Regex::new(&pattern) {
+                    Ok(pattern) => steps.borrow_mut().push(Step::Expect(name, timeout, pattern)),
+                    Err(_)      => stack.panic(format!("invalid pattern '{}'", pattern)),
+                }
+            }));
+
+            //
+            parser.register("report", false, Box::new(| stack, _ | {
+                stack.push_debug("no report format specified. valid options are 'tap' and 'junit'");
+                let format = match stack.pop().as_ref() {
+                    "tap"       => Format::Tap,
+                    "junit"     => Format::Junit,
+                    format      => stack.panic(format!("invalid report format '{}'. valid options are 'tap' and 'junit'", format)),
+                };
+                stack.push_debug("no report path specified");
+                reports.push((format, stack.pop()));
+            }));
+
+            //
+            parser.parse();
+        }
+
+        //
+        Self {
+            path:       path,
+            steps:      unwrap_item!(steps),
+            reports:    reports,
+        }
+    }
+
+    // run every step in order, once a step failed the remaining ones are skipped
+    pub fn run(&self, console: &Console, writer: &mut File) -> Vec<Outcome> {
+        let mut outcomes = Vec::new();
+        let mut failed = false;
+
+        //
+        for step in &self.steps {
+            match step {
+
+                //
+                Step::Send(line)                        => {
+                    if !failed && writer.write_all(line.as_bytes()).and_then(| _ | writer.write_all(b"\r")).is_err() {
+                        println!("[ harness ] unable to send '{}'", line);
+                        failed = true;
+                    }
+                },
+
+                //
+                Step::Expect(name, timeout, pattern)    => {
+                    let time = Instant::now();
+                    let passed = match failed {
+                        true    => None,
+                        false   => Some(console.expect(pattern, *timeout)),
+                    };
+                    failed |= passed == Some(false);
+                    outcomes.push(Outcome {
+                        name:       name.clone(),
+                        passed:     passed,
+                        time:       time.elapsed(),
+                    });
+                },
+            }
+        }
+
+        //
+        outcomes
+    }
+}
+
+// format the outcomes in the test anything protocol
+fn tap(outcomes: &[Outcome]) -> String {
+    let mut report = format!("TAP version 13\n1..{}\n", outcomes.len());
+    for (index, outcome) in outcomes.iter().enumerate() {
+        report += &match outcome.passed {
+            Some(true)  => format!("ok {} - {}\n", index + 1, outcome.name),
+            Some(false) => format!("not ok {} - {} # timed out\n", index + 1, outcome.name),
+            None        => format!("ok {} - {} # SKIP previous step failed\n", index + 1, outcome.name),
+        };
+    }
+    report
+}
+
+//
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// format the outcomes as a junit xml document
+fn junit(outcomes: &[Outcome], client: &str, spec: &str) -> String {
+    let failures = outcomes.iter().filter(| outcome | outcome.passed == Some(false)).count();
+    let skipped = outcomes.iter().filter(| outcome | outcome.passed.is_none()).count();
+    let time: f64 = outcomes.iter().map(| outcome | outcome.time.as_secs_f64()).sum();
+
+    //
+    let mut report = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    report += &format!("<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n", escape(spec), outcomes.len(), failures, skipped, time);
+    for outcome in outcomes {
+        report += &format!("  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"", escape(&outcome.name), escape(client), outcome.time.as_secs_f64());
+        report += match outcome.passed {
+            Some(true)  => "/>\n",
+            Some(false) => ">\n    <failure message=\"timed out\"/>\n  </testcase>\n",
+            None        => ">\n    <skipped/>\n  </testcase>\n",
+        };
+    }
+    report + "</testsuite>\n"
+}
+
+// serve the kernel to a single client and check its console output against a spec
+pub fn run(client_manager: Manager, selector: String, spec_path: String) -> ! {
+    let spec = Spec::new(spec_path);
+    if !spec.steps.iter().any(| step | matches!(step, Step::Expect(..))) {
+        println!("[ harness ] '{}' has no expect steps", spec.path);
+        exit(1);
+    }
+
+    // the spec describes the console of a single board, so the selector has to name exactly one client
+    let matching = client_manager.matching(&selector);
+    let (context, mode) = match matching.as_slice() {
+        [(context, mode)]   => (context, mode),
+        _                   => {
+            println!("[ harness ] '{}' matches {} client/s, test mode requires exactly one", selector, matching.len());
+            exit(2);
+        },
+    };
+    let mut client = match client::open(context, mode, None) {
+        Some(client)    => client,
+        None            => exit(1),
+    };
+    let (console, mut writer) = match client.harness() {
+        Some(harness)   => harness,
+        None            => {
+            println!("[ harness ] [ {} ] test mode requires a serial client", context.name);
+            exit(2);
+        },
+    };
+
+    //
+    let name = context.name.clone();
+    let mut reactor = Reactor::new();
+    client.start(&mut reactor, Token::Client(0));
+    thread::spawn(move || loop {
+        for _ in reactor.poll() {
+            client.ready(&mut reactor);
+        }
+    });
+    println!("[ harness ] [ {} ] running '{}'", name, spec.path);
+
+    //
+    let outcomes = spec.run(&console, &mut writer);
+    let passed = outcomes.iter().all(| outcome | outcome.passed == Some(true));
+
+    //
+    print!("{}", tap(&outcomes));
+    for (format, path) in &spec.reports {
+        let report = match format {
+            Format::Tap     => tap(&outcomes),
+            Format::Junit   => junit(&outcomes, &name, &spec.path),
+        };
+        if fs::write(path, report).is_err() {
+            println!("[ harness ] unable to write report '{}'", path);
+        }
+    }
+
+    //
+    println!("[ harness ] [ {} ] {}", name, if passed { "passed" } else { "failed" });
+    exit(if passed { 0 } else { 1 })
+}
@@ -0,0 +1,22 @@
+// the field names are spelled out and managers are always created explicitly
+#![allow(clippy::redundant_field_names, clippy::new_without_default)]
+
+extern crate libc;
+extern crate regex;
+extern crate sha2;
+
+#[macro_use]
+pub mod verbosity;
+#[macro_use]
+pub mod parser;
+pub mod client;
+pub mod store;
+pub mod reactor;
+pub mod harness;
+pub mod configuration;
+pub mod server;
+pub mod control;
+pub mod arguments;
+pub mod formatter;
+#[cfg(feature = "controller")]
+pub mod controller;
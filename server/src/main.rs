@@ -1,84 +1,102 @@
-#[macro_use]
-mod parser;
-mod client;
-#[cfg(feature = "controller")]
-mod controller;
+extern crate bmq5;
 
-// main
-fn main() -> ! {
-
-    // get command line arguments
-    let mut parameters: Vec<String> = std::env::args().rev().collect();
-    parameters.pop();
-
-    // managers
-    let client_manager = item!(client::Manager::new());
-    #[cfg(feature = "controller")]
-    let mut controller_manager = controller::Manager::new();
-
-    // keyboard driver
-    #[cfg(feature = "driver")]
-    let mut driver = false;
-
-    // devices lookup path
-    let mut lookup_path = item!(String::new());
+use bmq5::arguments::{ Arguments, Command, Usage, USAGE };
+use bmq5::configuration::{ Configuration, Overrides };
+use bmq5::parser::Format;
+use bmq5::{ server, verbosity, formatter };
+use std::path::Path;
+use std::panic::{ self, AssertUnwindSafe };
+use std::process::exit;
+use std::fs;
 
-    // file system
-    #[cfg(feature = "system")]
-    let mut system_path: Option<String> = None;
+// parse the configuration, errors are reported on a single line instead of with a backtrace
+fn load(configuration_path: &str, overrides: &Overrides) -> Configuration {
+    panic::set_hook(Box::new(| _ | ()));
+    let configuration = panic::catch_unwind(AssertUnwindSafe(| | Configuration::new(configuration_path, overrides)));
+    let _ = panic::take_hook();
 
     //
-    {
-        //
-        let client_names: parser::Item<Vec<String>> = item!(Default::default());
-        let mut parser = parser::Parser::new(&parameters.pop().expect("[ server ] no configuration file specified"));
+    match configuration {
+        Ok(configuration)   => configuration,
+        Err(payload)        => {
+            let message = match (payload.downcast_ref::<String>(), payload.downcast_ref::<&str>()) {
+                (Some(message), _)  => message.as_str(),
+                (_, Some(message))  => message,
+                _                   => "invalid configuration",
+            };
+            match message.starts_with('[') {
+                true    => eprintln!("{}", message),
+                false   => eprintln!("[ server ] {}", message),
+            }
+            exit(1);
+        },
+    }
+}
 
-        //
-        parser.register("binary", false, Box::new(| stack, prefix | {
-            stack.push_debug("no binary file specified");
-            client_manager.borrow_mut().binary(format!("{}{}", prefix.unwrap(), stack.pop()), stack.pop_name());
-        }));
+// main
+fn main() {
 
-        //
-        parser.register("lookup", true, Box::new(| stack, _ | {
-            *lookup_path.borrow_mut() = stack.pop_directory();
-        }));
+    // get command line arguments
+    let parameters: Vec<String> = std::env::args().skip(1).collect();
+    let arguments = match Arguments::parse(&parameters) {
+        Ok(arguments)               => arguments,
+        Err(Usage::Help)            => {
+            print!("{}", USAGE);
+            exit(0);
+        },
+        Err(Usage::Version)         => {
+            println!("bmq5-server {}", env!("CARGO_PKG_VERSION"));
+            exit(0);
+        },
+        Err(Usage::Error(message))  => {
+            eprintln!("[ server ] {}", message);
+            eprintln!("[ server ] run 'bmq5-server --help' for usage");
+            exit(2);
+        },
+    };
 
-        //
-        parser.register("client", false, Box::new(| stack, prefix | {
-            stack.push_debug("no client name specified");
-            let client_name = stack.pop();
-            assert!(client_names.borrow().iter().find(| name | name == &&client_name).is_none(), "[ server ] client names must be unique");
-            client_manager.borrow_mut().initialize(&*lookup_path.borrow(), format!("{}{}", prefix.unwrap(), client_name), client_names.borrow().len());
-            client_names.borrow_mut().push(client_name);
-        }));
+    //
+    verbosity::set(arguments.verbosity);
+    if !Path::new(&arguments.configuration_path).is_file() {
+        eprintln!("[ server ] configuration file '{}' not found", arguments.configuration_path);
+        exit(2);
+    }
 
-        //
-        #[cfg(feature = "controller")]
-        parser.register("controller", false, Box::new(| stack, prefix | {
-            stack.push_debug("no controller translation specified");
-            controller_manager.initialize(&client_names.borrow(), format!("{}{}", prefix.unwrap(), stack.pop()));
-        }));
+    //
+    let configuration_path = arguments.configuration_path;
+    match arguments.command {
+        Command::Run                    => server::run(load(&configuration_path, &arguments.overrides), configuration_path, arguments.overrides),
+        Command::Test(client, spec)     => load(&configuration_path, &arguments.overrides).test(client, spec),
+        Command::Check                  => load(&configuration_path, &arguments.overrides).check(),
+        Command::ListClients            => load(&configuration_path, &arguments.overrides).list_clients(),
+        Command::Send(client, command)  => {
+            if !load(&configuration_path, &arguments.overrides).send(&client, &command) {
+                exit(1);
+            }
+        },
 
         //
-        #[cfg(feature = "driver")]
-        parser.register("driver", true, Box::new(| stack, _ | {
-            driver = stack.pop_state();
-        }));
+        Command::Format(target, write)  => {
 
-        //
-        #[cfg(feature = "system")]
-        parser.register("system", true, Box::new(| stack, _ | {
-            system_path = Some(stack.pop_directory());
-        }));
+            // a file written in another format would be read in the old one again
+            let source = fs::read_to_string(&configuration_path).ok().map(| content | Format::detect(&configuration_path, &content));
+            if let (true, Some(target)) = (write, target.filter(| target | Some(*target) != source)) {
+                eprintln!("[ formatter ] can't write '{}' as {}, convert it without --write and save it with a matching extension", configuration_path, target.name());
+                exit(2);
+            }
 
-        //
-        parser.parse();
+            //
+            let formatted = match formatter::format(&configuration_path, target) {
+                Ok(formatted)   => formatted,
+                Err(error)      => {
+                    eprintln!("[ formatter ] {}", error);
+                    exit(1);
+                },
+            };
+            match write {
+                true    => fs::write(&configuration_path, formatted).expect("[ formatter ] unable to write the formatted file"),
+                false   => print!("{}", formatted),
+            }
+        },
     }
-
-    //
-    #[cfg(not(feature = "controller"))]
-    unwrap_item!(client_manager).start();
-    #[cfg(feature = "controller")]
-    controller_manager.start(unwrap_item!(client_manager));
 }
@@ -0,0 +1,452 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+// value of a structured configuration
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    Number(String),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),
+    Null,
+}
+
+// formats a configuration file can be written in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Keywords,
+    Json,
+    Toml,
+}
+
+//
+impl Format {
+
+    // get a format from its name as used on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keywords"  => Some(Format::Keywords),
+            "json"      => Some(Format::Json),
+            "toml"      => Some(Format::Toml),
+            _           => None,
+        }
+    }
+
+    // get the name of the format as used on the command line
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Format::Keywords    => "keywords",
+            Format::Json        => "json",
+            Format::Toml        => "toml",
+        }
+    }
+
+    // the extention decides if there is one, otherwise the first thing in the file does
+    pub fn detect(path: &str, content: &str) -> Self {
+        if path.ends_with(".json") {
+            return Format::Json;
+        }
+        if path.ends_with(".toml") {
+            return Format::Toml;
+        }
+
+        //
+        let first = content.lines().map(str::trim).find(| line | !line.is_empty() && !line.starts_with('#'));
+        match first {
+            Some(line) if line.starts_with('{')                             => Format::Json,
+            Some(line) if line.starts_with('[') || is_assignment(line)      => Format::Toml,
+            _                                                               => Format::Keywords,
+        }
+    }
+}
+
+// 'key = value' with a bare or quoted key
+fn is_assignment(line: &str) -> bool {
+    let key = match line.find('=') {
+        Some(index) => line[..index].trim(),
+        None        => return false,
+    };
+    let quoted = key.len() >= 2 && (key.starts_with('"') && key.ends_with('"') || key.starts_with('\'') && key.ends_with('\''));
+    quoted || !key.is_empty() && key.chars().all(is_bare)
+}
+
+//
+fn is_bare(character: char) -> bool {
+    character.is_ascii_alphanumeric() || character == '_' || character == '-'
+}
+
+// every top level key of a document, in the order they appear, along with their line
+pub type Entries = Vec<(String, Value, usize)>;
+
+// keys can only be used once, so documents that call a keyword again after another one list their calls in order
+// as tables with a single key in this array
+pub const CALLS: &str = "calls";
+
+// replace the array of calls with the keys of its tables, all of them are on the line of the array
+fn expand_calls(entries: Entries) -> Result<Entries, (usize, String)> {
+    let mut expanded = Vec::new();
+    for (key, value, line) in entries {
+        if key != CALLS {
+            expanded.push((key, value, line));
+            continue;
+        }
+        let calls = match value {
+            Value::Array(calls) => calls,
+            _                   => return Err((line, format!("'{}' has to be an array of tables with a single key", CALLS))),
+        };
+        for call in calls {
+            match call {
+                Value::Table(mut members) if members.len() == 1 => {
+                    let (key, value) = members.remove(0);
+                    expanded.push((key, value, line));
+                },
+                _                                               => return Err((line, format!("'{}' has to be an array of tables with a single key", CALLS))),
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+//
+struct Cursor<'a> {
+    characters:     Peekable<Chars<'a>>,
+    line:           usize,
+}
+
+//
+impl<'a> Cursor<'a> {
+
+    //
+    fn new(content: &'a str) -> Self {
+        Self {
+            characters:     content.chars().peekable(),
+            line:           1,
+        }
+    }
+
+    //
+    fn peek(&mut self) -> Option<char> {
+        self.characters.peek().copied()
+    }
+
+    //
+    fn next(&mut self) -> Option<char> {
+        let character = self.characters.next();
+        if character == Some('\n') {
+            self.line += 1;
+        }
+        character
+    }
+
+    // skip whitespace, new lines only if asked to and comments only in toml
+    fn skip(&mut self, newlines: bool, comments: bool) {
+        while let Some(character) = self.peek() {
+            match character {
+                '\n' if !newlines                               => return,
+                '#' if comments                                 => {
+                    while self.peek().is_some_and(| character | character != '\n') {
+                        self.next();
+                    }
+                },
+                character if character.is_whitespace()          => { self.next(); },
+                _                                               => return,
+            }
+        }
+    }
+
+    //
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(character) if character == expected    => Ok(()),
+            Some(character)                             => Err(format!("expected '{}' but found '{}'", expected, character)),
+            None                                        => Err(format!("expected '{}' but reached the end of the file", expected)),
+        }
+    }
+
+    //
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(character) = self.peek() {
+            if !(is_bare(character) || character == '.' || character == '+') {
+                break;
+            }
+            word.push(character);
+            self.next();
+        }
+        word
+    }
+
+    // double quoted string with json and toml escapes
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"')       => return Ok(string),
+                Some('\n')      => return Err(String::from("unterminated string")),
+                Some('\\')      => {
+                    let escaped = match self.next() {
+                        Some('n')   => '\n',
+                        Some('r')   => '\r',
+                        Some('t')   => '\t',
+                        Some('b')   => '\x08',
+                        Some('f')   => '\x0c',
+                        Some('e')   => '\x1b',
+                        Some('"')   => '"',
+                        Some('\\')  => '\\',
+                        Some('/')   => '/',
+                        Some('u')   => self.unicode(4)?,
+                        Some('U')   => self.unicode(8)?,
+                        _           => return Err(String::from("invalid escape sequence")),
+                    };
+                    string.push(escaped);
+                },
+                Some(character) => string.push(character),
+                None            => return Err(String::from("unterminated string")),
+            }
+        }
+    }
+
+    //
+    fn unicode(&mut self, length: usize) -> Result<char, String> {
+        let digits: String = (0..length).filter_map(| _ | self.next()).collect();
+        u32::from_str_radix(&digits, 16).ok().and_then(std::char::from_u32).ok_or_else(| | format!("invalid unicode escape '{}'", digits))
+    }
+
+    // single quoted toml string, taken literally
+    fn literal(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('\'')                      => return Ok(string),
+                Some('\n') | None               => return Err(String::from("unterminated string")),
+                Some(character)                 => string.push(character),
+            }
+        }
+    }
+
+    // numbers, booleans and null
+    fn scalar(&mut self, null: bool) -> Result<Value, String> {
+        let word = self.word();
+        match word.as_str() {
+            "true"                                      => Ok(Value::Boolean(true)),
+            "false"                                     => Ok(Value::Boolean(false)),
+            "null" if null                              => Ok(Value::Null),
+            "" if self.peek().is_none()                 => Err(String::from("unexpected end of the file")),
+            ""                                          => Err(format!("unexpected '{}'", self.peek().unwrap())),
+            number if number.replace('_', "").parse::<f64>().is_ok() => Ok(Value::Number(number.replace('_', ""))),
+            word                                        => Err(format!("invalid value '{}'", word)),
+        }
+    }
+
+    //
+    fn json(&mut self) -> Result<Value, String> {
+        self.skip(true, false);
+        match self.peek() {
+            Some('"')   => Ok(Value::Text(self.string()?)),
+            Some('[')   => {
+                self.next();
+                let mut values = Vec::new();
+                self.skip(true, false);
+                if self.peek() == Some(']') {
+                    self.next();
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.json()?);
+                    self.skip(true, false);
+                    match self.next() {
+                        Some(',')   => continue,
+                        Some(']')   => return Ok(Value::Array(values)),
+                        _           => return Err(String::from("expected ',' or ']'")),
+                    }
+                }
+            },
+            Some('{')   => {
+                self.next();
+                let mut members = Vec::new();
+                self.skip(true, false);
+                if self.peek() == Some('}') {
+                    self.next();
+                    return Ok(Value::Table(members));
+                }
+                loop {
+                    self.skip(true, false);
+                    let key = self.string()?;
+                    self.skip(true, false);
+                    self.expect(':')?;
+                    members.push((key, self.json()?));
+                    self.skip(true, false);
+                    match self.next() {
+                        Some(',')   => continue,
+                        Some('}')   => return Ok(Value::Table(members)),
+                        _           => return Err(String::from("expected ',' or '}'")),
+                    }
+                }
+            },
+            _           => self.scalar(true),
+        }
+    }
+
+    //
+    fn toml_key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"')   => self.string(),
+            Some('\'')  => self.literal(),
+            _           => {
+                let mut key = String::new();
+                while let Some(character) = self.peek().filter(| character | is_bare(*character)) {
+                    key.push(character);
+                    self.next();
+                }
+                match key.is_empty() {
+                    true    => Err(String::from("no key specified")),
+                    false   => Ok(key),
+                }
+            },
+        }
+    }
+
+    //
+    fn toml(&mut self) -> Result<Value, String> {
+        let ahead: String = self.characters.clone().take(3).collect();
+        if ahead == "\"\"\"" || ahead == "'''" {
+            return Err(String::from("multi-line strings are not supported in this subset of toml"));
+        }
+        match self.peek() {
+            Some('"')   => Ok(Value::Text(self.string()?)),
+            Some('\'')  => Ok(Value::Text(self.literal()?)),
+
+            // arrays may span several lines
+            Some('[')   => {
+                self.next();
+                let mut values = Vec::new();
+                loop {
+                    self.skip(true, true);
+                    if self.peek() == Some(']') {
+                        self.next();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.toml()?);
+                    self.skip(true, true);
+                    match self.next() {
+                        Some(',')   => continue,
+                        Some(']')   => return Ok(Value::Array(values)),
+                        _           => return Err(String::from("expected ',' or ']'")),
+                    }
+                }
+            },
+
+            // inline tables stay on one line
+            Some('{')   => {
+                self.next();
+                let mut members = Vec::new();
+                self.skip(false, false);
+                if self.peek() == Some('}') {
+                    self.next();
+                    return Ok(Value::Table(members));
+                }
+                loop {
+                    self.skip(false, false);
+                    let key = self.toml_key()?;
+                    self.skip(false, false);
+                    self.expect('=')?;
+                    self.skip(false, false);
+                    members.push((key, self.toml()?));
+                    self.skip(false, false);
+                    match self.next() {
+                        Some(',')   => continue,
+                        Some('}')   => return Ok(Value::Table(members)),
+                        _           => return Err(String::from("expected ',' or '}'")),
+                    }
+                }
+            },
+            _           => self.scalar(false),
+        }
+    }
+}
+
+// parse a json document, which has to be a single object
+pub fn parse_json(content: &str) -> Result<Entries, (usize, String)> {
+    let mut cursor = Cursor::new(content);
+    let error = | cursor: &Cursor, message: String | (cursor.line, message);
+
+    //
+    cursor.skip(true, false);
+    if let Err(message) = cursor.expect('{') {
+        return Err(error(&cursor, message));
+    }
+    let mut entries = Vec::new();
+    cursor.skip(true, false);
+    if cursor.peek() == Some('}') {
+        return Ok(entries);
+    }
+
+    // keys are taken one at a time to know their line
+    loop {
+        cursor.skip(true, false);
+        let line = cursor.line;
+        let key = cursor.string().map_err(| message | error(&cursor, message))?;
+        cursor.skip(true, false);
+        cursor.expect(':').map_err(| message | error(&cursor, message))?;
+        let value = cursor.json().map_err(| message | error(&cursor, message))?;
+        entries.push((key, value, line));
+
+        //
+        cursor.skip(true, false);
+        match cursor.next() {
+            Some(',')   => continue,
+            Some('}')   => break,
+            _           => return Err(error(&cursor, String::from("expected ',' or '}'"))),
+        }
+    }
+
+    //
+    cursor.skip(true, false);
+    match cursor.peek() {
+        Some(character) => Err(error(&cursor, format!("unexpected '{}' after the document", character))),
+        None            => expand_calls(entries),
+    }
+}
+
+// parse the subset of toml that maps onto keywords: top level keys that are set once, with single line strings,
+// numbers, booleans, arrays and inline tables as values. tables and dotted keys have no keyword to map onto
+pub fn parse_toml(content: &str) -> Result<Entries, (usize, String)> {
+    let mut cursor = Cursor::new(content);
+    let error = | cursor: &Cursor, message: String | (cursor.line, message);
+    let mut entries = Vec::new();
+
+    //
+    loop {
+        cursor.skip(true, true);
+        let line = cursor.line;
+        match cursor.peek() {
+            Some('[')   => return Err(error(&cursor, String::from("tables are not supported in this subset of toml, use top level keys"))),
+            Some(_)     => {},
+            None        => return expand_calls(entries),
+        }
+
+        //
+        let key = cursor.toml_key().map_err(| message | error(&cursor, message))?;
+        if entries.iter().any(| (other, _, _) | *other == key) {
+            return Err(error(&cursor, format!("key '{}' is already set, keys can only be set once in toml", key)));
+        }
+        cursor.skip(false, false);
+        if cursor.peek() == Some('.') {
+            return Err(error(&cursor, String::from("dotted keys are not supported in this subset of toml, use top level keys")));
+        }
+        cursor.expect('=').map_err(| message | error(&cursor, message))?;
+        cursor.skip(false, false);
+        let value = cursor.toml().map_err(| message | error(&cursor, message))?;
+        entries.push((key, value, line));
+
+        // nothing but a comment may follow on the same line
+        cursor.skip(false, true);
+        match cursor.next() {
+            Some('\n') | None   => {},
+            Some(character)     => return Err(error(&cursor, format!("unexpected '{}' after the value", character))),
+        }
+    }
+}
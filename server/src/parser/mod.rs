@@ -1,6 +1,15 @@
+mod source;
+mod tokenizer;
+mod document;
+
+use verbosity;
 use std::net::Ipv4Addr;
 use std::fs;
 
+pub use self::source::{ Location, host_name, join_lines };
+pub use self::tokenizer::{ Word, tokenize, tokenize_line };
+pub use self::document::{ Value, Format, Entries, CALLS, parse_json, parse_toml };
+
 pub use std::rc::Rc;
 pub use std::cell::RefCell;
 
@@ -8,11 +17,13 @@ pub use std::cell::RefCell;
 pub type Item<T> = Rc<RefCell<T>>;
 
 //
+#[macro_export]
 macro_rules! item {
     ($item:expr)    => ($crate::parser::Rc::new($crate::parser::RefCell::new($item)));
 }
 
 //
+#[macro_export]
 macro_rules! unwrap_item {
     ($item:expr)    => ({
         match $crate::parser::Rc::try_unwrap($item) {
@@ -22,14 +33,6 @@ macro_rules! unwrap_item {
     });
 }
 
-// get the size of a file in bytes
-pub fn file_size(filename: &str) -> u32 {
-    match fs::metadata(filename) {
-        Ok(metadata)    => metadata.len() as u32,
-        Err(_)          => panic!("[ server ] unable to read size of '{}'", filename)
-    }
-}
-
 //
 pub fn checked_path(path: &str, base: &str, extention: &str, reverse: bool) -> Option<String> {
     use std::path::Path;
@@ -57,7 +60,7 @@ pub fn checked_path(path: &str, base: &str, extention: &str, reverse: bool) -> O
 }
 
 //
-pub fn unwrap_sequence(buffer: &Vec<u8>, sequence: &Vec<usize>) -> u64 {
+pub fn unwrap_sequence(buffer: &[u8], sequence: &[usize]) -> u64 {
     let mut value = 0;
     for index in sequence {
         value = value << 8 | buffer[*index] as u64;
@@ -68,8 +71,17 @@ pub fn unwrap_sequence(buffer: &Vec<u8>, sequence: &Vec<usize>) -> u64 {
 //
 #[derive(Clone)]
 pub struct BinaryFile {
-    pub path:   String,
-    pub name:   Option<String>,
+    pub path:       String,
+    pub name:       Option<String>,
+    pub revision:   Revision,
+}
+
+// which stored version of a binary file should be served
+#[derive(Clone)]
+pub enum Revision {
+    Latest,
+    Previous,
+    Hash(String),
 }
 
 // version channels
@@ -93,8 +105,104 @@ impl Channel {
             Channel::None       => "",
         }
     }
+
+    // get a channel from its name as used in the configuration
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "stable"    => Some(Channel::Stable),
+            "beta"      => Some(Channel::Beta),
+            "nightly"   => Some(Channel::Nightly),
+            "none"      => Some(Channel::None),
+            _           => None,
+        }
+    }
+
+    // get the name of the channel as used in the configuration
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Channel::Stable     => "stable",
+            Channel::Beta       => "beta",
+            Channel::Nightly    => "nightly",
+            Channel::None       => "none",
+        }
+    }
+}
+
+// words of a single call, nested arrays are flattened. every string of a document is quoted, so only a single
+// character is taken literally, longer strings are read like bare words and can name a character
+fn arguments(value: Value, words: &mut Vec<Word>, outer: bool) -> Result<(), String> {
+    match value {
+        Value::Text(text)                   => words.push(Word {
+            quoted:     text.chars().count() == 1,
+            text:       text,
+            leading:    String::from(" "),
+        }),
+        Value::Number(number)               => words.push(Word::new(&number)),
+        Value::Boolean(boolean)             => words.push(Word::new(if boolean { "true" } else { "false" })),
+        Value::Null if outer                => {},
+        Value::Array(values) if outer       => {
+            for value in values {
+                arguments(value, words, false)?;
+            }
+        },
+        Value::Table(_)                     => return Err(String::from("tables can not be used as arguments")),
+        _                                   => return Err(String::from("arguments can not be nested")),
+    }
+    Ok(())
+}
+
+// call identifier, keyword and the arguments of every call a key of a structured document stands for
+pub type Calls = (Option<char>, String, Vec<Vec<Word>>);
+
+// the calls of a key of a structured document. an explicit identifier is taken as it is, otherwise 'once' tells
+// call once keywords from item keywords. an array of arrays can only be meant for an item keyword, anything else
+// is left undecided
+pub fn document_calls<F: Fn(&str) -> Option<bool>>(key: &str, value: Value, once: F) -> Result<Calls, String> {
+    let keyword = String::from(key.trim_start_matches(['?', ':', '+', '@']));
+    let items = match &value {
+        Value::Array(values)    => values.iter().any(| value | matches!(value, Value::Array(_))),
+        _                       => false,
+    };
+    let identifier = match (key.chars().next(), once(key)) {
+        (Some(identifier @ ('?' | ':' | '+' | '@')), _)     => Some(identifier),
+        (_, Some(true))                                     => Some('?'),
+        (_, Some(false))                                    => Some(':'),
+        (_, None) if items                                  => Some(':'),
+        (_, None)                                           => None,
+    };
+
+    //
+    let values = match (identifier, value) {
+        (Some(':') | Some('+'), Value::Array(values))   => values,
+        (_, value)                                      => vec![value],
+    };
+    let mut calls = Vec::new();
+    for value in values {
+        let mut words = Vec::new();
+        arguments(value, &mut words, true)?;
+        calls.push(words);
+    }
+    Ok((identifier, keyword, calls))
 }
 
+// characters that are easier to name than to write
+pub const CHARACTERS: &[(&str, u8)] = &[
+    ("null",        0x00),
+    ("backspace",   0x08),
+    ("tab",         0x09),
+    ("newline",     0x0a),
+    ("enter",       0x0d),
+    ("return",      0x0d),
+    ("escape",      0x1b),
+    ("space",       0x20),
+    ("quote",       0x22),
+    ("hash",        0x23),
+    ("dollar",      0x24),
+    ("apostrophe",  0x27),
+    ("backslash",   0x5c),
+    ("delete",      0x7f),
+];
+
 //
 pub enum CallMode {
     Item(String),
@@ -114,7 +222,7 @@ impl CallMode {
 }
 
 //
-pub type Function<'a> = Box<FnMut(&mut Stack, Option<&str>) + 'a>;
+pub type Function<'a> = Box<dyn FnMut(&mut Stack, Option<&str>) + 'a>;
 
 //
 pub struct Keyword<'a> {
@@ -125,48 +233,130 @@ pub struct Keyword<'a> {
 
 //
 pub struct Stack {
-    lines:              Vec<Option<Vec<String>>>,
+    lines:              Vec<Option<Vec<Word>>>,
+    locations:          Vec<Location>,
+    document:           Option<Entries>,
     debug_stack:        Vec<String>,
-    current_line:       usize,
+    current_line:       Location,
     counter:            usize,
 }
 
 //
 impl Stack {
 
-    //
+    // includes, variables and conditionals are resolved before any keyword is seen, structured documents
+    // are only turned into lines once the keywords are known
     pub fn new(path: &str) -> Self {
-        use std::io::{ BufRead, BufReader };
-
-        match fs::File::open(path) {
-            Ok(file)    => {
-
-                // collect all words in the file
-                let mut lines = Vec::new();
-                for line in BufReader::new(file).lines() {
-                    if let Ok(line) = line {
-                        let line = if line.len() > 0 && line.chars().nth(0).unwrap() != '#' {
-                            Some(line.split_whitespace().map(| word | String::from(word)).rev().collect())
-                        } else {
-                            None
-                        };
-                        lines.insert(0, line);
-                    }
-                }
-                lines.push(None);
-
-                // return new parser
-                Self {
-                    lines:          lines,
-                    debug_stack:    Vec::new(),
-                    current_line:   0,
-                    counter:        0,
-                }
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_)      => panic!("[ parser ] unable to open '{}'", path),
+        };
+
+        //
+        let entries = match Format::detect(path, &content) {
+            Format::Keywords    => None,
+            Format::Json        => Some(parse_json(&content)),
+            Format::Toml        => Some(parse_toml(&content)),
+        };
+        let document = match entries {
+            Some(Ok(document))          => Some(document),
+            Some(Err((line, message)))  => panic!("[ parser ] [ line : {} ] {}", line, message),
+            None                        => None,
+        };
+
+        //
+        let mut stack = Self::empty(document);
+        if stack.document.is_none() {
+            stack.load(source::read(path));
+        }
+        stack
+    }
+
+    // keyword text that doesn't live in a file, lines are shown along with the name
+    pub fn from_text(name: &str, content: &str) -> Self {
+        let mut stack = Self::empty(None);
+        stack.load(source::read_text(name, content));
+        stack
+    }
+
+    //
+    fn empty(document: Option<Entries>) -> Self {
+        Self {
+            lines:          Vec::new(),
+            locations:      Vec::new(),
+            document:       document,
+            debug_stack:    Vec::new(),
+            current_line:   Location {
+                path:       None,
+                number:     0,
             },
-            Err(_)      => panic!("[ parser ] unable to open '{}'", path)
+            counter:        0,
+        }
+    }
+
+    // lines are read from the top of the stack, so new lines go underneath the ones already loaded
+    fn load(&mut self, lines: Vec<(Vec<Word>, Location)>) {
+        let (lines, locations): (Vec<_>, Vec<_>) = lines.into_iter().rev().map(| (words, location) | (Some(words.into_iter().rev().collect()), location)).unzip();
+        self.lines.splice(0..0, lines);
+        self.locations.splice(0..0, locations);
+        if !matches!(self.lines.last(), Some(None)) {
+            self.push_sentinel();
         }
     }
 
+    // lines of some text that are read before anything else
+    pub fn prelude(&mut self, name: &str, content: &str) {
+        if matches!(self.lines.last(), Some(None)) {
+            self.lines.pop();
+            self.locations.pop();
+        }
+        for (words, location) in source::read_text(name, content).into_iter().rev() {
+            self.lines.push(Some(words.into_iter().rev().collect()));
+            self.locations.push(location);
+        }
+        self.push_sentinel();
+    }
+
+    // the line that is popped before the first one is read
+    fn push_sentinel(&mut self) {
+        self.lines.push(None);
+        self.locations.push(Location {
+            path:       None,
+            number:     0,
+        });
+    }
+
+    // turn the keys of a structured document into keyword lines. an explicit call identifier is taken as
+    // it is, otherwise call once keywords take the value as their arguments and item keywords are called
+    // for every element of an array
+    pub fn expand<F: Fn(&str) -> Option<bool>>(&mut self, once: F) {
+        let document = match self.document.take() {
+            Some(document)  => document,
+            None            => return,
+        };
+
+        //
+        let mut lines = Vec::new();
+        for (key, value, line) in document {
+            let location = Location {
+                path:       None,
+                number:     line,
+            };
+
+            // keywords that are neither known nor marked are reported once they are called
+            let (identifier, keyword, calls) = match document_calls(&key, value, &once) {
+                Ok(calls)       => calls,
+                Err(message)    => panic!("[ parser ] [ line : {} ] {}", location, message),
+            };
+            for arguments in calls {
+                let mut words = vec![Word::new(&format!("{}{}", identifier.unwrap_or(':'), keyword))];
+                words.extend(arguments);
+                lines.push((words, location.clone()));
+            }
+        }
+        self.load(lines);
+    }
+
     //
     pub fn panic(&self, message: String) -> ! {
         panic!("[ parser ] [ line : {} ] {}", self.current_line, message)
@@ -177,8 +367,8 @@ impl Stack {
         self.debug_stack.push(String::from(debug_message));
     }
 
-    //
-    pub fn pop(&mut self) -> String {
+    // take the next word along with whether it was quoted
+    pub fn pop_word(&mut self) -> Word {
         let debug_message = self.debug_stack.pop().unwrap();
         match self.lines.last_mut().unwrap() {
             Some(words) => {
@@ -191,11 +381,34 @@ impl Stack {
         }
     }
 
+    //
+    pub fn pop(&mut self) -> String {
+        self.pop_word().text
+    }
+
+    // get all remaining words of the line, separated by the whitespace they were written with
+    pub fn pop_line(&mut self) -> String {
+        match self.lines.last_mut().unwrap() {
+            Some(words) => {
+                let mut line = String::new();
+                for (index, word) in words.drain(..).rev().enumerate() {
+                    if index > 0 {
+                        line.push_str(&word.leading);
+                    }
+                    line.push_str(&word.text);
+                }
+                line
+            },
+            None        => panic!("[ parser ] invalid line")
+        }
+    }
+
     //
     pub fn pop_newline(&mut self) -> Option<String> {
-        while let Some(line) = self.lines.pop() {
-            self.current_line += 1;
+        while self.lines.pop().is_some() {
+            self.locations.pop();
             if self.lines.last()?.is_some() {
+                self.current_line = self.locations.last().unwrap().clone();
                 break
             }
         };
@@ -215,6 +428,11 @@ impl Stack {
 
     //
     pub fn pop_counter(&mut self) -> usize {
+        self.pop_counter_or(| _ | None)
+    }
+
+    // same as pop_counter, but names are looked up before the value is parsed
+    pub fn pop_counter_or<F: Fn(&str) -> Option<usize>>(&mut self, lookup: F) -> usize {
         self.push_debug("no value specified");
         match self.pop().as_ref() {
             "*"     => {
@@ -222,12 +440,12 @@ impl Stack {
                 self.counter
             },
             value   => {
-                match value.parse() {
-                    Ok(value)   => {
+                match lookup(value).or_else(| | value.parse().ok()) {
+                    Some(value) => {
                         self.counter = value;
                         value
                     },
-                    Err(_)      => panic!("[ parser ] [ line : {} ] failed to parse value", self.current_line)
+                    None        => panic!("[ parser ] [ line : {} ] failed to parse value '{}'", self.current_line, value)
                 }
             },
         }
@@ -252,28 +470,6 @@ impl Stack {
         self.pop_u64() as u8
     }
 
-    //
-    pub fn pop_mode(&mut self) -> Option<u8> {
-        self.push_debug("no mode specified");
-        match self.pop().as_ref() {
-            "base"  => None,
-            word    => {
-                if word.len() != 8 {
-                    self.panic(format!("mode identifier must have 8 bits"));
-                }
-                let mut mask = 0;
-                for offset in 0..8 {
-                    match word.chars().nth(offset).unwrap() {
-                        '0'     => continue,
-                        '1'     => mask |= 1 << offset,
-                        state   => self.panic(format!("unexpected character '{}' in channel identifier", state))
-                    }
-                }
-                Some(mask)
-            },
-        }
-    }
-
     //
     pub fn pop_index(&mut self) -> usize {
         self.push_debug("no index specified");
@@ -293,12 +489,25 @@ impl Stack {
     //
     pub fn pop_channel(&mut self) -> Channel {
         self.push_debug("no channel specified. valid options are 'stable', 'beta', 'nightly' or 'none'");
+        let channel = self.pop();
+        match Channel::from_name(&channel) {
+            Some(channel)   => channel,
+            None            => panic!("[ parser ] invalid channel '{}'. valid options are 'stable', 'beta', 'nightly' or 'none'", channel)
+        }
+    }
+
+    //
+    pub fn pop_revision(&mut self) -> Revision {
+        self.push_debug("no revision specified. valid options are 'latest', 'previous' or a content hash");
         match self.pop().as_ref() {
-            "stable"    => Channel::Stable,
-            "beta"      => Channel::Beta,
-            "nightly"   => Channel::Nightly,
-            "none"      => Channel::None,
-            channel     => panic!("[ parser ] invalid channel '{}'. valid options are 'stable', 'beta', 'nightly' or 'none'", channel)
+            "latest"    => Revision::Latest,
+            "previous"  => Revision::Previous,
+            hash        => {
+                if hash.is_empty() || !hash.chars().all(| character | character.is_ascii_hexdigit()) {
+                    self.panic(format!("invalid revision '{}'. valid options are 'latest', 'previous' or a content hash", hash));
+                }
+                Revision::Hash(hash.to_lowercase())
+            },
         }
     }
 
@@ -319,7 +528,7 @@ impl Stack {
     }
 
     //
-    pub fn pop_binary(&mut self, binary_files: &Vec<BinaryFile>) -> String {
+    pub fn pop_binary(&mut self, binary_files: &[BinaryFile]) -> BinaryFile {
 
         //
         self.push_debug("no binary name or index specified");
@@ -329,13 +538,13 @@ impl Stack {
         match word.parse() {
             Ok(index)    => {
                 let index: usize = index;
-                binary_files[index].path.clone()
+                binary_files[index].clone()
             },
             _            => {
-                for (index, binary) in binary_files.iter().enumerate() {
+                for binary in binary_files.iter() {
                     if let Some(name) = &binary.name {
                         if name == &word {
-                            return binary.path.clone()
+                            return binary.clone()
                         }
                     }
                 }
@@ -347,11 +556,30 @@ impl Stack {
     //
     pub fn pop_ascii(&mut self) -> u8 {
         self.push_debug("no character specified");
-        let word = self.pop();
+        let word = self.pop_word();
+
+        // a quoted character is taken as it is, so '" "' or '"\x1b"' work as expected
+        if word.quoted {
+            let mut characters = word.text.chars();
+            return match (characters.next(), characters.next()) {
+                (Some(character), None) if (character as u32) < 256    => character as u32 as u8,
+                _                                                       => self.panic(format!("'{}' is not a single ascii character", word.text)),
+            };
+        }
+
+        // names come before the short forms, so 'space' is not taken as 's'. like before there were names,
+        // 'b' takes the character after it and anything else starting with 's' is a space
+        let word = word.text;
+        if let Some((_, character)) = CHARACTERS.iter().find(| (name, _) | *name == word) {
+            return *character;
+        }
         match word.chars().nth(0).unwrap() {
-            'b'     => word.chars().nth(1).expect("[ parser ] no character specified") as u8,
-            's'     => 32,
-            _       => word.parse().expect("[ parser ] failed to parse character"),
+            'b' if word.chars().count() >= 2    => word.chars().nth(1).unwrap() as u8,
+            's'                                 => 32,
+            _                                   => match word.parse() {
+                Ok(character)   => character,
+                Err(_)          => self.panic(format!("invalid character '{}'", word)),
+            },
         }
     }
 
@@ -362,13 +590,13 @@ impl Stack {
 
                 //
                 let mut sequence: Vec<usize> = Vec::new();
-                while let Some(word) = words.pop() {
+                while let Some(Word { text: word, .. }) = words.pop() {
 
                     //
                     if word == ";" {
                         match sequence.len() {
                             0       => panic!("[ parser ] [ line : {} ] empty sequence", self.current_line),
-                            1...3   => return sequence,
+                            1..=3   => return sequence,
                             _       => panic!("[ parser ] [ line : {} ] sequence must be 4 items at max", self.current_line),
                         }
                     }
@@ -387,7 +615,7 @@ impl Stack {
 
     //
     #[cfg(feature = "controller")]
-    pub fn pop_client(&mut self, client_names: &Vec<String>) -> u8 {
+    pub fn pop_client(&mut self, client_names: &[String]) -> u8 {
 
         //
         self.push_debug("no client name or index specified");
@@ -402,7 +630,7 @@ impl Stack {
                         return index as u8
                     }
                 }
-                panic!("[ parser ] client name not found")
+                panic!("[ parser ] client, group or tag '{}' not found", word)
             },
         }
     }
@@ -419,12 +647,27 @@ impl<'a> Parser<'a> {
 
     //
     pub fn new(path: &str) -> Self {
+        verbose!(verbosity::DETAILS, "[ parser ] parsing '{}'", path);
         Self {
             stack:    Stack::new(path),
             keywords:       Default::default(),
         }
     }
 
+    // keyword text that doesn't live in a file
+    pub fn from_text(name: &str, content: &str) -> Self {
+        verbose!(verbosity::DETAILS, "[ parser ] parsing '{}'", name);
+        Self {
+            stack:          Stack::from_text(name, content),
+            keywords:       Default::default(),
+        }
+    }
+
+    // parse some text before the file itself
+    pub fn prelude(&mut self, name: &str, content: &str) {
+        self.stack.prelude(name, content);
+    }
+
     //
     pub fn register(&mut self, identifier: &str, once: bool, function: Function<'a>) {
         self.keywords.push(Keyword {
@@ -436,6 +679,8 @@ impl<'a> Parser<'a> {
 
     //
     pub fn parse(&mut self) {
+        let keywords = &self.keywords;
+        self.stack.expand(| name | keywords.iter().find(| keyword | keyword.identifier == name).map(| keyword | matches!(keyword.call_mode, CallMode::Once(_))));
 
         //
         while let Some(mut word) = self.stack.pop_newline() {
@@ -445,7 +690,7 @@ impl<'a> Parser<'a> {
 
             //
             let keyword = self.keywords.iter_mut().find(| keyword | keyword.identifier == word);
-            let mut keyword = match keyword {
+            let keyword = match keyword {
                 Some(keyword)   => keyword,
                 None            => self.stack.panic(format!("invalid keyword '{}'", word)),
             };
@@ -462,7 +707,7 @@ impl<'a> Parser<'a> {
                                     (keyword.function)(&mut self.stack, None);
                                     keyword.call_mode = CallMode::Once(true)
                                 },
-                                true    => self.stack.panic(format!("repeated call of call once")),
+                                true    => self.stack.panic(String::from("repeated call of call once")),
                             }
                         },
                         _                       => self.stack.panic(format!("invalid call mode. use ':', '+' or '@' for '{}'", keyword.identifier)),
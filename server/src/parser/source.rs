@@ -0,0 +1,264 @@
+use super::tokenizer::{ Word, tokenize };
+use std::path::{ Path, PathBuf };
+use std::ffi::CStr;
+use std::fmt;
+use std::fs;
+use libc;
+
+// where a line came from, lines of the file that is parsed are only shown by their number
+#[derive(Clone)]
+pub struct Location {
+    pub path:       Option<String>,
+    pub number:     usize,
+}
+
+//
+impl fmt::Display for Location {
+
+    //
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match &self.path {
+            Some(path)  => write!(formatter, "{} in '{}'", self.number, path),
+            None        => write!(formatter, "{}", self.number),
+        }
+    }
+}
+
+// one level of 'if', 'else' and 'end'
+struct Condition {
+    active:         bool,
+    matched:        bool,
+    otherwise:      bool,
+}
+
+// everything that lives across included files
+struct Source {
+    variables:      Vec<(String, String)>,
+    includes:       Vec<PathBuf>,
+    host:           String,
+    lines:          Vec<(Vec<Word>, Location)>,
+}
+
+// name of the machine the server is running on
+pub fn host_name() -> String {
+    let mut buffer = [0 as libc::c_char; 256];
+    match unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len() - 1) } {
+        0   => unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().into_owned(),
+        _   => String::new(),
+    }
+}
+
+// read a file with every include, variable and conditional resolved, in the order the lines appear
+pub fn read(path: &str) -> Vec<(Vec<Word>, Location)> {
+    let mut source = Source {
+        variables:      Vec::new(),
+        includes:       Vec::new(),
+        host:           host_name(),
+        lines:          Vec::new(),
+    };
+    source.read(Path::new(path), None);
+    source.lines
+}
+
+// same as read, but for text that doesn't live in a file. includes are relative to the working directory
+pub fn read_text(name: &str, content: &str) -> Vec<(Vec<Word>, Location)> {
+    let mut source = Source {
+        variables:      Vec::new(),
+        includes:       Vec::new(),
+        host:           host_name(),
+        lines:          Vec::new(),
+    };
+    let directory = std::env::current_dir().unwrap_or_default();
+    source.lines_of(content, &directory, Some(String::from(name)), name);
+    source.lines
+}
+
+// every line of a file before anything is resolved, along with its number. a backslash at the very end continues
+// a line on the next one, unless it is the old 'b\' form of a backslash. blank lines and comments are never
+// continued. fails with the number of a line that continues past the end of the file
+pub fn join_lines(content: &str) -> Result<Vec<(usize, String)>, usize> {
+    let mut joined = Vec::new();
+    let mut lines = content.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let mut line = String::from(line);
+        if !line.trim().is_empty() && !line.trim_start().starts_with('#') {
+            while line.ends_with('\\') && line.split_whitespace().last() != Some("b\\") {
+                line.pop();
+                match lines.next() {
+                    Some((_, next)) => line.push_str(next),
+                    None            => return Err(index + 1),
+                }
+            }
+        }
+        joined.push((index + 1, line));
+    }
+    Ok(joined)
+}
+
+//
+impl Source {
+
+    // a variable set in the configuration shadows the environment
+    fn lookup(&self, name: &str) -> Option<String> {
+        if let Some((_, value)) = self.variables.iter().rev().find(| (variable, _) | variable == name) {
+            return Some(value.clone());
+        }
+        match name {
+            "HOST"  => Some(self.host.clone()),
+            name    => std::env::var(name).ok(),
+        }
+    }
+
+    // split a line into words with every '${NAME}' resolved
+    fn tokenize(&self, line: &str, location: &Location, directory: &Path) -> Vec<Word> {
+        let lookup = | name: &str | match name {
+            "DIRECTORY" => Ok(directory.display().to_string()),
+            name        => self.lookup(name).ok_or_else(| | format!("undefined variable '{}'", name)),
+        };
+        match tokenize(line, lookup) {
+            Ok(words)   => words,
+            Err(error)  => panic!("[ parser ] [ line : {} ] {}", location, error),
+        }
+    }
+
+    //
+    fn read(&mut self, path: &Path, included: Option<&Location>) {
+        let canonical = match fs::canonicalize(path) {
+            Ok(canonical)   => canonical,
+            Err(_)          => match included {
+                Some(location)  => panic!("[ parser ] [ line : {} ] unable to open '{}'", location, path.display()),
+                None            => panic!("[ parser ] unable to open '{}'", path.display()),
+            },
+        };
+
+        // a file may be included more than once, but never by itself
+        if let Some(position) = self.includes.iter().position(| include | include == &canonical) {
+            let mut cycle: Vec<String> = self.includes[position..].iter().map(| include | format!("'{}'", include.display())).collect();
+            cycle.push(format!("'{}'", canonical.display()));
+            panic!("[ parser ] [ line : {} ] include cycle {}", included.unwrap(), cycle.join(" -> "));
+        }
+
+        //
+        let content = match fs::read_to_string(&canonical) {
+            Ok(content)     => content,
+            Err(_)          => panic!("[ parser ] unable to open '{}'", path.display()),
+        };
+        let directory = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+        let name = included.map(| _ | path.display().to_string());
+
+        //
+        self.includes.push(canonical.clone());
+        self.lines_of(&content, &directory, name, &path.display().to_string());
+        self.includes.pop();
+    }
+
+    // resolve the lines of a single file or text, 'name' is shown along with the line number
+    fn lines_of(&mut self, content: &str, directory: &Path, name: Option<String>, label: &str) {
+        let mut conditions: Vec<Condition> = Vec::new();
+        let lines = match join_lines(content) {
+            Ok(lines)   => lines,
+            Err(number) => panic!("[ parser ] [ line : {} ] line continues past the end of the file", Location { path: name, number: number }),
+        };
+        for (number, line) in lines {
+            let location = Location {
+                path:       name.clone(),
+                number:     number,
+            };
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            // conditionals are tracked even inside skipped blocks so they can be nested
+            let active = conditions.iter().all(| condition | condition.active);
+            match line.split_whitespace().next().unwrap() {
+                "if"    => {
+                    let matched = active && self.condition(self.tokenize(&line, &location, directory), &location);
+                    conditions.push(Condition {
+                        active:     matched,
+                        matched:    matched,
+                        otherwise:  false,
+                    });
+                    continue;
+                },
+                "else"  => {
+                    let parent = conditions.iter().rev().skip(1).all(| condition | condition.active);
+                    match conditions.last_mut() {
+                        Some(condition) if !condition.otherwise => {
+                            condition.active = parent && !condition.matched;
+                            condition.otherwise = true;
+                        },
+                        Some(_)                                 => panic!("[ parser ] [ line : {} ] repeated 'else'", location),
+                        None                                    => panic!("[ parser ] [ line : {} ] 'else' without 'if'", location),
+                    }
+                    continue;
+                },
+                "end"   => {
+                    if conditions.pop().is_none() {
+                        panic!("[ parser ] [ line : {} ] 'end' without 'if'", location);
+                    }
+                    continue;
+                },
+                _       if !active => continue,
+                _       => {},
+            }
+
+            //
+            let mut words = self.tokenize(&line, &location, directory);
+            match words.first().map(| word | word.text.as_str()) {
+                Some("include")     => self.include(words.split_off(1), directory, &location, false),
+                Some("include?")    => self.include(words.split_off(1), directory, &location, true),
+                Some("set")         => self.set(words.split_off(1), &location, true),
+                Some("default")     => self.set(words.split_off(1), &location, false),
+                Some(_)             => self.lines.push((words, location)),
+                None                => {},
+            }
+        }
+
+        //
+        if !conditions.is_empty() {
+            panic!("[ parser ] unterminated 'if' in '{}'", label);
+        }
+    }
+
+    // only 'if host <name> ..' is supported for now
+    fn condition(&self, words: Vec<Word>, location: &Location) -> bool {
+        let words: Vec<&str> = words.iter().skip(1).map(| word | word.text.as_str()).collect();
+        match words.split_first() {
+            Some((&"host", names)) if !names.is_empty()     => names.iter().any(| name | *name == self.host),
+            Some((&"host", _))                              => panic!("[ parser ] [ line : {} ] no host specified", location),
+            Some((condition, _))                            => panic!("[ parser ] [ line : {} ] invalid condition '{}'. valid options are 'host'", location, condition),
+            None                                            => panic!("[ parser ] [ line : {} ] no condition specified", location),
+        }
+    }
+
+    // relative paths are relative to the including file, optional includes may be missing
+    fn include(&mut self, words: Vec<Word>, directory: &Path, location: &Location, optional: bool) {
+        if words.len() != 1 {
+            panic!("[ parser ] [ line : {} ] include takes exactly one file", location);
+        }
+
+        //
+        let path = directory.join(&words[0].text);
+        if optional && !path.exists() {
+            return;
+        }
+        self.read(&path, Some(location));
+    }
+
+    // 'default' only sets variables that are neither set nor in the environment
+    fn set(&mut self, mut words: Vec<Word>, location: &Location, overwrite: bool) {
+        if words.is_empty() {
+            panic!("[ parser ] [ line : {} ] no variable specified", location);
+        }
+
+        //
+        let name = words.remove(0).text;
+        if !name.chars().all(| character | character.is_ascii_alphanumeric() || character == '_') {
+            panic!("[ parser ] [ line : {} ] invalid variable name '{}'", location, name);
+        }
+        if overwrite || self.lookup(&name).is_none() {
+            let value: Vec<String> = words.into_iter().map(| word | word.text).collect();
+            self.variables.push((name, value.join(" ")));
+        }
+    }
+}
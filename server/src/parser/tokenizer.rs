@@ -0,0 +1,169 @@
+// a single word of a line, quoted words are never taken as anything but text. the whitespace in front of
+// it is kept so the rest of a line can be taken as it was written
+#[derive(Clone, Debug, PartialEq)]
+pub struct Word {
+    pub text:       String,
+    pub quoted:     bool,
+    pub leading:    String,
+}
+
+//
+impl Word {
+
+    //
+    pub fn new(text: &str) -> Self {
+        Self {
+            text:       String::from(text),
+            quoted:     false,
+            leading:    String::from(" "),
+        }
+    }
+}
+
+// escape sequence inside double quotes, the backslash is already taken
+fn escape<I: Iterator<Item = char>>(characters: &mut I) -> Result<char, String> {
+    match characters.next() {
+        Some('n')       => Ok('\n'),
+        Some('r')       => Ok('\r'),
+        Some('t')       => Ok('\t'),
+        Some('0')       => Ok('\0'),
+        Some('e')       => Ok('\x1b'),
+        Some('\\')      => Ok('\\'),
+        Some('"')       => Ok('"'),
+        Some('\'')      => Ok('\''),
+        Some('$')       => Ok('$'),
+
+        // exactly two hex digits
+        Some('x')       => {
+            let digits: String = characters.take(2).collect();
+            match u8::from_str_radix(&digits, 16) {
+                Ok(value) if digits.len() == 2  => Ok(value as char),
+                _                               => Err(format!("invalid escape sequence '\\x{}'", digits)),
+            }
+        },
+
+        // '\u{..}' with up to six hex digits
+        Some('u')       => {
+            if characters.next() != Some('{') {
+                return Err(String::from("expected '{' after '\\u'"));
+            }
+            let digits: String = characters.take_while(| character | *character != '}').collect();
+            match u32::from_str_radix(&digits, 16).ok().and_then(std::char::from_u32) {
+                Some(character) if digits.len() <= 6    => Ok(character),
+                _                                       => Err(format!("invalid escape sequence '\\u{{{}}}'", digits)),
+            }
+        },
+
+        //
+        Some(character) => Err(format!("invalid escape sequence '\\{}'", character)),
+        None            => Err(String::from("unterminated escape sequence")),
+    }
+}
+
+// split a line into words. words are separated by whitespace unless it is quoted, double quotes take
+// escape sequences, single quotes take everything literally and a '#' at the start of a word comments
+// out the rest of the line. 'lookup' resolves '${NAME}' outside of single quotes
+pub fn tokenize<F: FnMut(&str) -> Result<String, String>>(line: &str, lookup: F) -> Result<Vec<Word>, String> {
+    tokenize_line(line, lookup).map(| (words, _) | words)
+}
+
+// same as tokenize, but the comment is kept
+pub fn tokenize_line<F: FnMut(&str) -> Result<String, String>>(line: &str, mut lookup: F) -> Result<(Vec<Word>, Option<String>), String> {
+    let mut words = Vec::new();
+    let mut characters = line.chars().peekable();
+    let mut leading = String::new();
+    loop {
+
+        // skip to the next word
+        while let Some(character) = characters.peek().copied().filter(| character | character.is_whitespace()) {
+            leading.push(character);
+            characters.next();
+        }
+        match characters.peek() {
+            Some('#')   => return Ok((words, Some(characters.collect::<String>().trim_end().to_string()))),
+            None        => return Ok((words, None)),
+            Some(_)     => {},
+        }
+
+        //
+        let mut word = Word::new("");
+        word.leading = std::mem::take(&mut leading);
+        while let Some(character) = characters.next() {
+            match character {
+                character if character.is_whitespace() => {
+                    leading.push(character);
+                    break;
+                },
+
+                // the old 'b'' and 'b"' forms of a quote character are not the start of a quote
+                '\'' | '"' if legacy_character(&word, &mut characters) => word.text.push(character),
+
+                //
+                '\''    => {
+                    word.quoted = true;
+                    loop {
+                        match characters.next() {
+                            Some('\'')      => break,
+                            Some(character) => word.text.push(character),
+                            None            => return Err(String::from("unterminated quote")),
+                        }
+                    }
+                },
+
+                //
+                '"'     => {
+                    word.quoted = true;
+                    loop {
+                        match characters.next() {
+                            Some('"')                                       => break,
+                            Some('\\')                                      => word.text.push(escape(&mut characters)?),
+                            Some('$') if characters.peek() == Some(&'{')    => word.text.push_str(&variable(&mut characters, &mut lookup)?),
+                            Some(character)                                 => word.text.push(character),
+                            None                                            => return Err(String::from("unterminated quote")),
+                        }
+                    }
+                },
+
+                // '$${' is a literal '${', a '$' on its own is left alone
+                '$'     => match characters.peek().copied() {
+                    Some('{')                                   => word.text.push_str(&variable(&mut characters, &mut lookup)?),
+                    Some('$') if escaped_variable(&characters)  => {
+                        characters.next();
+                        word.text.push('$');
+                        word.text.push(characters.next().unwrap());
+                    },
+                    _                                           => word.text.push('$'),
+                },
+
+                //
+                character => word.text.push(character),
+            }
+        }
+        words.push(word);
+    }
+}
+
+// a quote that directly follows a lone 'b' and ends the word
+fn legacy_character<I: Iterator<Item = char>>(word: &Word, characters: &mut std::iter::Peekable<I>) -> bool {
+    !word.quoted && word.text == "b" && characters.peek().is_none_or(| character | character.is_whitespace())
+}
+
+// check for the '{' of '$${' behind the second '$'
+fn escaped_variable<I: Iterator<Item = char> + Clone>(characters: &std::iter::Peekable<I>) -> bool {
+    let mut ahead = characters.clone();
+    ahead.next();
+    ahead.next() == Some('{')
+}
+
+// the name of a variable up to the closing brace, the '$' is already taken
+fn variable<I: Iterator<Item = char>, F: FnMut(&str) -> Result<String, String>>(characters: &mut I, lookup: &mut F) -> Result<String, String> {
+    characters.next();
+    let mut name = String::new();
+    loop {
+        match characters.next() {
+            Some('}')       => return lookup(&name),
+            Some(character) => name.push(character),
+            None            => return Err(String::from("unterminated variable")),
+        }
+    }
+}
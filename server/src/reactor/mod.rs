@@ -0,0 +1,160 @@
+use libc;
+use std::os::unix::io::{ AsRawFd, FromRawFd, RawFd };
+use std::time::{ Duration, Instant };
+use std::io::Read;
+use std::fs::File;
+
+// what a file descriptor or timer belongs to
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Token {
+    Signal,
+    Watcher,
+    Control,
+    Client(usize),
+    Controller(usize),
+}
+
+// what a file descriptor is waited on for
+#[derive(Copy, Clone, PartialEq)]
+pub enum Interest {
+    Read,
+    Write,
+}
+
+// single threaded event loop, every descriptor is waited on with one call to poll
+pub struct Reactor {
+    descriptors:    Vec<libc::pollfd>,
+    tokens:         Vec<Token>,
+    timers:         Vec<(Instant, Token)>,
+}
+
+//
+impl Reactor {
+
+    //
+    pub fn new() -> Self {
+        Self {
+            descriptors:    Vec::new(),
+            tokens:         Vec::new(),
+            timers:         Vec::new(),
+        }
+    }
+
+    // wait for a descriptor to become readable or writable
+    pub fn register(&mut self, descriptor: RawFd, token: Token, interest: Interest) {
+        let events = match interest {
+            Interest::Read  => libc::POLLIN,
+            Interest::Write => libc::POLLOUT,
+        };
+        self.descriptors.push(libc::pollfd { fd: descriptor, events: events, revents: 0 });
+        self.tokens.push(token);
+    }
+
+    // stop waiting for a descriptor, has to be called before it's closed
+    pub fn deregister(&mut self, descriptor: RawFd) {
+        while let Some(index) = self.descriptors.iter().position(| entry | entry.fd == descriptor) {
+            self.descriptors.remove(index);
+            self.tokens.remove(index);
+        }
+    }
+
+    // report the token once the duration passed, replaces the previous timer of the token
+    pub fn timer(&mut self, duration: Duration, token: Token) {
+        self.timers.retain(| (_, timer) | *timer != token);
+        self.timers.push((Instant::now() + duration, token));
+    }
+
+    // block until at least one descriptor is ready or a timer expired
+    pub fn poll(&mut self) -> Vec<Token> {
+        let mut ready: Vec<Token> = Vec::new();
+
+        //
+        while ready.is_empty() {
+            let timeout = match self.timers.iter().map(| (deadline, _) | *deadline).min() {
+                Some(deadline)  => deadline.saturating_duration_since(Instant::now()).as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32,
+                None            => -1,
+            };
+
+            //
+            let count = unsafe { libc::poll(self.descriptors.as_mut_ptr(), self.descriptors.len() as libc::nfds_t, timeout) };
+            if count < 0 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                panic!("[ reactor ] poll failed");
+            }
+
+            //
+            for (descriptor, token) in self.descriptors.iter().zip(&self.tokens) {
+                if descriptor.revents != 0 && !ready.contains(token) {
+                    ready.push(*token);
+                }
+            }
+
+            //
+            let now = Instant::now();
+            for (_, token) in self.timers.iter().filter(| (deadline, _) | *deadline <= now) {
+                if !ready.contains(token) {
+                    ready.push(*token);
+                }
+            }
+            self.timers.retain(| (deadline, _) | *deadline > now);
+        }
+        ready
+    }
+}
+
+// signals are blocked and read from a descriptor instead of interrupting the server
+pub struct Signals {
+    file:       File,
+}
+
+//
+impl Signals {
+
+    // has to be created before any thread is spawned, so every thread blocks the signals
+    pub fn new(signals: &[i32]) -> Self {
+        unsafe {
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            for signal in signals {
+                libc::sigaddset(&mut set, *signal);
+            }
+
+            //
+            if libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) != 0 {
+                panic!("[ reactor ] unable to block signals");
+            }
+            let descriptor = libc::signalfd(-1, &set, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC);
+            if descriptor < 0 {
+                panic!("[ reactor ] unable to create signal descriptor");
+            }
+
+            //
+            Self {
+                file:   File::from_raw_fd(descriptor),
+            }
+        }
+    }
+
+    //
+    pub fn start(&self, reactor: &mut Reactor) {
+        reactor.register(self.file.as_raw_fd(), Token::Signal, Interest::Read);
+    }
+
+    // take the next pending signal
+    pub fn read(&mut self) -> Option<i32> {
+        let mut information = [0; 128];
+        match self.file.read(&mut information) {
+            Ok(128) => Some(u32::from_ne_bytes([information[0], information[1], information[2], information[3]]) as i32),
+            _       => None,
+        }
+    }
+}
+
+// name of a signal for the log
+pub fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGINT    => "SIGINT",
+        libc::SIGTERM   => "SIGTERM",
+        libc::SIGHUP    => "SIGHUP",
+        _               => "signal",
+    }
+}
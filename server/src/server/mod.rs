@@ -0,0 +1,187 @@
+use configuration::{ Configuration, Overrides };
+use reactor::{ Reactor, Signals, Token, signal_name };
+use client::Clients;
+use control::{ Control, Command };
+#[cfg(feature = "controller")]
+use controller;
+
+use std::time::{ Duration, Instant };
+use std::panic::{ self, AssertUnwindSafe };
+use std::io::Write;
+use libc;
+
+// how long the boards get to receive what is already being sent once the server stops
+const SHUTDOWN_TIMEOUT: Duration    = Duration::from_secs(10);
+
+// how often to check for scripts to finish while stopping
+const SCRIPT_INTERVAL: Duration     = Duration::from_millis(100);
+
+// everything that is started from a configuration
+struct Runtime {
+    clients:        Clients,
+    control:        Option<Control>,
+    #[cfg(feature = "controller")]
+    controllers:    controller::Manager,
+}
+
+//
+impl Runtime {
+
+    //
+    fn start(configuration: Configuration, reactor: &mut Reactor) -> Self {
+        let clients = configuration.client_manager.start(reactor);
+        let control = configuration.control_path.and_then(| path | Control::new(&path));
+        if let Some(control) = &control {
+            control.start(reactor);
+        }
+        #[cfg(feature = "controller")]
+        let mut controllers = configuration.controller_manager;
+        #[cfg(feature = "controller")]
+        controllers.start(reactor);
+
+        //
+        Self {
+            clients:        clients,
+            control:        control,
+            #[cfg(feature = "controller")]
+            controllers:    controllers,
+        }
+    }
+
+    // handle the clients and controllers until a signal arrives
+    fn run(&mut self, reactor: &mut Reactor, signals: &mut Signals) -> i32 {
+        loop {
+            let mut received = None;
+            for token in reactor.poll() {
+                match token {
+                    Token::Signal               => received = signals.read(),
+                    Token::Control              => self.control(reactor),
+                    #[cfg(feature = "controller")]
+                    Token::Controller(index)    => {
+                        for message in self.controllers.ready(index, reactor) {
+                            self.clients.event(message.target as usize, &message.payload, reactor);
+                        }
+                    },
+                    token                       => self.clients.ready(token, reactor),
+                }
+            }
+
+            //
+            if let Some(signal) = received {
+                return signal;
+            }
+        }
+    }
+
+    // handle the commands written to the control pipe
+    fn control(&mut self, reactor: &mut Reactor) {
+        let commands = match &mut self.control {
+            Some(control)   => control.ready(),
+            None            => return,
+        };
+
+        //
+        for command in commands {
+            match command {
+                Command::Enable(selectors)  => self.clients.enable(&selectors, reactor),
+                Command::Disable(selectors) => self.clients.disable(&selectors, reactor),
+                Command::Status(selectors)  => self.clients.status(&selectors),
+            }
+        }
+    }
+
+    // stop taking requests and wait for the running transfers, returns false if any had to be aborted along with
+    // the signal that aborted them
+    fn stop(&mut self, reactor: &mut Reactor, signals: &mut Signals, message: &str) -> (bool, Option<i32>) {
+        if let Some(control) = &self.control {
+            control.stop(reactor);
+        }
+        #[cfg(feature = "controller")]
+        self.controllers.stop(reactor);
+        self.clients.stop(reactor, message);
+
+        //
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while self.clients.busy() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::default() {
+                println!("[ server ] timed out waiting for running transfers");
+                self.clients.abort(reactor);
+                return (false, None);
+            }
+
+            // scripts run on their own thread, so they are checked periodically
+            reactor.timer(remaining.min(SCRIPT_INTERVAL), Token::Signal);
+            for token in reactor.poll() {
+                match token {
+                    Token::Signal               => {
+                        if let Some(signal) = signals.read() {
+                            println!("[ server ] received {} again, aborting running transfers", signal_name(signal));
+                            self.clients.abort(reactor);
+                            return (false, Some(signal));
+                        }
+                    },
+                    Token::Controller(_)        => {},
+                    token                       => self.clients.ready(token, reactor),
+                }
+            }
+        }
+        (true, None)
+    }
+}
+
+// parse the configuration again, a broken configuration is reported and ignored
+fn reload(configuration_path: &str, overrides: &Overrides) -> Option<Configuration> {
+    println!("[ server ] reloading '{}'", configuration_path);
+    match panic::catch_unwind(AssertUnwindSafe(| | Configuration::new(configuration_path, overrides))) {
+        Ok(configuration)   => Some(configuration),
+        Err(_)              => {
+            println!("[ server ] failed to reload '{}', keeping the current configuration", configuration_path);
+            None
+        },
+    }
+}
+
+// leave once the server is stopped, the exit code tells if any transfer had to be aborted
+fn exit(finished: bool) -> ! {
+    println!("[ server ] stopped");
+    let _ = std::io::stdout().flush();
+    std::process::exit(if finished { 0 } else { 1 });
+}
+
+// run the server until it's stopped by SIGINT or SIGTERM, SIGHUP reloads the configuration with the same overrides
+pub fn run(mut configuration: Configuration, configuration_path: String, overrides: Overrides) -> ! {
+    let mut signals = Signals::new(&[libc::SIGINT, libc::SIGTERM, libc::SIGHUP]);
+
+    //
+    loop {
+        let mut reactor = Reactor::new();
+        signals.start(&mut reactor);
+        let mut runtime = Runtime::start(configuration, &mut reactor);
+
+        //
+        configuration = loop {
+            let signal = runtime.run(&mut reactor, &mut signals);
+            if signal == libc::SIGHUP {
+                match reload(&configuration_path, &overrides) {
+                    Some(configuration) => break configuration,
+                    None                => continue,
+                }
+            }
+
+            //
+            println!("[ server ] received {}, shutting down", signal_name(signal));
+            let (finished, _) = runtime.stop(&mut reactor, &mut signals, "server shutting down");
+            exit(finished);
+        };
+
+        // the new configuration only takes over once the running transfers are done, unless the server is asked to stop
+        if let (_, Some(signal)) = runtime.stop(&mut reactor, &mut signals, "server reloading") {
+            if signal != libc::SIGHUP {
+                println!("[ server ] received {} while reloading, shutting down", signal_name(signal));
+                exit(false);
+            }
+        }
+        println!("[ server ] configuration reloaded");
+    }
+}
@@ -0,0 +1,217 @@
+use parser::{ BinaryFile, Channel, Revision };
+use std::sync::Mutex;
+use std::path::Path;
+use std::fs;
+
+// a single binary served to a client
+#[derive(Clone)]
+pub struct Entry {
+    pub hash:       String,
+    pub timestamp:  u64,
+    pub channel:    String,
+    pub path:       String,
+}
+
+//
+impl Entry {
+
+    // parse an entry from a line of a history file
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.splitn(4, ' ');
+        let hash = words.next()?;
+        let timestamp = words.next()?.parse().ok()?;
+        let channel = words.next()?;
+        let path = words.next()?;
+
+        Some(Self {
+            hash:       String::from(hash),
+            timestamp:  timestamp,
+            channel:    String::from(channel),
+            path:       String::from(path),
+        })
+    }
+
+    // format the entry as a line of a history file
+    fn format(&self) -> String {
+        format!("{} {} {} {}\n", self.hash, self.timestamp, self.channel, self.path)
+    }
+}
+
+// content addressed directory holding every binary served to the clients
+pub struct Store {
+    directory:  String,
+    retention:  usize,
+    lock:       Mutex<()>,
+}
+
+//
+impl Store {
+
+    // open or create a store in the given directory
+    pub fn new(directory: String, retention: usize) -> Self {
+        assert!(retention > 0, "[ store ] retention must keep at least one entry");
+
+        //
+        for subdirectory in &["objects", "clients"] {
+            if fs::create_dir_all(format!("{}{}", directory, subdirectory)).is_err() {
+                panic!("[ store ] unable to create '{}{}'", directory, subdirectory);
+            }
+        }
+
+        //
+        Self {
+            directory:  directory,
+            retention:  retention,
+            lock:       Mutex::new(()),
+        }
+    }
+
+    // calculate the content hash of a binary
+    pub fn hash(data: &[u8]) -> String {
+        use sha2::{ Sha256, Digest };
+        Sha256::digest(data).iter().map(| byte | format!("{:02x}", byte)).collect()
+    }
+
+    //
+    fn object_path(&self, hash: &str) -> String {
+        format!("{}objects/{}", self.directory, hash)
+    }
+
+    // client names keep their prefix, so histories may be nested in directories
+    fn history_path(&self, client: &str) -> String {
+        format!("{}clients/{}", self.directory, client)
+    }
+
+    // get every binary served to a client, oldest first
+    pub fn history(&self, client: &str) -> Vec<Entry> {
+        read_history(Path::new(&self.history_path(client)))
+    }
+
+    // find the file that should be served for a binary, None if there is no matching revision
+    pub fn resolve(&self, client: &str, binary: &BinaryFile) -> Option<String> {
+        match &binary.revision {
+
+            // serve the binary straight from disk
+            Revision::Latest        => Some(binary.path.clone()),
+
+            // serve the last binary that differs from the one currently on disk
+            Revision::Previous      => {
+                let current = fs::read(&binary.path).ok().map(| data | Self::hash(&data));
+                self.history(client).iter().rev()
+                    .find(| entry | entry.path == binary.path && Some(&entry.hash) != current.as_ref())
+                    .map(| entry | self.object_path(&entry.hash))
+            },
+
+            // serve a specific binary, a unique prefix of the hash is enough
+            Revision::Hash(prefix)  => {
+                let objects = match fs::read_dir(format!("{}objects", self.directory)) {
+                    Ok(objects) => objects,
+                    Err(_)      => return None,
+                };
+                let mut matches = objects.filter_map(| object | object.ok())
+                    .filter_map(| object | object.file_name().into_string().ok())
+                    .filter(| hash | hash.starts_with(prefix.as_str()));
+
+                //
+                match (matches.next(), matches.next()) {
+                    (Some(hash), None)  => Some(self.object_path(&hash)),
+                    (Some(_), Some(_))  => {
+                        println!("[ store ] hash '{}' is ambiguous", prefix);
+                        None
+                    },
+                    _                   => None,
+                }
+            },
+        }
+    }
+
+    // record a binary that was served to a client and apply the retention policy to its path
+    pub fn insert(&self, client: &str, path: &str, channel: Channel, data: &[u8]) -> String {
+        use std::time::{ SystemTime, UNIX_EPOCH };
+
+        //
+        let _guard = self.lock.lock().unwrap();
+        let hash = Self::hash(data);
+
+        // objects are immutable, so they only need to be written once
+        let object_path = self.object_path(&hash);
+        if fs::metadata(&object_path).is_err() {
+            let temporary_path = format!("{}.partial", object_path);
+            if fs::write(&temporary_path, data).and_then(| _ | fs::rename(&temporary_path, &object_path)).is_err() {
+                println!("[ store ] unable to write object '{}'", hash);
+                return hash;
+            }
+        }
+
+        //
+        let mut history = self.history(client);
+        history.push(Entry {
+            hash:       hash.clone(),
+            timestamp:  SystemTime::now().duration_since(UNIX_EPOCH).map(| time | time.as_secs()).unwrap_or(0),
+            channel:    String::from(channel.name()),
+            path:       String::from(path),
+        });
+
+        // only keep the newest entries of every path, so files requested by the board don't push kernels out
+        let mut surplus = history.iter().filter(| entry | entry.path == path).count().saturating_sub(self.retention);
+        history.retain(| entry | {
+            let expired = surplus > 0 && entry.path == path;
+            if expired {
+                surplus -= 1;
+            }
+            !expired
+        });
+
+        //
+        let content: String = history.iter().map(| entry | entry.format()).collect();
+        let history_path = self.history_path(client);
+        let created = Path::new(&history_path).parent().map_or(Ok(()), fs::create_dir_all);
+        if created.and_then(| _ | fs::write(&history_path, content)).is_err() {
+            println!("[ store ] [ {} ] unable to write history", client);
+        }
+
+        //
+        self.collect_garbage();
+        hash
+    }
+
+    // remove every object that is no longer referenced by any client history
+    fn collect_garbage(&self) {
+
+        //
+        let mut referenced: Vec<String> = Vec::new();
+        collect_references(Path::new(&format!("{}clients", self.directory)), &mut referenced);
+
+        //
+        if let Ok(objects) = fs::read_dir(format!("{}objects", self.directory)) {
+            for object in objects.filter_map(| object | object.ok()) {
+                if let Ok(hash) = object.file_name().into_string() {
+                    if !referenced.contains(&hash) {
+                        let _ = fs::remove_file(object.path());
+                    }
+                }
+            }
+        }
+    }
+}
+
+// entries of a history file, oldest first
+fn read_history(path: &Path) -> Vec<Entry> {
+    match fs::read_to_string(path) {
+        Ok(content) => content.lines().filter_map(Entry::parse).collect(),
+        Err(_)      => Vec::new(),
+    }
+}
+
+// hashes of every history below a directory
+fn collect_references(directory: &Path, referenced: &mut Vec<String>) {
+    if let Ok(entries) = fs::read_dir(directory) {
+        for entry in entries.filter_map(| entry | entry.ok()) {
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir()   => collect_references(&entry.path(), referenced),
+                Ok(_)                       => referenced.extend(read_history(&entry.path()).into_iter().map(| entry | entry.hash)),
+                Err(_)                      => {},
+            }
+        }
+    }
+}
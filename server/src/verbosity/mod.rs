@@ -0,0 +1,26 @@
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+// requests, parsed files and other details of what the server is doing
+pub const DETAILS: usize = 1;
+
+// every chunk of data exchanged with the boards
+pub const TRAFFIC: usize = 2;
+
+// raised once for every --verbose on the command line
+static LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+//
+pub fn set(level: usize) {
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+//
+pub fn enabled(level: usize) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level
+}
+
+// print a line only if the verbosity level is high enough
+#[macro_export]
+macro_rules! verbose {
+    ($level:expr, $($argument:tt)*)  => (if $crate::verbosity::enabled($level) { println!($($argument)*); });
+}
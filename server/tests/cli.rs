@@ -0,0 +1,177 @@
+extern crate bmq5;
+
+mod common;
+
+use bmq5::arguments::{ Arguments, Command, Usage };
+use bmq5::parser::Format;
+use common::{ Bench, libc };
+use std::time::Duration;
+use std::fs;
+
+// parse a command line given as a single string
+fn parse(line: &str) -> Result<Arguments, Usage> {
+    let arguments: Vec<String> = line.split_whitespace().map(String::from).collect();
+    Arguments::parse(&arguments)
+}
+
+#[test]
+fn arguments_are_parsed() {
+    let arguments = parse("-vv --client first run --channel=beta configuration -c second").ok().unwrap();
+    assert_eq!(arguments.command, Command::Run);
+    assert_eq!(arguments.configuration_path, "configuration");
+    assert_eq!(arguments.overrides.clients, vec!["first", "second"]);
+    assert_eq!(arguments.overrides.channel.map(| channel | channel.name()), Some("beta"));
+    assert_eq!(arguments.verbosity, 2);
+
+    //
+    let arguments = parse("configuration").ok().unwrap();
+    assert_eq!(arguments.command, Command::Run);
+    assert!(arguments.overrides.clients.is_empty());
+
+    //
+    let arguments = parse("send configuration board reboot now").ok().unwrap();
+    assert_eq!(arguments.command, Command::Send(String::from("board"), String::from("reboot now")));
+
+    //
+    let arguments = parse("fmt --to json -w keyboard.event").ok().unwrap();
+    assert_eq!(arguments.command, Command::Format(Some(Format::Json), true));
+    assert_eq!(arguments.configuration_path, "keyboard.event");
+
+    //
+    let arguments = parse("test configuration board boot.spec").ok().unwrap();
+    assert_eq!(arguments.command, Command::Test(String::from("board"), String::from("boot.spec")));
+    assert!(!arguments.overrides.controllers);
+}
+
+#[test]
+fn invalid_arguments_are_reported() {
+    assert_eq!(parse("").err(), Some(Usage::Error(String::from("no configuration file specified"))));
+    assert_eq!(parse("check").err(), Some(Usage::Error(String::from("no configuration file specified"))));
+    assert_eq!(parse("--bogus configuration").err(), Some(Usage::Error(String::from("unknown option '--bogus'"))));
+    assert_eq!(parse("configuration --client").err(), Some(Usage::Error(String::from("option '--client' requires a value"))));
+    assert_eq!(parse("configuration extra").err(), Some(Usage::Error(String::from("unexpected argument 'extra'"))));
+    assert_eq!(parse("test configuration board").err(), Some(Usage::Error(String::from("no test spec specified"))));
+    assert!(matches!(parse("--channel alpha configuration").err(), Some(Usage::Error(_))));
+    assert_eq!(parse("configuration --help").err(), Some(Usage::Help));
+    assert_eq!(parse("-V").err(), Some(Usage::Version));
+    assert_eq!(parse("check --write configuration").err(), Some(Usage::Error(String::from("options '--to' and '--write' are only valid for 'fmt'"))));
+    assert_eq!(parse("fmt --to yaml configuration").err(), Some(Usage::Error(String::from("invalid format 'yaml'. valid options are 'keywords', 'json' or 'toml'"))));
+}
+
+#[test]
+fn usage_errors_exit_cleanly() {
+    let bench = Bench::new("cli-usage");
+
+    //
+    let mut server = bench.server(&[]);
+    server.expect_error("no configuration file specified");
+    assert_eq!(server.wait(), Some(2));
+
+    //
+    let mut server = bench.server(&["missing.conf"]);
+    server.expect_error("configuration file 'missing.conf' not found");
+    assert_eq!(server.wait(), Some(2));
+
+    // broken configurations are reported on a single line, without a backtrace
+    let configuration = bench.configuration(&["board"], "");
+    for command in &["run", "check"] {
+        let mut server = bench.server(&[command, &configuration]);
+        assert_eq!(server.expect_error(""), "[ server ] [ board ] failed to find configuration path");
+        assert_eq!(server.wait(), Some(1));
+    }
+
+    //
+    let mut server = bench.server(&["--help"]);
+    server.expect("usage: bmq5-server");
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn check_leaves_devices_alone() {
+    let bench = Bench::new("cli-check");
+    let device = bench.path("device");
+    bench.file("board.client", &format!("?serial {}\n?use kernel\n", device));
+    bench.file("spare.client", &format!("?serial {}\n?use kernel\n", device));
+    let configuration = bench.configuration(&["board", "spare"], "");
+
+    //
+    let mut server = bench.server(&["check", "--client", "board", &configuration]);
+    server.expect("configuration is valid, 1 of 2 client/s enabled");
+    assert_eq!(server.wait(), Some(0));
+    assert!(fs::metadata(&device).is_err());
+}
+
+#[test]
+fn clients_are_listed_with_overrides() {
+    let bench = Bench::new("cli-list");
+    bench.file("board.client", "?serial /dev/ttyUSB0\n?use kernel\n?channel nightly\n");
+    bench.file("remote.client", "?ethernet 10.0.0.2\n");
+    let configuration = bench.configuration(&["board", "remote"], "");
+
+    //
+    let mut server = bench.server(&["list-clients", "--channel", "beta", &configuration]);
+    let board = server.expect("board");
+    assert!(board.contains("serial") && board.contains("beta") && board.contains("kernel.img.beta") && board.contains("/dev/ttyUSB0"));
+    let remote = server.expect("remote");
+    assert!(remote.contains("ethernet") && remote.contains("10.0.0.2"));
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn commands_are_sent_to_the_console() {
+    let bench = Bench::new("cli-send");
+    let device = bench.file("device", "");
+    bench.file("board.client", &format!("?serial {}\n?use kernel\n", device));
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let mut server = bench.server(&["send", &configuration, "board", "reboot"]);
+    server.expect("[ board ] sent 'reboot'");
+    assert_eq!(server.wait(), Some(0));
+    assert_eq!(fs::read_to_string(&device).unwrap(), "reboot\r");
+}
+
+#[test]
+fn only_selected_clients_are_started() {
+    let bench = Bench::new("cli-select");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("first", "?use kernel\n");
+    bench.pipe_client("second", "?use kernel\n");
+    let configuration = bench.configuration(&["first", "second"], "");
+
+    //
+    let mut server = bench.server(&["--client", "first", &configuration]);
+    let board = bench.simulator(&pipes);
+    board.expect("kernel started");
+    server.reject("[ second ]", Duration::from_millis(300));
+    server.signal(libc::SIGTERM);
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn files_are_formatted_in_place() {
+    let bench = Bench::new("cli-fmt");
+    let path = bench.file("board.client", "?serial   /dev/ttyUSB0\n?use kernel   # default binary\n");
+
+    //
+    let mut server = bench.server(&["fmt", "--write", &path]);
+    assert_eq!(server.wait(), Some(0));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "?serial /dev/ttyUSB0\n?use kernel # default binary\n");
+
+    //
+    let mut server = bench.server(&["fmt", "--to", "toml", &path]);
+    server.expect("\"?serial\" = \"/dev/ttyUSB0\"");
+    assert_eq!(server.wait(), Some(0));
+
+    // converting in place would leave a file that is read in the wrong format
+    let json = bench.file("board.json", "{ \"?serial\": \"/dev/ttyUSB0\" }\n");
+    let mut server = bench.server(&["fmt", "--to", "toml", "--write", &json]);
+    server.expect_error("can't write");
+    assert_eq!(server.wait(), Some(2));
+    assert_eq!(fs::read_to_string(&json).unwrap(), "{ \"?serial\": \"/dev/ttyUSB0\" }\n");
+
+    // the format of the file itself is fine
+    let mut server = bench.server(&["fmt", "--to", "json", "--write", &json]);
+    assert_eq!(server.wait(), Some(0));
+    assert_eq!(fs::read_to_string(&json).unwrap(), "{\n    \"?serial\": \"/dev/ttyUSB0\"\n}\n");
+}
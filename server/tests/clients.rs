@@ -0,0 +1,101 @@
+mod common;
+
+use common::{ Bench, libc };
+use std::time::Duration;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+// write a line to the control pipe of the server
+fn control(bench: &Bench, command: &str) {
+    let mut pipe = OpenOptions::new().write(true).open(bench.path("control")).unwrap();
+    pipe.write_all(format!("{}\n", command).as_bytes()).unwrap();
+}
+
+#[test]
+fn missing_devices_are_skipped() {
+    let bench = Bench::new("clients-missing");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("first", "?use kernel\n");
+    bench.file("second.client", &format!("?serial {}\n?use kernel\n", bench.path("missing")));
+    let configuration = bench.configuration(&["second", "first"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    server.expect("not found, client skipped");
+    board.expect("kernel started");
+}
+
+#[test]
+fn clients_are_selected_by_tag() {
+    let bench = Bench::new("clients-tags");
+    bench.file("kernel.img.stable", "kernel");
+    let first = bench.pipe_client("first", "?use kernel\n?tags lab arm\n");
+    bench.pipe_client("second", "?use kernel\n?tags office\n");
+    let third = bench.pipe_client("third", "?use kernel\n?tags arm\n?state disabled\n");
+    let configuration = bench.configuration(&["first", "second", "third"], "");
+
+    //
+    let _server = bench.server(&["--client", "arm", &configuration]);
+    let first = bench.simulator(&first);
+    let third = bench.simulator(&third);
+    first.expect("kernel started");
+    third.expect("kernel started");
+}
+
+#[test]
+fn clients_are_enabled_at_runtime() {
+    let bench = Bench::new("clients-runtime");
+    bench.file("kernel.img.stable", "kernel");
+    let first = bench.pipe_client("first", "?use kernel\n");
+    let second = bench.pipe_client("second", "?use kernel\n?state disabled\n");
+    let configuration = bench.configuration(&["first", "second"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let first = bench.simulator(&first);
+    first.expect("kernel started");
+    server.expect("[ control ] listening");
+    server.reject("[ second ] started", Duration::from_millis(300));
+
+    //
+    let mut second = bench.simulator(&second);
+    control(&bench, "enable second");
+    server.expect("[ second ] client enabled");
+    second.expect("kernel started");
+
+    //
+    control(&bench, "disable second");
+    server.expect("[ second ] client disabled");
+    second.command("reset");
+    second.reject("kernel started", Duration::from_millis(300));
+
+    //
+    control(&bench, "status");
+    server.expect("[ first ] enabled");
+    server.expect("[ second ] disabled");
+    control(&bench, "enable lab");
+    server.expect("no client, tag or group 'lab' found");
+}
+
+#[test]
+fn disabled_clients_can_be_enabled_again() {
+    let bench = Bench::new("clients-decline");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("kernel started");
+    control(&bench, "disable board");
+    server.expect("[ board ] client disabled");
+
+    //
+    control(&bench, "enable board");
+    server.expect("[ board ] client enabled");
+    board.command("reset");
+    board.expect("kernel started");
+    server.signal(libc::SIGTERM);
+}
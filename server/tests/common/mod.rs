@@ -0,0 +1,219 @@
+#![allow(dead_code, clippy::redundant_field_names)]
+
+pub extern crate libc;
+
+use std::sync::mpsc::{ channel, Receiver };
+use std::process::{ Child, ChildStdin, Command, Stdio };
+use std::io::{ BufRead, BufReader, Read, Write };
+use std::time::{ Duration, Instant };
+use std::path::PathBuf;
+use std::ffi::CString;
+use std::fs;
+use std::thread;
+
+//
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+// collect the lines a child process prints so they can be waited for
+fn collect_lines<R: Read + Send + 'static>(source: R) -> Receiver<String> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for line in BufReader::new(source).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// wait for a line containing the pattern, every line before it is discarded
+fn expect_line(lines: &Receiver<String>, pattern: &str, timeout: Duration) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        match lines.recv_timeout(remaining) {
+            Ok(line) if line.contains(pattern)  => return Some(line),
+            Ok(_)                               => continue,
+            Err(_)                              => return None,
+        }
+    }
+}
+
+// temporary configuration tree
+pub struct Bench {
+    pub directory:  PathBuf,
+}
+
+//
+impl Bench {
+
+    //
+    pub fn new(name: &str) -> Self {
+        let directory = std::env::temp_dir().join(format!("bmq5-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        Self {
+            directory:  directory,
+        }
+    }
+
+    // absolute path of a file inside the bench
+    pub fn path(&self, name: &str) -> String {
+        self.directory.join(name).to_string_lossy().into_owned()
+    }
+
+    //
+    pub fn file(&self, name: &str, content: &str) -> String {
+        self.binary(name, content.as_bytes())
+    }
+
+    //
+    pub fn binary(&self, name: &str, content: &[u8]) -> String {
+        let path = self.path(name);
+        if let Some(parent) = self.directory.join(name).parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    //
+    pub fn fifo(&self, name: &str) -> String {
+        let path = self.path(name);
+        let name = CString::new(path.clone()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(name.as_ptr(), 0o600) }, 0, "unable to create fifo '{}'", path);
+        path
+    }
+
+    // configuration with a kernel binary, a control pipe, the given clients and any other lines after them
+    pub fn configuration(&self, clients: &[&str], lines: &str) -> String {
+        let clients: String = clients.iter().map(| client | format!(":client {}\n", client)).collect();
+        self.file("configuration", &format!("?lookup {0}/\n?control {0}/control\n@binary {0}/\n:binary kernel.img kernel\n{1}{2}", self.directory.display(), clients, lines))
+    }
+
+    // create a client that talks to the server through a pair of named pipes
+    pub fn pipe_client(&self, name: &str, settings: &str) -> (String, String) {
+        let input = self.fifo(&format!("{}.input", name));
+        let output = self.fifo(&format!("{}.output", name));
+        self.pipe_settings(name, settings);
+        (input, output)
+    }
+
+    // change the settings of a client created with pipe_client
+    pub fn pipe_settings(&self, name: &str, settings: &str) {
+        let input = self.path(&format!("{}.input", name));
+        let output = self.path(&format!("{}.output", name));
+        self.file(&format!("{}.client", name), &format!("?pipe {} {}\n{}", input, output, settings));
+    }
+
+    //
+    pub fn server(&self, arguments: &[&str]) -> Process {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bmq5-server"))
+            .args(arguments)
+            .current_dir(&self.directory)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        Process {
+            lines:  collect_lines(child.stdout.take().unwrap()),
+            errors: collect_lines(child.stderr.take().unwrap()),
+            stdin:  None,
+            child:  child,
+        }
+    }
+
+    // simulate a board on the pipes of a client
+    pub fn simulator(&self, pipes: &(String, String)) -> Process {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bmq5-simulator"))
+            .args(["pipe", &pipes.1, &pipes.0])
+            .current_dir(&self.directory)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        Process {
+            lines:  collect_lines(child.stdout.take().unwrap()),
+            errors: channel().1,
+            stdin:  child.stdin.take(),
+            child:  child,
+        }
+    }
+}
+
+//
+impl Drop for Bench {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+// output and input of a running server or simulator, the process is killed when it's dropped
+pub struct Process {
+    lines:      Receiver<String>,
+    errors:     Receiver<String>,
+    stdin:      Option<ChildStdin>,
+    child:      Child,
+}
+
+//
+impl Process {
+
+    // wait for a line on stdout that contains the pattern
+    pub fn expect(&self, pattern: &str) -> String {
+        match expect_line(&self.lines, pattern, TIMEOUT) {
+            Some(line)  => line,
+            None        => panic!("timed out waiting for '{}'", pattern),
+        }
+    }
+
+    // make sure no line containing the pattern shows up for a while
+    pub fn reject(&self, pattern: &str, timeout: Duration) {
+        if let Some(line) = expect_line(&self.lines, pattern, timeout) {
+            panic!("unexpected line '{}'", line);
+        }
+    }
+
+    // wait for a line on stderr that contains the pattern
+    pub fn expect_error(&self, pattern: &str) -> String {
+        match expect_line(&self.errors, pattern, TIMEOUT) {
+            Some(line)  => line,
+            None        => panic!("timed out waiting for error '{}'", pattern),
+        }
+    }
+
+    // wait for the process to exit and get its exit code
+    pub fn wait(&mut self) -> Option<i32> {
+        let deadline = Instant::now() + TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return status.code();
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        None
+    }
+
+    // deliver a signal to the process
+    pub fn signal(&self, signal: i32) {
+        unsafe { libc::kill(self.child.id() as i32, signal) };
+    }
+
+    // send a command to a simulator
+    pub fn command(&mut self, command: &str) {
+        let stdin = self.stdin.as_mut().expect("process has no input");
+        stdin.write_all(format!("{}\n", command).as_bytes()).unwrap();
+        stdin.flush().unwrap();
+    }
+}
+
+//
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,230 @@
+#[macro_use]
+extern crate bmq5;
+
+mod common;
+
+use bmq5::parser::{ Parser, Item, Word, Value, host_name, tokenize, parse_json, parse_toml };
+use common::Bench;
+
+// every ':entry' line of a file with its remaining words
+fn entries(path: &str) -> Vec<String> {
+    let entries: Item<Vec<String>> = item!(Vec::new());
+    {
+        let mut parser = Parser::new(path);
+        parser.register("entry", false, Box::new(| stack, _ | entries.borrow_mut().push(stack.pop_line())));
+        parser.parse();
+    }
+    unwrap_item!(entries)
+}
+
+#[test]
+fn files_are_included() {
+    let bench = Bench::new("configuration-include");
+    bench.file("shared/bench.conf", ":entry shared\ninclude? missing.conf\ninclude nested.conf\n");
+    bench.file("shared/nested.conf", ":entry nested\n");
+    let path = bench.file("developer.conf", ":entry first\ninclude shared/bench.conf\ninclude shared/nested.conf\n:entry last\n");
+    assert_eq!(entries(&path), vec!["first", "shared", "nested", "nested", "last"]);
+}
+
+#[test]
+#[should_panic(expected = "include cycle")]
+fn include_cycles_are_rejected() {
+    let bench = Bench::new("configuration-cycle");
+    bench.file("second.conf", "include first.conf\n");
+    let path = bench.file("first.conf", ":entry first\ninclude second.conf\n");
+    entries(&path);
+}
+
+#[test]
+fn variables_are_interpolated() {
+    let bench = Bench::new("configuration-variables");
+    std::env::set_var("BMQ5_TEST_DEVICE", "/dev/ttyUSB3");
+    bench.file("shared.conf", "default BOARD shared\ndefault BMQ5_TEST_DEVICE /dev/ttyUSB0\n:entry ${BOARD} ${BMQ5_TEST_DEVICE}\n");
+    let path = bench.file("developer.conf", "set BOARD mine\ninclude shared.conf\nset BOARD other board\n:entry ${BOARD} costs $5 $${BOARD}\n:entry ${DIRECTORY}\n");
+    assert_eq!(entries(&path), vec![
+        String::from("mine /dev/ttyUSB3"),
+        String::from("other board costs $5 ${BOARD}"),
+        bench.directory.canonicalize().unwrap().display().to_string(),
+    ]);
+}
+
+#[test]
+fn conditionals_match_the_host() {
+    let bench = Bench::new("configuration-host");
+    let host = host_name();
+    let path = bench.file("bench.conf", &format!("if host {0}\n:entry here\nif host elsewhere\n:entry nested\nelse\n:entry ${{HOST}}\nend\nelse\n:entry there\nend\nif host elsewhere\nset MISSING ${{UNDEFINED}}\nend\n", host));
+    assert_eq!(entries(&path), vec![String::from("here"), host]);
+}
+
+#[test]
+fn words_are_tokenized() {
+    let lookup = | name: &str | match name {
+        "NAME"  => Ok(String::from("two words")),
+        name    => Err(format!("undefined variable '{}'", name)),
+    };
+    let texts = | line: &str | tokenize(line, lookup).map(| words | words.into_iter().map(| word | word.text).collect::<Vec<String>>());
+
+    //
+    assert_eq!(texts("  plain   words\\ \\$ "), Ok(vec![String::from("plain"), String::from("words\\"), String::from("\\$")]));
+    assert_eq!(texts("\"quoted path\" 'single \\n' mixed\"-\"word"), Ok(vec![String::from("quoted path"), String::from("single \\n"), String::from("mixed-word")]));
+    assert_eq!(texts("\"\\n\\t\\x1b\\u{e4}\\\"\""), Ok(vec![String::from("\n\t\x1b\u{e4}\"")]));
+    assert_eq!(texts("value # comment \"unterminated"), Ok(vec![String::from("value")]));
+    assert_eq!(texts("${NAME} '${NAME}' \"${NAME}\" $${NAME}"), Ok(vec![String::from("two words"), String::from("${NAME}"), String::from("two words"), String::from("${NAME}")]));
+    assert_eq!(texts("\"open"), Err(String::from("unterminated quote")));
+    assert_eq!(texts("b' b\" b'c'"), Ok(vec![String::from("b'"), String::from("b\""), String::from("bc")]));
+    assert_eq!(texts("\"\\q\""), Err(String::from("invalid escape sequence '\\q'")));
+    assert_eq!(texts("${MISSING}"), Err(String::from("undefined variable 'MISSING'")));
+    assert_eq!(tokenize("'a' b", lookup).unwrap()[0], Word { text: String::from("a"), quoted: true, leading: String::new() });
+    assert_eq!(tokenize("a  \t'b'", lookup).unwrap()[1].leading, String::from("  \t"));
+}
+
+#[test]
+fn lines_are_continued_and_quoted() {
+    let bench = Bench::new("configuration-tokens");
+    let path = bench.file("bench.conf", "  # indented comment\n:entry \"/srv/boards/lab one\" \\\n    second # trailing\n:entry \" \" \"\\x1b\" 65\n");
+
+    //
+    let words: Item<Vec<String>> = item!(Vec::new());
+    let characters: Item<Vec<u8>> = item!(Vec::new());
+    {
+        let mut parser = Parser::new(&path);
+        parser.register("entry", false, Box::new(| stack, _ | {
+            stack.push_debug("no path specified");
+            let first = stack.pop();
+            match first.as_ref() {
+                "/srv/boards/lab one"   => words.borrow_mut().extend(vec![first.clone(), stack.pop_line()]),
+                _                       => {
+                    characters.borrow_mut().push(first.as_bytes()[0]);
+                    characters.borrow_mut().push(stack.pop_ascii());
+                    characters.borrow_mut().push(stack.pop_ascii());
+                },
+            }
+        }));
+        parser.parse();
+    }
+
+    //
+    assert_eq!(unwrap_item!(words), vec!["/srv/boards/lab one", "second"]);
+    assert_eq!(unwrap_item!(characters), vec![b' ', 0x1b, 65]);
+}
+
+#[test]
+fn legacy_characters_are_parsed() {
+    let bench = Bench::new("configuration-legacy");
+    let path = bench.file("bench.conf", ":entry b' b\" bxy sp s space 65\n");
+
+    //
+    let characters: Item<Vec<u8>> = item!(Vec::new());
+    {
+        let mut parser = Parser::new(&path);
+        parser.register("entry", false, Box::new(| stack, _ | {
+            for _ in 0..7 {
+                characters.borrow_mut().push(stack.pop_ascii());
+            }
+        }));
+        parser.parse();
+    }
+
+    //
+    assert_eq!(unwrap_item!(characters), vec![b'\'', b'"', b'x', b' ', b' ', b' ', 65]);
+}
+
+#[test]
+fn legacy_backslash_is_not_continued() {
+    let bench = Bench::new("configuration-backslash");
+    let path = bench.file("bench.conf", ":entry b\\\n:entry \\\n  bz\n");
+
+    //
+    let characters: Item<Vec<u8>> = item!(Vec::new());
+    {
+        let mut parser = Parser::new(&path);
+        parser.register("entry", false, Box::new(| stack, _ | {
+            characters.borrow_mut().push(stack.pop_ascii());
+        }));
+        parser.parse();
+    }
+
+    // the second line is still continued
+    assert_eq!(unwrap_item!(characters), vec![b'\\', b'z']);
+}
+
+#[test]
+fn structured_characters_are_named() {
+    let bench = Bench::new("configuration-named");
+    let path = bench.file("bench.json", "{ \"entry\": [[\"escape\", \"x\", \"s\", \"b'\", 65]] }\n");
+
+    //
+    let characters: Item<Vec<u8>> = item!(Vec::new());
+    {
+        let mut parser = Parser::new(&path);
+        parser.register("entry", false, Box::new(| stack, _ | {
+            for _ in 0..5 {
+                characters.borrow_mut().push(stack.pop_ascii());
+            }
+        }));
+        parser.parse();
+    }
+
+    // a single character is taken literally, longer strings are read like bare words
+    assert_eq!(unwrap_item!(characters), vec![0x1b, b'x', b's', b'\'', 65]);
+}
+
+#[test]
+fn documents_are_parsed() {
+    let json = parse_json("{\n  \"name\": \"a\\u00e4\",\n  \"list\": [1, -2.5, true, null, [\"x\"]],\n  \"table\": {}\n}\n").unwrap();
+    assert_eq!(json, vec![
+        (String::from("name"), Value::Text(String::from("a\u{e4}")), 2),
+        (String::from("list"), Value::Array(vec![Value::Number(String::from("1")), Value::Number(String::from("-2.5")), Value::Boolean(true), Value::Null, Value::Array(vec![Value::Text(String::from("x"))])]), 3),
+        (String::from("table"), Value::Table(Vec::new()), 4),
+    ]);
+
+    //
+    let toml = parse_toml("# comment\nname = 'a\\b' # trailing\n\"?list\" = [\n  1_000, # first\n  \"x\",\n]\ninline = { key = false }\n").unwrap();
+    assert_eq!(toml, vec![
+        (String::from("name"), Value::Text(String::from("a\\b")), 2),
+        (String::from("?list"), Value::Array(vec![Value::Number(String::from("1000")), Value::Text(String::from("x"))]), 3),
+        (String::from("inline"), Value::Table(vec![(String::from("key"), Value::Boolean(false))]), 7),
+    ]);
+
+    //
+    assert_eq!(parse_json("{\n\"open\": [1,\n"), Err((3, String::from("unexpected end of the file"))));
+    assert_eq!(parse_toml("[clients]\n"), Err((1, String::from("tables are not supported in this subset of toml, use top level keys"))));
+    assert_eq!(parse_toml("key = 1\nkey = 2\n"), Err((2, String::from("key 'key' is already set, keys can only be set once in toml"))));
+    assert_eq!(parse_toml("key = '''\ntext'''\n"), Err((1, String::from("multi-line strings are not supported in this subset of toml"))));
+    assert_eq!(parse_toml("table.key = 1\n"), Err((1, String::from("dotted keys are not supported in this subset of toml, use top level keys"))));
+    assert_eq!(parse_toml("key = 1 2\n"), Err((1, String::from("unexpected '2' after the value"))));
+}
+
+#[test]
+fn structured_configurations_match_keywords() {
+    use bmq5::configuration::{ Configuration, Overrides };
+
+    //
+    let bench = Bench::new("configuration-structured");
+    let directory = bench.directory.display().to_string();
+    bench.file("board.client", "# board in toml\nserial = \"/dev/ttyUSB0\"\nuse = \"kernel\"\ntags = [\"lab\", \"fast\"]\n");
+    bench.file("spare.client", "{ \"ethernet\": \"10.0.0.2\", \"state\": \"disabled\" }\n");
+    let keywords = bench.file("bench.conf", &format!("?lookup {0}/\n@binary {0}/\n:binary kernel.img kernel\n:client board desk\n:client spare\n:channel lab beta\n", directory));
+    let json = bench.file("bench.json", &format!("{{\n  \"lookup\": \"{0}/\",\n  \"@binary\": \"{0}/\",\n  \"binary\": [[\"kernel.img\", \"kernel\"]],\n  \"client\": [[\"board\", \"desk\"], \"spare\"],\n  \"channel\": [[\"lab\", \"beta\"]]\n}}\n", directory));
+    let toml = bench.file("bench.toml", &format!("lookup = \"{0}/\"\n\"@binary\" = \"{0}/\"\nbinary = [[\"kernel.img\", \"kernel\"]]\nclient = [\n    [\"board\", \"desk\"],\n    \"spare\",\n]\nchannel = [[\"lab\", \"beta\"]]\n", directory));
+
+    //
+    let summary = | path: &str | -> Vec<String> {
+        let configuration = Configuration::new(path, &Overrides::new());
+        configuration.client_manager.settings().iter().map(| (context, _) | {
+            let binary = context.binary.as_ref().map(| binary | binary.path.clone()).unwrap_or_default();
+            format!("{} {} {} {} {}", context.name, context.tags.join(","), context.channel.name(), binary, context.enabled)
+        }).collect()
+    };
+    let expected = vec![
+        format!("board desk,lab,fast beta {}/kernel.img.beta true", directory),
+        String::from("spare  stable  false"),
+    ];
+    assert_eq!(summary(&keywords), expected);
+    assert_eq!(summary(&json), expected);
+    assert_eq!(summary(&toml), expected);
+
+    // calls listed in order, so a keyword can be called again after another one
+    let calls = bench.file("calls.toml", &format!("calls = [\n    {{ lookup = \"{0}/\" }},\n    {{ \"@binary\" = \"{0}/\" }},\n    {{ \":binary\" = [[\"kernel.img\", \"kernel\"]] }},\n    {{ \":client\" = [[\"board\", \"desk\"]] }},\n    {{ \":channel\" = [[\"lab\", \"beta\"]] }},\n    {{ \":client\" = [\"spare\"] }},\n]\n", directory));
+    assert_eq!(summary(&calls), expected);
+}
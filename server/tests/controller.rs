@@ -0,0 +1,344 @@
+#![cfg(feature = "controller")]
+
+extern crate bmq5;
+
+mod common;
+
+use bmq5::controller::evdev::{ encode, EV_KEY, EV_SYN, EV_MSC };
+use common::Bench;
+use std::io::Write;
+use std::fs::{ File, OpenOptions };
+use std::time::Duration;
+use std::thread;
+
+// keyboard layout with a shift layer, a caps lock toggle and keys to switch between the boards
+const KEYBOARD_EVENT: &str = "
+:mode base
+:press 30 97
+:press * 98
+:set 42 0
+:toggle 58 1
+:target 59 second
+:target 60 first
+:target 61 both
+:mode 10000000
+:press 30 65
+:press * 66
+:mode 01000000
+:press 30 88
+";
+
+// create two boards and a keyboard controller reading fixed width records from a fifo
+fn bench(name: &str) -> (Bench, String, (String, String), (String, String), String) {
+    let bench = Bench::new(name);
+    bench.file("kernel.img.stable", "kernel");
+    let first = bench.pipe_client("first", "?use kernel\n");
+    let second = bench.pipe_client("second", "?use kernel\n");
+    let source = bench.fifo("keyboard.source");
+    bench.file("keyboard.event", KEYBOARD_EVENT);
+    bench.file("keyboard.controller", &format!(concat!(
+        "?source {}\n?width 4\n?target first\n",
+        ":byte event 0 ;\n:byte id 1 ;\n:byte value 2 ;\n",
+        ":value up 0\n:value down 1\n:value repeat 2\n",
+        ":event keyboard 1\n"), source));
+    let configuration = bench.file("configuration", &format!(
+        "?lookup {0}/\n@binary {0}/\n:binary kernel.img kernel\n:client first\n:client second\n:group both first second\n@controller {0}/\n:controller keyboard.controller\n",
+        bench.directory.display()));
+    (bench, configuration, first, second, source)
+}
+
+//
+fn key(source: &mut File, identifier: u8, value: u8) {
+    source.write_all(&[1, identifier, value, 0]).unwrap();
+    source.flush().unwrap();
+}
+
+#[test]
+fn keys_are_translated() {
+    let (bench, configuration, first, second, source) = bench("controller-keys");
+    let server = bench.server(&[&configuration]);
+    // the clients have to be started before the controller opens its source
+    let board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    board.expect("kernel started");
+    server.expect("[ first ] transmitted");
+
+    // press and release
+    key(&mut source, 30, 1);
+    board.expect("event 00000000 0x61");
+    key(&mut source, 30, 0);
+
+    // counters continue from the previous index
+    key(&mut source, 31, 1);
+    board.expect("event 00000000 0x62");
+
+    // repeats are sent as well
+    key(&mut source, 30, 2);
+    board.expect("event 00000000 0x61");
+}
+
+#[test]
+fn modifiers_select_modes() {
+    let (bench, configuration, first, second, source) = bench("controller-modifiers");
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    board.expect("kernel started");
+    server.expect("[ first ] transmitted");
+
+    // shift is held while the key is pressed
+    key(&mut source, 42, 1);
+    key(&mut source, 30, 1);
+    board.expect("event 00000001 0x41");
+    key(&mut source, 42, 0);
+    key(&mut source, 30, 1);
+    board.expect("event 00000000 0x61");
+
+    // caps lock toggles a mode until it's pressed again
+    key(&mut source, 58, 1);
+    key(&mut source, 58, 0);
+    key(&mut source, 30, 1);
+    board.expect("event 00000010 0x58");
+}
+
+#[test]
+fn target_switches_client() {
+    let (bench, configuration, first, second, source) = bench("controller-target");
+    let server = bench.server(&[&configuration]);
+    let first = bench.simulator(&first);
+    let second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    first.expect("kernel started");
+    second.expect("kernel started");
+
+    // events are dropped while a kernel is being transmitted
+    for _ in 0..2 {
+        server.expect("] transmitted");
+    }
+
+    //
+    key(&mut source, 59, 1);
+    key(&mut source, 30, 1);
+    second.expect("event 00000000 0x61");
+
+    //
+    key(&mut source, 60, 1);
+    key(&mut source, 31, 1);
+    first.expect("event 00000000 0x62");
+}
+
+#[test]
+fn target_group_reaches_every_client() {
+    let (bench, configuration, first, second, source) = bench("controller-group");
+    let server = bench.server(&[&configuration]);
+    let first = bench.simulator(&first);
+    let second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    for _ in 0..2 {
+        server.expect("] transmitted");
+    }
+
+    //
+    key(&mut source, 61, 1);
+    key(&mut source, 30, 1);
+    first.expect("event 00000000 0x61");
+    second.expect("event 00000000 0x61");
+}
+
+#[test]
+fn input_devices_are_decoded() {
+    let (bench, configuration, first, second, _) = bench("controller-evdev");
+    let device = bench.fifo("keyboard.device");
+    bench.file("keyboard.controller", &format!("?evdev {}\n?grab enabled\n?target first\n:event keyboard EV_KEY\n", device));
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut device = OpenOptions::new().write(true).open(device).unwrap();
+    server.expect("unable to grab");
+    server.expect("[ first ] transmitted");
+
+    // the layout of struct input_event is known, so a report with its synchronization is enough
+    let mut report = Vec::new();
+    report.extend(encode(EV_MSC, 4, 0x70004));
+    report.extend(encode(EV_KEY, 42, 1));
+    report.extend(encode(EV_KEY, 30, 1));
+    report.extend(encode(EV_SYN, 0, 0));
+    report.extend(encode(EV_KEY, 30, 2));
+    device.write_all(&report).unwrap();
+    board.expect("event 00000001 0x41");
+    board.expect("event 00000001 0x41");
+}
+
+#[test]
+fn layouts_need_no_event_file() {
+    let (bench, configuration, first, second, _) = bench("controller-layout");
+    let device = bench.fifo("keyboard.device");
+    bench.file("keyboard.controller", &format!("default LAYOUT uk\n?evdev {}\n?layout ${{LAYOUT}}\n?target first\n", device));
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut device = OpenOptions::new().write(true).open(device).unwrap();
+    server.expect("[ first ] transmitted");
+
+    //
+    let mut report = Vec::new();
+    report.extend(encode(EV_KEY, 54, 1));
+    report.extend(encode(EV_KEY, 4, 1));
+    report.extend(encode(EV_KEY, 54, 0));
+    report.extend(encode(EV_KEY, 28, 1));
+    device.write_all(&report).unwrap();
+    board.expect("event 00000001 0xa3");
+    board.expect("event 00000000 0x0d");
+}
+
+#[test]
+fn boards_can_take_key_events() {
+    let (bench, configuration, first, second, source) = bench("controller-key-events");
+    bench.pipe_settings("first", "?use kernel\n?events keys\n");
+    bench.file("keyboard.event", &format!("{}?release enabled\n:mode base\n:key KEY_UP\n", KEYBOARD_EVENT));
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    board.command("events keys");
+    board.expect("event protocol 'keys'");
+    server.expect("[ first ] transmitted");
+
+    // keys without a character and releases reach the board as well
+    key(&mut source, 103, 1);
+    board.expect("key 103 down 00000000 0x00");
+    key(&mut source, 42, 1);
+    key(&mut source, 30, 2);
+    board.expect("key 30 repeat 00000001 0x41");
+    key(&mut source, 30, 0);
+    board.expect("key 30 up 00000001 0x41");
+}
+
+#[test]
+fn keys_send_sequences() {
+    let (bench, configuration, first, second, source) = bench("controller-sequences");
+    bench.file("keyboard.event", &format!("{}:mode base\n:send 103 \"\\e[A\"\n:send KEY_F5 \"load\\r\"\n:send KEY_F6 \"ä\"\n", KEYBOARD_EVENT));
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    server.expect("[ first ] transmitted");
+
+    // every byte is sent like a typed character
+    key(&mut source, 103, 1);
+    board.expect("event 00000000 0x1b");
+    board.expect("event 00000000 0x5b");
+    board.expect("event 00000000 0x41");
+    key(&mut source, 63, 1);
+    for character in b"load\r" {
+        board.expect(&format!("event 00000000 0x{:02x}", character));
+    }
+    key(&mut source, 64, 1);
+    board.expect("event 00000000 0xc3");
+    board.expect("event 00000000 0xa4");
+}
+
+#[test]
+fn held_keys_are_repeated_by_the_server() {
+    let (bench, configuration, first, second, source) = bench("controller-typematic");
+    bench.file("keyboard.event", &format!("?repeat host 200 20\n{}", KEYBOARD_EVENT));
+    bench.file("buttons.event", "?repeat none\n:mode base\n:press 31 99\n");
+    let controller = std::fs::read_to_string(bench.path("keyboard.controller")).unwrap();
+    bench.file("keyboard.controller", &format!("{}:event buttons 2\n", controller));
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    server.expect("[ first ] transmitted");
+
+    // repeats of the device are dropped in favour of the ones of the server
+    key(&mut source, 31, 1);
+    key(&mut source, 31, 2);
+    board.expect("event 00000000 0x62");
+    for _ in 0..3 {
+        board.expect("event 00000000 0x62");
+    }
+
+    // releasing the key stops the repeats, the release itself isn't forwarded
+    key(&mut source, 31, 0);
+    board.reject("event", Duration::from_millis(400));
+    key(&mut source, 30, 1);
+    board.expect("event 00000000 0x61");
+
+    // pressing a key the server doesn't repeat stops the repeats as well
+    key(&mut source, 31, 1);
+    board.expect("event 00000000 0x62");
+    board.expect("event 00000000 0x62");
+    source.write_all(&[2, 31, 1, 0]).unwrap();
+    board.expect("event 00000000 0x63");
+    board.reject("event", Duration::from_millis(400));
+}
+
+#[test]
+fn controllers_share_state_in_groups() {
+    let (bench, configuration, first, second, source) = bench("controller-share");
+    let pedal = bench.fifo("pedal.source");
+    bench.file("pedal.event", ":mode base\n:set 1 0\n:target 2 second\n");
+    bench.file("pedal.controller", &format!("?source {}\n?width 4\n?target first\n:byte event 0 ;\n:byte id 1 ;\n:byte value 2 ;\n:value up 0\n:value down 1\n:event pedal 1\n", pedal));
+    let shared = format!("{}:controller pedal.controller foot\n:share desk keyboard foot\n", std::fs::read_to_string(&configuration).unwrap());
+    bench.file("configuration", &shared);
+    let server = bench.server(&[&configuration]);
+    let first = bench.simulator(&first);
+    let second = bench.simulator(&second);
+    let mut source = OpenOptions::new().write(true).open(source).unwrap();
+    let mut pedal = OpenOptions::new().write(true).open(pedal).unwrap();
+    for _ in 0..2 {
+        server.expect("] transmitted");
+    }
+
+    // the pedal holds shift for the keyboard, the server reads both sources on their own so it gets some time
+    key(&mut pedal, 1, 1);
+    thread::sleep(Duration::from_millis(200));
+    key(&mut source, 30, 1);
+    first.expect("event 00000001 0x41");
+    key(&mut pedal, 1, 0);
+    thread::sleep(Duration::from_millis(200));
+    key(&mut source, 30, 1);
+    first.expect("event 00000000 0x61");
+
+    // and switches its target
+    key(&mut pedal, 2, 1);
+    thread::sleep(Duration::from_millis(200));
+    key(&mut source, 30, 1);
+    second.expect("event 00000000 0x61");
+}
+
+#[test]
+fn gamepads_send_buttons_and_axes() {
+    use bmq5::controller::evdev::EV_ABS;
+
+    //
+    let (bench, configuration, first, second, _) = bench("controller-gamepad");
+    bench.pipe_settings("first", "?use kernel\n?events keys\n");
+    let device = bench.fifo("gamepad.device");
+    bench.file("buttons.event", ":mode base\n:key BTN_SOUTH\n");
+    bench.file("axes.event", "?release enabled\n:mode base\n:key KEY_LEFT\n:key KEY_RIGHT\n:hat ABS_HAT0X KEY_LEFT KEY_RIGHT\n:axis ABS_X 0 255\n:deadzone ABS_X 8\n:analog ABS_X\n");
+    bench.file("keyboard.controller", &format!("?evdev {}\n?target first\n:event buttons EV_KEY\n:event axes EV_ABS\n", device));
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&first);
+    let _second = bench.simulator(&second);
+    let mut device = OpenOptions::new().write(true).open(device).unwrap();
+    board.command("events keys");
+    board.expect("event protocol 'keys'");
+    server.expect("[ first ] transmitted");
+
+    // buttons are keys, hat switches press keys and sticks send their position
+    let mut report = Vec::new();
+    report.extend(encode(EV_KEY, 0x130, 1));
+    report.extend(encode(EV_ABS, 16, -1));
+    report.extend(encode(EV_ABS, 16, 0));
+    report.extend(encode(EV_ABS, 0, 255));
+    report.extend(encode(EV_SYN, 0, 0));
+    device.write_all(&report).unwrap();
+    board.expect("key 304 down 00000000 0x00");
+    board.expect("key 105 down 00000000 0x00");
+    board.expect("key 105 up 00000000 0x00");
+    board.expect("axis 0 32767");
+}
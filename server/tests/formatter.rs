@@ -0,0 +1,138 @@
+#[macro_use]
+extern crate bmq5;
+
+mod common;
+
+use bmq5::formatter::format;
+use bmq5::parser::{ Parser, Item, Format };
+use common::Bench;
+
+#[test]
+fn keyword_files_are_normalized() {
+    let bench = Bench::new("formatter-keywords");
+    let path = bench.file("keyboard.event", "# keymap\n:mode base\n\n\n:press 31 bA   # letter\n:press 30 97\n:press * s\n:press 1000 98\n:set * 0\n  if host bench\n:client board ${LAB} 'two words'\nend\n\n");
+    assert_eq!(format(&path, None), Ok(String::from("\
+# keymap
+:mode base
+
+:press 30   97
+:press 31   \"A\" # letter
+:press 31   \" \"
+:press 1000 98
+:set 1001 0
+if host bench
+:client board ${LAB} \"two words\"
+end
+")));
+}
+
+#[test]
+fn formats_are_converted() {
+    let bench = Bench::new("formatter-convert");
+    let path = bench.file("bench.conf", "?lookup /srv/boards/\n@binary /srv/binaries/\n:binary kernel.img kernel\n:client board lab\n:client spare\n:group lab board\n?watch\n");
+
+    //
+    let json = format(&path, Some(Format::Json)).unwrap();
+    assert_eq!(json, "\
+{
+    \"?lookup\": \"/srv/boards/\",
+    \"@binary\": \"/srv/binaries/\",
+    \":binary\": [
+        [\"kernel.img\", \"kernel\"]
+    ],
+    \":client\": [
+        [\"board\", \"lab\"],
+        \"spare\"
+    ],
+    \":group\": [
+        [\"lab\", \"board\"]
+    ],
+    \"?watch\": null
+}
+");
+
+    // converting back gives the normalized keyword file
+    let keywords = format(&path, None).unwrap();
+    let json_path = bench.file("bench.json", &json);
+    assert_eq!(format(&json_path, Some(Format::Keywords)), Ok(keywords.clone()));
+    let toml_path = bench.file("bench.toml", &format(&json_path, Some(Format::Toml)).unwrap());
+    assert_eq!(format(&toml_path, Some(Format::Keywords)), Ok(keywords));
+}
+
+#[test]
+fn repeated_keywords_keep_their_order() {
+    let bench = Bench::new("formatter-repeated");
+    let path = bench.file("boot.script", ":send\n:expect 5000 ready\n:send load\n:expect 5000 ready\n");
+
+    //
+    let json = format(&path, Some(Format::Json)).unwrap();
+    assert_eq!(json, "\
+{
+    \"calls\": [
+        {\":send\": [[]]},
+        {\":expect\": [[5000, \"ready\"]]},
+        {\":send\": [\"load\"]},
+        {\":expect\": [[5000, \"ready\"]]}
+    ]
+}
+");
+    let toml = format(&path, Some(Format::Toml)).unwrap();
+    assert_eq!(toml, "\
+calls = [
+    { \":send\" = [[]] },
+    { \":expect\" = [[5000, \"ready\"]] },
+    { \":send\" = [\"load\"] },
+    { \":expect\" = [[5000, \"ready\"]] },
+]
+");
+
+    // both convert back to the same calls in the same order
+    let keywords = format(&path, None).unwrap();
+    assert_eq!(format(&bench.file("boot.json", &json), Some(Format::Keywords)), Ok(keywords.clone()));
+    assert_eq!(format(&bench.file("boot.toml", &toml), Some(Format::Keywords)), Ok(keywords));
+}
+
+// the characters the parser reads from the press entries of a keymap
+fn pressed(path: &str) -> Vec<(String, u8)> {
+    let presses: Item<Vec<(String, u8)>> = item!(Vec::new());
+    {
+        let mut parser = Parser::new(path);
+        parser.register("press", false, Box::new(| stack, _ | {
+            stack.push_debug("no index specified");
+            let index = stack.pop();
+            presses.borrow_mut().push((index, stack.pop_ascii()));
+        }));
+        parser.parse();
+    }
+    unwrap_item!(presses)
+}
+
+#[test]
+fn converted_keymaps_are_loaded() {
+    let bench = Bench::new("formatter-keymap");
+    let path = bench.file("keyboard.event", ":press 30 escape\n:press 31 s\n:press 32 bA\n:press 33 97\n:press 34 'x'\n:press 35 space\n:press 36 b'\n");
+    let expected = pressed(&path);
+    assert_eq!(expected.iter().map(| (_, character) | *character).collect::<Vec<u8>>(), vec![0x1b, b' ', b'A', 97, b'x', b' ', b'\'']);
+
+    //
+    for (name, target) in &[("keyboard.json", Format::Json), ("keyboard.toml", Format::Toml), ("normalized.event", Format::Keywords)] {
+        let converted = bench.file(name, &format(&path, Some(*target)).unwrap());
+        assert_eq!(pressed(&converted), expected, "{}", name);
+    }
+}
+
+#[test]
+fn unconvertible_files_are_reported() {
+    let bench = Bench::new("formatter-errors");
+    let path = bench.file("bench.conf", "include shared.conf\n?lookup /srv/\n");
+    assert_eq!(format(&path, Some(Format::Toml)), Err(String::from("directives can only be used in keyword files")));
+
+    //
+    let path = bench.file("variables.conf", "?lookup ${ROOT}/\n");
+    assert_eq!(format(&path, Some(Format::Json)), Err(String::from("variables can only be used in keyword files")));
+
+    //
+    let path = bench.file("bare.json", "{ \"client\": [\"first\", \"second\"] }\n");
+    assert_eq!(format(&path, Some(Format::Keywords)), Err(String::from("can't tell if 'client' is called once or for every item, write it as '?client' or ':client'")));
+    assert_eq!(format(&path, None), Ok(String::from("{\n    \"client\": [\"first\", \"second\"]\n}\n")));
+}
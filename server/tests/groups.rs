@@ -0,0 +1,90 @@
+mod common;
+
+use common::Bench;
+use std::fs;
+
+#[test]
+fn groups_assign_channels_and_binaries() {
+    let bench = Bench::new("groups-assign");
+    bench.file("first.client", "?serial /dev/ttyUSB0\n?use kernel\n?tags rack1\n");
+    bench.file("second.client", "?serial /dev/ttyUSB1\n?use kernel\n");
+    bench.file("third.client", "?serial /dev/ttyUSB2\n?use kernel\n?channel beta\n");
+    let configuration = bench.configuration(&[], ":binary other.img other\n:client first pi4\n:client second pi4\n:client third\n:group lab rack1 third\n:channel pi4 nightly\n:use lab other\n");
+
+    //
+    let mut server = bench.server(&["list-clients", &configuration]);
+    let first = server.expect("first");
+    assert!(first.contains("other.img.nightly") && first.contains("pi4,rack1"), "{}", first);
+    let second = server.expect("second");
+    assert!(second.contains("kernel.img.nightly"), "{}", second);
+    let third = server.expect("third");
+    assert!(third.contains("other.img.beta"), "{}", third);
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn groups_select_clients() {
+    let bench = Bench::new("groups-select");
+    bench.file("first.client", "?serial /dev/ttyUSB0\n?use kernel\n");
+    bench.file("second.client", "?serial /dev/ttyUSB1\n?use kernel\n");
+    bench.file("third.client", "?serial /dev/ttyUSB2\n?use kernel\n");
+    let configuration = bench.configuration(&[], ":binary other.img other\n:client first\n:client second pi4\n:client third\n@group lab-\n:group pi first pi4\n:group all lab-pi third\n");
+
+    //
+    let mut server = bench.server(&["check", "--client", "lab-pi", &configuration]);
+    server.expect("2 of 3 client/s enabled");
+    assert_eq!(server.wait(), Some(0));
+
+    //
+    let mut server = bench.server(&["check", "--client", "lab-all", &configuration]);
+    server.expect("3 of 3 client/s enabled");
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn unknown_members_are_rejected() {
+    let bench = Bench::new("groups-unknown");
+    bench.file("first.client", "?serial /dev/ttyUSB0\n?use kernel\n");
+    let configuration = bench.configuration(&[], ":binary other.img other\n:client first\n:group lab first ghost\n");
+
+    //
+    let mut server = bench.server(&["check", &configuration]);
+    server.expect_error("no client, tag or group 'ghost' found");
+    assert_ne!(server.wait(), Some(0));
+}
+
+#[test]
+fn commands_are_sent_to_groups() {
+    let bench = Bench::new("groups-send");
+    let first = bench.file("first.device", "");
+    let second = bench.file("second.device", "");
+    bench.file("first.client", &format!("?serial {}\n?use kernel\n", first));
+    bench.file("second.client", &format!("?serial {}\n?use kernel\n", second));
+    let configuration = bench.configuration(&[], ":binary other.img other\n:client first pi4\n:client second pi4\n");
+
+    //
+    let mut server = bench.server(&["send", &configuration, "pi4", "reboot"]);
+    assert_eq!(server.wait(), Some(0));
+    for device in &[first, second] {
+        assert_eq!(fs::read_to_string(device).unwrap(), "reboot\r");
+    }
+}
+
+#[test]
+fn prefixed_clients_are_selected_by_name() {
+    let bench = Bench::new("groups-prefixed");
+    let device = bench.file("pi4.device", "");
+    bench.file("boards/pi4.client", &format!("?serial {}\n?use kernel\n", device));
+    bench.file("boards/other.client", "?serial /dev/ttyUSB0\n?use kernel\n");
+    let configuration = bench.configuration(&[], ":binary other.img other\n@client boards/\n:client pi4\n:client other\n:group lab pi4\n");
+
+    //
+    let mut server = bench.server(&["check", "--client", "pi4", &configuration]);
+    server.expect("1 of 2 client/s enabled");
+    assert_eq!(server.wait(), Some(0));
+
+    //
+    let mut server = bench.server(&["send", &configuration, "lab", "reboot"]);
+    assert_eq!(server.wait(), Some(0));
+    assert_eq!(fs::read_to_string(&device).unwrap(), "reboot\r");
+}
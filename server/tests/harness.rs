@@ -0,0 +1,162 @@
+mod common;
+
+use common::Bench;
+use std::fs;
+
+// script that loads, verifies and boots the kernel through the bootloader console
+const VERIFY_SCRIPT: &str = "
+:send
+:expect 5000 > $
+:send load
+:expect 5000 !
+:transmit
+:expect 5000 > $
+:send dump
+:expect 5000 6b 65 72 6e 65 6c
+:missed broken
+:send boot
+:pass
+:label broken
+:send reboot
+:fail dump did not match
+";
+
+#[test]
+fn test_mode_passes() {
+    let bench = Bench::new("test-mode-passes");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+    let report = bench.path("report.xml");
+    let spec = bench.file("boot.spec", &format!(":report junit {}\n:expect banner 5000 kernel running, 0x6 bytes\n:expect ready 5000 ready\n", report));
+
+    //
+    let mut server = bench.server(&["test", &configuration, "board", &spec]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("kernel started");
+    board.command("print ready");
+    server.expect("ok 2 - ready");
+    assert_eq!(server.wait(), Some(0));
+
+    //
+    let report = fs::read_to_string(report).unwrap();
+    assert!(report.contains("tests=\"2\" failures=\"0\" skipped=\"0\""));
+}
+
+#[test]
+fn test_mode_fails_on_timeout() {
+    let bench = Bench::new("test-mode-fails");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+    let report = bench.path("report.tap");
+    let spec = bench.file("boot.spec", &format!(":report tap {}\n:expect banner 5000 kernel running\n:expect shell 200 login:\n:expect prompt 200 \\$ \n", report));
+
+    //
+    let mut server = bench.server(&["test", &configuration, "board", &spec]);
+    let _board = bench.simulator(&pipes);
+    server.expect("not ok 2 - shell");
+    assert_eq!(server.wait(), Some(1));
+
+    //
+    let report = fs::read_to_string(report).unwrap();
+    assert!(report.contains("ok 1 - banner\n"));
+    assert!(report.contains("ok 3 - prompt # SKIP"));
+}
+
+#[test]
+fn test_mode_runs_the_named_client() {
+    let bench = Bench::new("test-mode-named");
+    bench.file("kernel.img.stable", "kernel");
+    bench.pipe_client("first", "?use kernel\n");
+    let pipes = bench.pipe_client("second", "?use kernel\n");
+    let configuration = bench.configuration(&["first lab", "second lab"], "");
+    let spec = bench.file("boot.spec", ":expect banner 5000 kernel running\n");
+
+    // a tag shared by both clients is rejected
+    let mut server = bench.server(&["test", &configuration, "lab", &spec]);
+    server.expect("'lab' matches 2 client/s");
+    assert_eq!(server.wait(), Some(2));
+
+    // a spec that expects nothing can't pass
+    let empty = bench.file("empty.spec", ":send boot\n");
+    let mut server = bench.server(&["test", &configuration, "second", &empty]);
+    server.expect("has no expect steps");
+    assert_eq!(server.wait(), Some(1));
+
+    //
+    let mut server = bench.server(&["test", &configuration, "second", &spec]);
+    let _board = bench.simulator(&pipes);
+    server.expect("[ harness ] [ second ] passed");
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn script_verifies_and_boots_kernel() {
+    let bench = Bench::new("script-passes");
+    bench.file("kernel.img.stable", "kernel");
+    bench.file("verify.script", VERIFY_SCRIPT);
+    let pipes = bench.pipe_client("board", "?use kernel\n?script verify\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    board.expect("command 'load'");
+    board.expect("received kernel of 0x6 bytes");
+    board.expect("command 'dump'");
+    board.expect("command 'boot'");
+    board.expect("kernel started");
+    server.expect("script passed");
+}
+
+#[test]
+fn script_branches_on_mismatch() {
+    let bench = Bench::new("script-fails");
+    bench.file("kernel.img.stable", "broken");
+    bench.file("verify.script", VERIFY_SCRIPT);
+    let pipes = bench.pipe_client("board", "?use kernel\n?script verify\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    board.expect("command 'dump'");
+    board.expect("command 'reboot'");
+    server.expect("script failed: dump did not match");
+}
+
+#[test]
+fn changed_binary_reboots_client() {
+    let bench = Bench::new("watcher");
+    bench.file("kernel.img.stable", "kernel");
+    bench.file("prompt.script", ":send\n:expect 5000 > $\n");
+    let pipes = bench.pipe_client("board", "?use kernel\n?reset enabled\n?script prompt\n");
+    let configuration = bench.configuration(&["board"], "?watch 100\n");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    server.expect("script passed");
+
+    //
+    bench.file("kernel.img.stable", "new kernel");
+    server.expect("kernel.img.stable' changed");
+    board.expect("command 'reboot'");
+    board.expect("booting");
+}
+
+#[test]
+fn scripts_keep_whitespace() {
+    let bench = Bench::new("script-whitespace");
+    bench.file("kernel.img.stable", "kernel");
+    bench.file("spacing.script", ":send\n:expect 5000 > $\n:send say   two  spaces\n:expect 5000 say   two  spaces\n:missed collapsed\n:pass\n:label collapsed\n:fail whitespace was collapsed\n");
+    let pipes = bench.pipe_client("board", "?use kernel\n?script spacing\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    // column aligned text is sent and matched as it's written
+    let server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    board.expect("command 'say   two  spaces'");
+    server.expect("script passed");
+}
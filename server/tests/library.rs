@@ -0,0 +1,339 @@
+#[macro_use]
+extern crate bmq5;
+
+mod common;
+
+use bmq5::client::protocol::{ Decoder, Message, Request, encode_transmission };
+use bmq5::parser::{ Parser, Item };
+use common::Bench;
+
+// decode everything a board sent
+fn decode(bytes: &[u8]) -> Vec<Message> {
+    let mut decoder = Decoder::new();
+    bytes.iter().filter_map(| byte | decoder.push(*byte)).collect()
+}
+
+#[test]
+fn requests_are_decoded() {
+    let messages = decode(b"ok?kboot?fdata/font.bin?");
+    let mut expected: Vec<Message> = b"ok".iter().map(| byte | Message::Console(*byte)).collect();
+    expected.push(Message::Request(Request::TransmitKernel));
+    expected.extend(b"boot".iter().map(| byte | Message::Console(*byte)));
+    expected.push(Message::Request(Request::TransmitFile(String::from("data/font.bin"))));
+    assert_eq!(messages, expected);
+}
+
+#[test]
+fn invalid_requests_terminate() {
+    assert_eq!(decode(b"?x"), vec![Message::Request(Request::Terminate(String::from("invalid request 'x'")))]);
+    let path = format!("?f{}?", "a".repeat(200));
+    let requests: Vec<Message> = decode(path.as_bytes()).into_iter().filter(| message | matches!(message, Message::Request(_))).collect();
+    assert_eq!(requests, vec![Message::Request(Request::Terminate(String::from("exeeded path length")))]);
+}
+
+#[test]
+fn requests_round_trip() {
+    for request in [Request::TransmitKernel, Request::TransmitFile(String::from("kernel.img"))] {
+        assert_eq!(decode(&request.encode()), vec![Message::Request(request)]);
+    }
+}
+
+#[test]
+fn transmission_has_size_header() {
+    assert_eq!(encode_transmission(b"abc"), vec![b'!', 0, 0, 0, 3, b'a', b'b', b'c']);
+}
+
+#[test]
+fn parser_calls_registered_keywords() {
+    let bench = Bench::new("library-parser");
+    let path = bench.file("tool.conf", "# tool settings\n?name board\n@entry prefix-\n:entry first 1\n+entry second 2\n");
+
+    //
+    let name: Item<Option<String>> = item!(None);
+    let entries: Item<Vec<(String, u64)>> = item!(Vec::new());
+    {
+        let mut parser = Parser::new(&path);
+        parser.register("name", true, Box::new(| stack, _ | {
+            stack.push_debug("no name specified");
+            *name.borrow_mut() = Some(stack.pop());
+        }));
+        parser.register("entry", false, Box::new(| stack, prefix | {
+            stack.push_debug("no entry specified");
+            let entry = format!("{}{}", prefix.unwrap(), stack.pop());
+            entries.borrow_mut().push((entry, stack.pop_u64()));
+        }));
+        parser.parse();
+    }
+
+    //
+    assert_eq!(unwrap_item!(name), Some(String::from("board")));
+    assert_eq!(unwrap_item!(entries), vec![(String::from("prefix-first"), 1), (String::from("prefix-second"), 2)]);
+}
+
+#[cfg(feature = "controller")]
+#[test]
+fn events_are_translated() {
+    use bmq5::controller::event::{ Event, Rules, State };
+    use bmq5::client::protocol::KeyState;
+
+    //
+    let bench = Bench::new("library-event");
+    let path = bench.file("keyboard.event", ":mode base\n:press 30 97\n:set 42 0\n:mode 10000000\n:press 30 65\n");
+    let mut rules = Rules::new();
+    rules.bytes_id = Some(vec![1]);
+    rules.bytes_value = Some(vec![2]);
+    rules.value_up = Some(0);
+    rules.value_down = Some(1);
+    let event = Event::new(rules, path, 1, &[String::from("board")]);
+
+    //
+    let mut state = State::new(0);
+    assert_eq!(event.translate(&[1, 30, 1], &mut state, false).and_then(| key | key.character), Some(97));
+    assert_eq!(event.translate(&[1, 42, 1], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(state.modifiers.bits(), 1);
+    assert_eq!(event.translate(&[1, 30, 1], &mut state, false).and_then(| key | key.character), Some(65));
+    assert_eq!(event.translate(&[1, 30, 0], &mut state, false).map(| key | key.state), Some(KeyState::Up));
+}
+
+#[cfg(feature = "controller")]
+#[test]
+fn layouts_are_extended_by_name() {
+    use bmq5::controller::event::{ Event, Rules, State };
+    use bmq5::controller::layout;
+
+    //
+    let bench = Bench::new("library-layout");
+    let path = bench.file("keyboard.event", ":mode base\n:press KEY_F1 escape\n:press * space\n:mode shift\n:press KEY_F1 hash\n");
+    let mut rules = Rules::new();
+    rules.bytes_id = Some(vec![1]);
+    rules.bytes_value = Some(vec![2]);
+    rules.value_up = Some(0);
+    rules.value_down = Some(1);
+    let event = Event::with_layout(rules, Some(path), layout::find("de"), 1, &[String::from("board")]);
+
+    // the layout is kept, the file only adds keys
+    let mut state = State::new(0);
+    assert_eq!(event.translate(&[1, 21, 1], &mut state, false).and_then(| key | key.character), Some(b'z'));
+    assert_eq!(event.translate(&[1, 59, 1], &mut state, false).and_then(| key | key.character), Some(0x1b));
+    assert_eq!(event.translate(&[1, 60, 1], &mut state, false).and_then(| key | key.character), Some(b' '));
+    assert_eq!(event.translate(&[1, 42, 1], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(event.translate(&[1, 21, 1], &mut state, false).and_then(| key | key.character), Some(b'Z'));
+    assert_eq!(event.translate(&[1, 59, 1], &mut state, false).and_then(| key | key.character), Some(b'#'));
+    assert_eq!(event.translate(&[1, 42, 0], &mut state, false).and_then(| key | key.character), None);
+
+    // control characters and characters outside of ascii
+    assert_eq!(event.translate(&[1, 29, 1], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(event.translate(&[1, 46, 1], &mut state, false).and_then(| key | key.character), Some(3));
+    assert_eq!(event.translate(&[1, 29, 0], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(event.translate(&[1, 12, 1], &mut state, false).and_then(| key | key.character), Some(0xdf));
+    assert!(layout::find("fr").is_none());
+}
+
+#[cfg(feature = "controller")]
+#[test]
+fn modes_are_chosen_by_named_modifiers() {
+    use bmq5::controller::event::{ Event, Rules, State };
+    use bmq5::controller::modifiers::Expression;
+
+    //
+    let names: Vec<String> = ["shift", "control", "alt", "fn", "layer1", "layer2", "layer3", "layer4", "layer5", "layer6"].iter().map(| name | String::from(*name)).collect();
+    let bench = Bench::new("library-modifiers");
+    let path = bench.file("keyboard.event", ":mode base\n:press 30 97\n:set 42 shift\n:toggle 58 layer6\n:set 29 1\n:mode layer6 & !(shift | control)\n:press 30 120\n:mode shift\n:press 30 65\n");
+    let mut rules = Rules::new();
+    rules.bytes_id = Some(vec![1]);
+    rules.bytes_value = Some(vec![2]);
+    rules.value_up = Some(0);
+    rules.value_down = Some(1);
+    rules.modifiers = names.clone();
+    let event = Event::new(rules, path, 1, &[String::from("board")]);
+
+    // modifiers past the first eight select modes as well
+    let mut state = State::new(0);
+    assert_eq!(event.translate(&[1, 58, 1], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(state.modifiers.bits(), 1 << 9);
+    assert_eq!(event.translate(&[1, 30, 1], &mut state, false).and_then(| key | key.character), Some(b'x'));
+    assert_eq!(event.translate(&[1, 42, 1], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(event.translate(&[1, 30, 1], &mut state, false).and_then(| key | key.character), Some(b'A'));
+    assert_eq!(event.translate(&[1, 42, 0], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(event.translate(&[1, 29, 1], &mut state, false).and_then(| key | key.character), None);
+    assert_eq!(event.translate(&[1, 30, 1], &mut state, false).and_then(| key | key.character), Some(b'a'));
+
+    // offsets are checked against the declared modifiers
+    assert_eq!(Expression::parse("shift & !fn", &names), Ok(Expression::And(Box::new(Expression::Modifier(0)), Box::new(Expression::Not(Box::new(Expression::Modifier(3)))))));
+    assert_eq!(Expression::parse("10", &names), Err(String::from("modifier offset 10 is out of range, there are only 10 modifiers")));
+    assert_eq!(Expression::parse("shift & meta", &names), Err(String::from("no modifier called 'meta'")));
+    assert_eq!(Expression::parse("(shift", &names), Err(String::from("expected ')' in mode expression")));
+}
+
+#[cfg(feature = "controller")]
+#[test]
+fn layers_fall_through() {
+    use bmq5::controller::event::{ Event, Rules, State };
+
+    //
+    let bench = Bench::new("library-layers");
+    let path = bench.file("keyboard.event", ":mode base\n:press KEY_A ba\n:press KEY_B bb\n:set KEY_LEFTSHIFT 0\n:hold KEY_RIGHTCTRL symbols\n:lock KEY_F12 numbers\n:oneshot KEY_RIGHTMETA symbols\n:mode 10000000\n:press KEY_A bA\n:layer symbols\n:press KEY_A bS\n:block KEY_B\n:layer numbers\n:press KEY_A b1\n");
+    let mut rules = Rules::new();
+    rules.bytes_id = Some(vec![1, 2]);
+    rules.bytes_value = Some(vec![3]);
+    rules.value_up = Some(0);
+    rules.value_down = Some(1);
+    let event = Event::new(rules, path, 1, &[String::from("board")]);
+    let press = | state: &mut State, code: u16, value: u8 | event.translate(&[1, (code >> 8) as u8, code as u8, value], state, false).and_then(| key | key.character);
+
+    // keys the shift mode doesn't set fall through to the base mode
+    let mut state = State::new(0);
+    assert_eq!(press(&mut state, 42, 1), None);
+    assert_eq!(press(&mut state, 30, 1), Some(b'A'));
+    assert_eq!(press(&mut state, 48, 1), Some(b'b'));
+    assert_eq!(press(&mut state, 42, 0), None);
+
+    // momentary layers are stacked on top of the modes, keys are released by the layer they were pressed in
+    assert_eq!(press(&mut state, 97, 1), None);
+    assert_eq!(press(&mut state, 30, 1), Some(b'S'));
+    assert_eq!(press(&mut state, 48, 1), None);
+    assert_eq!(press(&mut state, 97, 0), None);
+    assert_eq!(press(&mut state, 30, 0), Some(b'S'));
+    assert_eq!(press(&mut state, 30, 1), Some(b'a'));
+
+    // toggled layers stay until the key is pressed again, the highest active layer wins
+    assert_eq!(press(&mut state, 88, 1), None);
+    assert_eq!(press(&mut state, 30, 1), Some(b'1'));
+    assert_eq!(press(&mut state, 97, 1), None);
+    assert_eq!(press(&mut state, 30, 1), Some(b'1'));
+    assert_eq!(press(&mut state, 97, 0), None);
+    assert_eq!(press(&mut state, 88, 1), None);
+    assert_eq!(press(&mut state, 30, 1), Some(b'a'));
+
+    // one shot layers only last for the next key
+    assert_eq!(press(&mut state, 126, 1), None);
+    assert_eq!(press(&mut state, 30, 1), Some(b'S'));
+    assert_eq!(press(&mut state, 30, 1), Some(b'a'));
+}
+
+#[cfg(feature = "controller")]
+#[test]
+#[should_panic(expected = "no controller called 'pedal' in group 'desk'")]
+fn state_groups_need_known_controllers() {
+    use bmq5::controller::Manager;
+    Manager::new().share("desk", &[String::from("pedal")]);
+}
+
+#[cfg(feature = "controller")]
+#[test]
+fn axes_are_translated() {
+    use bmq5::controller::event::{ Event, Rules, State };
+    use bmq5::client::protocol::{ AxisEvent, KeyState, Payload };
+
+    //
+    let bench = Bench::new("library-axes");
+    let path = bench.file("gamepad.event", ":mode base\n:press KEY_LEFT bl\n:press KEY_RIGHT br\n:axis ABS_X 0 255\n:deadzone ABS_X 20\n:hysteresis ABS_X 10\n:direction ABS_X KEY_LEFT KEY_RIGHT\n:axis ABS_Y -100 100\n:deadzone ABS_Y 10\n:hysteresis ABS_Y 5\n:analog ABS_Y\n:hat ABS_HAT0X KEY_LEFT KEY_RIGHT\n");
+    let mut rules = Rules::new();
+    rules.evdev = true;
+    rules.value_up = Some(0);
+    rules.value_down = Some(1);
+    let event = Event::new(rules, path, 3, &[String::from("board")]);
+    let keys = | payloads: Vec<Payload> | payloads.into_iter().map(| payload | match payload {
+        Payload::Key(key)   => (key.character, key.state),
+        payload             => panic!("unexpected {:?}", payload),
+    }).collect::<Vec<_>>();
+
+    // sides are only left once the axis is back past the hysteresis
+    let mut state = State::new(0);
+    assert_eq!(keys(event.translate_events(0, Some(140), &mut state, false)), vec![]);
+    assert_eq!(keys(event.translate_events(0, Some(160), &mut state, false)), vec![(Some(b'r'), KeyState::Down)]);
+    assert_eq!(keys(event.translate_events(0, Some(142), &mut state, false)), vec![]);
+    assert_eq!(keys(event.translate_events(0, Some(0), &mut state, false)), vec![(Some(b'r'), KeyState::Up), (Some(b'l'), KeyState::Down)]);
+    assert_eq!(keys(event.translate_events(0, Some(127), &mut state, false)), vec![(Some(b'l'), KeyState::Up)]);
+
+    // hat switches press a key for either direction
+    assert_eq!(keys(event.translate_events(16, Some(-1), &mut state, false)), vec![(Some(b'l'), KeyState::Down)]);
+    assert_eq!(keys(event.translate_events(16, Some(0), &mut state, false)), vec![(Some(b'l'), KeyState::Up)]);
+
+    // positions are scaled from the edge of the deadzone, small movements are dropped
+    let axis = | position: i16 | vec![Payload::Axis(AxisEvent { code: 1, value: position })];
+    assert_eq!(event.translate_events(1, Some(5), &mut state, false), vec![]);
+    assert_eq!(event.translate_events(1, Some(55), &mut state, false), axis(16383));
+    assert_eq!(event.translate_events(1, Some(58), &mut state, false), vec![]);
+    assert_eq!(event.translate_events(1, Some(200), &mut state, false), axis(32767));
+    assert_eq!(event.translate_events(1, Some(-100), &mut state, false), axis(-32767));
+    assert_eq!(event.translate_events(1, Some(-8), &mut state, false), axis(0));
+}
+
+#[test]
+fn key_events_are_encoded() {
+    use bmq5::client::protocol::{ KeyEvent, KeyState, Events, AxisEvent, Payload };
+    let mut event = KeyEvent {
+        code:       0x110,
+        state:      KeyState::Down,
+        modifiers:  1,
+        character:  None,
+        sequence:   Vec::new(),
+    };
+    assert_eq!(event.encode(Events::Keys), vec![1, 0x01, 0x10, 0, 0, 0, 0, 0, 0, 0, 1, 0]);
+    assert_eq!(event.encode(Events::Characters), Vec::<u8>::new());
+
+    // boards that take characters only see presses and repeats
+    event.character = Some(b'A');
+    assert_eq!(event.encode(Events::Characters), vec![1, b'A']);
+    event.state = KeyState::Up;
+    assert_eq!(event.encode(Events::Characters), Vec::<u8>::new());
+    assert_eq!(event.encode(Events::Keys), vec![0, 0x01, 0x10, 0, 0, 0, 0, 0, 0, 0, 1, b'A']);
+
+    // sequences follow the key event, boards that take characters get them one by one
+    event.state = KeyState::Repeat;
+    event.character = None;
+    event.sequence = b"\x1b[A".to_vec();
+    assert_eq!(event.encode(Events::Characters), vec![1, 0x1b, 1, b'[', 1, b'A']);
+    assert_eq!(event.encode(Events::Keys), vec![2, 0x01, 0x10, 0, 0, 0, 0, 0, 0, 0, 1, 0, 3, 3, 0x1b, b'[', b'A']);
+
+    // modifiers past the first eight only reach boards that take key events
+    event.modifiers = 1 << 40 | 1;
+    event.sequence.clear();
+    assert_eq!(event.encode(Events::Keys), vec![2, 0x01, 0x10, 0, 0, 1, 0, 0, 0, 0, 1, 0]);
+
+    // axes only reach boards that take key events
+    let axis = Payload::Axis(AxisEvent { code: 1, value: -2 });
+    assert_eq!(axis.encode(Events::Keys), vec![4, 0, 1, 0xff, 0xfe]);
+    assert_eq!(axis.encode(Events::Characters), Vec::<u8>::new());
+}
+
+#[test]
+fn prefixed_clients_keep_their_history() {
+    use bmq5::store::Store;
+    use bmq5::parser::{ BinaryFile, Channel, Revision };
+
+    //
+    let bench = Bench::new("library-store");
+    let store = Store::new(format!("{}/", bench.path("store")), 2);
+    let first = store.insert("boards/pi4", "kernel.img", Channel::None, b"first");
+    store.insert("boards/pi4", "kernel.img", Channel::None, b"second");
+
+    // the object of the first kernel is still referenced by the nested history
+    assert_eq!(store.history("boards/pi4").len(), 2);
+    let binary = BinaryFile { path: String::from("kernel.img"), name: None, revision: Revision::Hash(first[..8].to_string()) };
+    assert_eq!(store.resolve("boards/pi4", &binary), Some(format!("{}/objects/{}", bench.path("store"), first)));
+
+    // a prefix shared by both kernels matches neither
+    let binary = BinaryFile { path: String::from("kernel.img"), name: None, revision: Revision::Hash(String::new()) };
+    assert_eq!(store.resolve("boards/pi4", &binary), None);
+
+    // files requested by the board are kept apart from the kernels
+    for file in &[b"config" as &[u8], b"overlay", b"initrd"] {
+        store.insert("boards/pi4", "files/extra", Channel::None, file);
+    }
+    let history = store.history("boards/pi4");
+    assert_eq!(history.iter().filter(| entry | entry.path == "kernel.img").count(), 2);
+    assert_eq!(history.iter().filter(| entry | entry.path == "files/extra").count(), 2);
+}
+
+#[test]
+fn control_commands_are_parsed() {
+    use bmq5::control::Command;
+    assert_eq!(Command::parse("enable first lab\n"), Ok(Command::Enable(vec![String::from("first"), String::from("lab")])));
+    assert_eq!(Command::parse("disable first"), Ok(Command::Disable(vec![String::from("first")])));
+    assert_eq!(Command::parse("status"), Ok(Command::Status(Vec::new())));
+    assert_eq!(Command::parse("status pi4"), Ok(Command::Status(vec![String::from("pi4")])));
+    assert!(Command::parse("enable").is_err());
+    assert!(Command::parse("reboot first").is_err());
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::Bench;
+use std::time::Duration;
+use std::fs;
+
+#[test]
+fn kernel_is_delivered() {
+    let bench = Bench::new("kernel-delivered");
+    let kernel: Vec<u8> = (0..4096).map(| index | (index * 7) as u8).collect();
+    bench.binary("kernel.img.stable", &kernel);
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("received kernel of 0x1000 bytes");
+    server.expect("transmitting 0x1000 bytes");
+
+    //
+    board.command(&format!("save {}", bench.path("received")));
+    board.expect("saved kernel");
+    assert_eq!(fs::read(bench.path("received")).unwrap(), kernel);
+}
+
+#[test]
+fn kernel_is_delivered_after_reboot() {
+    let bench = Bench::new("kernel-reboot");
+    bench.file("kernel.img.stable", "first");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let _server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("received kernel of 0x5 bytes");
+
+    //
+    bench.file("kernel.img.stable", "second kernel");
+    board.command("reset");
+    board.expect("received kernel of 0xd bytes");
+}
+
+#[test]
+fn channel_suffix_is_resolved() {
+    let bench = Bench::new("channel-suffix");
+    bench.file("kernel.img", "none");
+    bench.file("kernel.img.stable", "stable");
+    bench.file("kernel.img.beta", "beta-kernel");
+    bench.file("kernel.img.nightly", "nightly-kernel-build");
+
+    //
+    let channels = [("none", 4), ("stable", 6), ("beta", 11), ("nightly", 20)];
+    let names: Vec<String> = channels.iter().map(| (channel, _) | format!("board-{}", channel)).collect();
+    let pipes: Vec<(String, String)> = channels.iter().zip(&names).map(| ((channel, _), name) | {
+        bench.pipe_client(name, &format!("?use kernel\n?channel {}\n", channel))
+    }).collect();
+    let name_references: Vec<&str> = names.iter().map(| name | name.as_str()).collect();
+    let configuration = bench.configuration(&name_references, "");
+
+    // every board has to be there before the clients are started
+    let _server = bench.server(&[&configuration]);
+    let boards: Vec<_> = pipes.iter().map(| pipes | bench.simulator(pipes)).collect();
+    for ((_, size), board) in channels.iter().zip(&boards) {
+        board.expect(&format!("received kernel of 0x{:x} bytes", size));
+    }
+}
+
+#[test]
+fn binary_is_referenced_by_index() {
+    let bench = Bench::new("binary-index");
+    bench.file("kernel.img.stable", "indexed");
+    let pipes = bench.pipe_client("board", "?use 0\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let _server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    board.expect("received kernel of 0x7 bytes");
+}
+
+#[test]
+fn file_request_is_served() {
+    let bench = Bench::new("file-request");
+    bench.file("kernel.img.stable", "kernel");
+    let file = bench.file("data/font.bin", "0123456789abcdef0123");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("kernel started");
+
+    //
+    board.command(&format!("fetch {}", file));
+    board.expect(&format!("received '{}' of 0x14 bytes", file));
+    server.expect("transmitting 0x14 bytes");
+
+    // the client keeps running after a request
+    board.command(&format!("fetch {}", file));
+    board.expect(&format!("received '{}' of 0x14 bytes", file));
+}
+
+#[test]
+fn console_output_is_forwarded() {
+    let bench = Bench::new("console-output");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("kernel started");
+    board.command("print hello from the board");
+    server.expect("hello from the board");
+}
+
+#[test]
+fn revisions_are_stored_and_rolled_back() {
+    let bench = Bench::new("revisions");
+    let store = bench.path("store");
+    let pipes = bench.pipe_client("board", "?use kernel\n?revision previous\n");
+    let configuration = bench.file("configuration", &format!(
+        "?lookup {0}/\n?store {1} 4\n@binary {0}/\n:binary kernel.img kernel\n:client board\n",
+        bench.directory.display(), store));
+
+    // nothing was served yet, so there is no previous revision
+    bench.file("kernel.img.stable", "good kernel");
+    {
+        let server = bench.server(&[&configuration]);
+        let _board = bench.simulator(&pipes);
+        server.expect("no stored revision");
+    }
+
+    // serve the good kernel once
+    bench.pipe_settings("board", "?use kernel\n");
+    {
+        let _server = bench.server(&[&configuration]);
+        bench.simulator(&pipes).expect("received kernel of 0xb bytes");
+    }
+    assert_eq!(fs::read_to_string(format!("{}/clients/board", store)).unwrap().lines().count(), 1);
+
+    // after a bad build the previous revision is still the good kernel
+    bench.file("kernel.img.stable", "bad");
+    bench.pipe_settings("board", "?use kernel\n?revision previous\n");
+    let _server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    board.expect("received kernel of 0xb bytes");
+    board.reject("received kernel of 0x3 bytes", Duration::from_millis(200));
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::{ Bench, libc };
+
+#[test]
+fn terminate_stops_server() {
+    let bench = Bench::new("signal-terminate");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let mut server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    board.expect("kernel started");
+    server.expect("transmitted");
+
+    //
+    server.signal(libc::SIGTERM);
+    server.expect("received SIGTERM, shutting down");
+    server.expect("stopped");
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn interrupt_stops_server() {
+    let bench = Bench::new("signal-interrupt");
+    bench.file("kernel.img.stable", "kernel");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let mut server = bench.server(&[&configuration]);
+    let _board = bench.simulator(&pipes);
+    server.expect("started in serial mode");
+    server.signal(libc::SIGINT);
+    server.expect("received SIGINT, shutting down");
+    assert_eq!(server.wait(), Some(0));
+}
+
+#[test]
+fn hangup_reloads_configuration() {
+    let bench = Bench::new("signal-reload");
+    bench.file("kernel.img.stable", "stable");
+    bench.file("kernel.img.beta", "beta-kernel");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("received kernel of 0x6 bytes");
+
+    //
+    bench.pipe_settings("board", "?use kernel\n?channel beta\n");
+    server.signal(libc::SIGHUP);
+    server.expect("configuration reloaded");
+    board.command("reset");
+    board.expect("received kernel of 0xb bytes");
+}
+
+#[test]
+fn broken_configuration_is_not_reloaded() {
+    let bench = Bench::new("signal-broken");
+    bench.file("kernel.img.stable", "stable");
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let server = bench.server(&[&configuration]);
+    let mut board = bench.simulator(&pipes);
+    board.expect("received kernel of 0x6 bytes");
+
+    //
+    bench.file("configuration", ":invalid keyword\n");
+    server.signal(libc::SIGHUP);
+    server.expect("keeping the current configuration");
+    board.command("reset");
+    board.expect("received kernel of 0x6 bytes");
+}
+
+#[test]
+fn aborted_transfer_finishes_the_frame() {
+    let bench = Bench::new("signal-abort");
+    bench.binary("kernel.img.stable", &vec![0xaa; 0x1000000]);
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let mut server = bench.server(&[&configuration]);
+    let board = bench.simulator(&pipes);
+    server.expect("transmitting 0x1000000 bytes");
+    server.signal(libc::SIGTERM);
+    server.expect("received SIGTERM, shutting down");
+    server.signal(libc::SIGTERM);
+    server.expect("transmission aborted");
+
+    // the board gets a complete frame instead of waiting for the rest of it forever
+    board.expect("received kernel of 0x1000000 bytes");
+    assert_eq!(server.wait(), Some(1));
+}
+
+#[test]
+fn terminate_while_reloading_stops_server() {
+    let bench = Bench::new("signal-reload-terminate");
+    bench.binary("kernel.img.stable", &vec![0xaa; 0x1000000]);
+    let pipes = bench.pipe_client("board", "?use kernel\n");
+    let configuration = bench.configuration(&["board"], "");
+
+    //
+    let mut server = bench.server(&[&configuration]);
+    let _board = bench.simulator(&pipes);
+    server.expect("transmitting 0x1000000 bytes");
+    server.signal(libc::SIGHUP);
+    server.expect("reloading");
+    server.signal(libc::SIGTERM);
+    server.expect("received SIGTERM while reloading, shutting down");
+    server.expect("stopped");
+    assert_eq!(server.wait(), Some(1));
+}